@@ -0,0 +1,413 @@
+use anyhow::{Context, Result};
+use hmac::{Hmac, KeyInit, Mac};
+use rust_i18n::t;
+use sha2::Sha256;
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::trash::checksum::to_hex;
+use crate::trash::encoding::escape_field;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// `prev` value of the first record in a chain.
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Bytes read at a time when looking for the last record.
+const TAIL_CHUNK: u64 = 4096;
+
+/// Append-only, hash-chained log of trash, restore and purge operations.
+///
+/// Each record is a single line of tab-separated `key=value` fields. The
+/// `hash` field is an HMAC-SHA256 over the rest of the line, and the `prev`
+/// field repeats the hash of the record before it, so editing or removing
+/// any record breaks the chain from that point on.
+pub struct AuditLog {
+    path: PathBuf,
+    key: Vec<u8>,
+}
+
+/// Result of walking the chain with [`AuditLog::verify`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChainStatus {
+    Intact { records: usize },
+    Broken { record: usize, reason: ChainBreak },
+}
+
+/// Why a record failed verification.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChainBreak {
+    /// The line could not be parsed as a record.
+    Malformed,
+    /// The sequence number does not follow the previous record.
+    Sequence,
+    /// The `prev` field does not match the previous record's hash.
+    PrevHash,
+    /// The record's own hash does not match its content (or the key).
+    Hash,
+}
+
+impl ChainBreak {
+    pub fn message(&self) -> String {
+        match self {
+            ChainBreak::Malformed => t!("verify_log_reason_malformed"),
+            ChainBreak::Sequence => t!("verify_log_reason_sequence"),
+            ChainBreak::PrevHash => t!("verify_log_reason_prev"),
+            ChainBreak::Hash => t!("verify_log_reason_hash"),
+        }
+        .to_string()
+    }
+}
+
+/// A parsed log line.
+struct Record<'a> {
+    seq: u64,
+    prev: &'a str,
+    hash: &'a str,
+    /// Everything covered by `hash` (the line without its trailing `hash=` field).
+    body: &'a str,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf, key: Vec<u8>) -> Self {
+        Self { path, key }
+    }
+
+    /// Build the audit log configured by the environment.
+    ///
+    /// Auditing is enabled by `SAFERM_AUDIT_LOG` (path of the log file). The
+    /// HMAC key is read from `SAFERM_AUDIT_KEY_FILE` when set, so a root-owned
+    /// file can anchor the chain on shared machines; otherwise the key is empty
+    /// and the chain only detects edits, not a full rewrite.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(path) = std::env::var_os("SAFERM_AUDIT_LOG") else {
+            return Ok(None);
+        };
+
+        let key = match std::env::var_os("SAFERM_AUDIT_KEY_FILE") {
+            Some(key_file) => {
+                let mut key = fs::read(&key_file).with_context(|| {
+                    t!(
+                        "error_audit_key",
+                        path = Path::new(&key_file).display().to_string()
+                    )
+                })?;
+                // Keys written with `echo` end in a newline; don't make it significant.
+                while key.last().is_some_and(|b| *b == b'\n' || *b == b'\r') {
+                    key.pop();
+                }
+                key
+            }
+            None => Vec::new(),
+        };
+
+        Ok(Some(Self::new(PathBuf::from(path), key)))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a record for `op` on `path` (trash entry `id`, if known), chained to the
+    /// current last record.
+    pub fn record(&self, op: &str, backend: &str, path: &Path, id: Option<&OsStr>) -> Result<()> {
+        if let Some(parent) = self.path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create audit log dir: {:?}", parent))?;
+        }
+
+        let mut options = OpenOptions::new();
+        options.read(true).append(true).create(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options
+            .open(&self.path)
+            .with_context(|| format!("failed to open audit log: {:?}", self.path))?;
+
+        // Serialize concurrent saferm processes so the chain never forks.
+        file.lock()
+            .with_context(|| format!("failed to lock audit log: {:?}", self.path))?;
+
+        let (last_seq, prev) = last_link(&mut file)?;
+        let mut body = format!(
+            "seq={}\tts={}\top={}\tbackend={}\tpath={}",
+            last_seq + 1,
+            chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%:z"),
+            op,
+            backend,
+            escape_field(&path.to_string_lossy()),
+        );
+        if let Some(id) = id {
+            body.push_str(&format!("\tid={}", escape_field(&id.to_string_lossy())));
+        }
+        body.push_str(&format!("\tprev={}", prev));
+        let hash = self.mac_hex(&body);
+        writeln!(file, "{}\thash={}", body, hash)
+            .with_context(|| format!("failed to write audit log: {:?}", self.path))?;
+        Ok(())
+    }
+
+    /// Walk the chain from the first record and report the first broken link.
+    pub fn verify(&self) -> Result<ChainStatus> {
+        let file = match File::open(&self.path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(ChainStatus::Intact { records: 0 });
+            }
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("failed to open audit log: {:?}", self.path));
+            }
+        };
+
+        let mut expected_prev = GENESIS.to_string();
+        let mut records = 0;
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            let number = index + 1;
+            let broken = |reason| ChainStatus::Broken {
+                record: number,
+                reason,
+            };
+
+            let Some(record) = parse_record(&line) else {
+                return Ok(broken(ChainBreak::Malformed));
+            };
+            if record.seq != number as u64 {
+                return Ok(broken(ChainBreak::Sequence));
+            }
+            if record.prev != expected_prev {
+                return Ok(broken(ChainBreak::PrevHash));
+            }
+            if self.mac_hex(record.body) != record.hash {
+                return Ok(broken(ChainBreak::Hash));
+            }
+
+            expected_prev = record.hash.to_string();
+            records = number;
+        }
+
+        Ok(ChainStatus::Intact { records })
+    }
+
+    fn mac_hex(&self, body: &str) -> String {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(body.as_bytes());
+        to_hex(&mac.finalize().into_bytes())
+    }
+}
+
+/// Return (sequence number, hash) of the last record, or (0, GENESIS) for an empty log.
+///
+/// Only the tail of the log is read, so appending stays cheap as the log grows.
+/// A blank last line is corrupt, as it is for [`AuditLog::verify`].
+fn last_link(file: &mut File) -> Result<(u64, String)> {
+    let len = file.seek(SeekFrom::End(0))?;
+    if len == 0 {
+        return Ok((0, GENESIS.to_string()));
+    }
+
+    // Read backwards until the line before the last record ends, or the log starts
+    let mut tail = Vec::new();
+    let mut start = len;
+    let last_line = loop {
+        let chunk = TAIL_CHUNK.min(start);
+        start -= chunk;
+        let mut buf = vec![0; chunk as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut buf)?;
+        buf.extend_from_slice(&tail);
+        tail = buf;
+
+        let content = tail.strip_suffix(b"\n").unwrap_or(&tail);
+        match content.iter().rposition(|b| *b == b'\n') {
+            Some(newline) => break &content[newline + 1..],
+            None if start == 0 => break content,
+            None => {}
+        }
+    };
+
+    match std::str::from_utf8(last_line).ok().and_then(parse_record) {
+        Some(record) => Ok((record.seq, record.hash.to_string())),
+        None => anyhow::bail!("audit log is corrupt (run saferm --verify-log)"),
+    }
+}
+
+fn parse_record(line: &str) -> Option<Record<'_>> {
+    let (body, hash) = line.rsplit_once("\thash=")?;
+    let mut seq = None;
+    let mut prev = None;
+    for field in body.split('\t') {
+        let (key, value) = field.split_once('=')?;
+        match key {
+            "seq" => seq = value.parse().ok(),
+            "prev" => prev = Some(value),
+            _ => {}
+        }
+    }
+
+    Some(Record {
+        seq: seq?,
+        prev: prev?,
+        hash,
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup(key: &[u8]) -> (TempDir, AuditLog) {
+        let tmp = TempDir::new().unwrap();
+        let log = AuditLog::new(tmp.path().join("audit.log"), key.to_vec());
+        (tmp, log)
+    }
+
+    #[test]
+    fn test_record_and_verify() {
+        let (_tmp, log) = setup(b"seed");
+
+        log.record("trash", "managed", Path::new("/home/u/a.txt"), None)
+            .unwrap();
+        log.record(
+            "restore",
+            "managed",
+            Path::new("/home/u/a.txt"),
+            Some(OsStr::new("01HZX3J8Q5T0V6W7Y8Z9A0B1C2")),
+        )
+        .unwrap();
+        log.record("purge", "managed", Path::new("/home/u/b\tc.txt"), None)
+            .unwrap();
+
+        assert_eq!(log.verify().unwrap(), ChainStatus::Intact { records: 3 });
+    }
+
+    #[test]
+    fn test_verify_missing_log() {
+        let (_tmp, log) = setup(b"");
+        assert_eq!(log.verify().unwrap(), ChainStatus::Intact { records: 0 });
+    }
+
+    #[test]
+    fn test_edited_record_is_detected() {
+        let (_tmp, log) = setup(b"seed");
+        log.record("trash", "managed", Path::new("/secret.txt"), None)
+            .unwrap();
+        log.record("trash", "managed", Path::new("/other.txt"), None)
+            .unwrap();
+
+        let content = fs::read_to_string(log.path()).unwrap();
+        fs::write(log.path(), content.replace("/secret.txt", "/boring.txt")).unwrap();
+
+        assert_eq!(
+            log.verify().unwrap(),
+            ChainStatus::Broken {
+                record: 1,
+                reason: ChainBreak::Hash
+            }
+        );
+    }
+
+    #[test]
+    fn test_removed_record_is_detected() {
+        let (_tmp, log) = setup(b"seed");
+        for name in ["/a", "/b", "/c"] {
+            log.record("trash", "managed", Path::new(name), None)
+                .unwrap();
+        }
+
+        let content = fs::read_to_string(log.path()).unwrap();
+        let kept: Vec<_> = content
+            .lines()
+            .enumerate()
+            .filter(|(i, _)| *i != 1)
+            .map(|(_, l)| format!("{}\n", l))
+            .collect();
+        fs::write(log.path(), kept.concat()).unwrap();
+
+        assert_eq!(
+            log.verify().unwrap(),
+            ChainStatus::Broken {
+                record: 2,
+                reason: ChainBreak::Sequence
+            }
+        );
+    }
+
+    #[test]
+    fn test_wrong_key_is_detected() {
+        let (tmp, log) = setup(b"seed");
+        log.record("trash", "managed", Path::new("/a"), None)
+            .unwrap();
+
+        let other = AuditLog::new(tmp.path().join("audit.log"), b"forged".to_vec());
+        assert_eq!(
+            other.verify().unwrap(),
+            ChainStatus::Broken {
+                record: 1,
+                reason: ChainBreak::Hash
+            }
+        );
+    }
+
+    #[test]
+    fn test_malformed_line_is_detected() {
+        let (_tmp, log) = setup(b"");
+        log.record("trash", "managed", Path::new("/a"), None)
+            .unwrap();
+        let mut file = OpenOptions::new().append(true).open(log.path()).unwrap();
+        writeln!(file, "garbage").unwrap();
+
+        assert_eq!(
+            log.verify().unwrap(),
+            ChainStatus::Broken {
+                record: 2,
+                reason: ChainBreak::Malformed
+            }
+        );
+    }
+
+    #[test]
+    fn test_records_chain_past_long_lines() {
+        let (_tmp, log) = setup(b"seed");
+        let long = format!("/{}", "x".repeat(3 * TAIL_CHUNK as usize));
+        log.record("trash", "managed", Path::new(&long), None)
+            .unwrap();
+        log.record("trash", "managed", Path::new(&long), None)
+            .unwrap();
+        log.record("trash", "managed", Path::new("/short"), None)
+            .unwrap();
+
+        assert_eq!(log.verify().unwrap(), ChainStatus::Intact { records: 3 });
+    }
+
+    #[test]
+    fn test_blank_line_is_corrupt_for_record_and_verify() {
+        let (_tmp, log) = setup(b"");
+        log.record("trash", "managed", Path::new("/a"), None)
+            .unwrap();
+        let mut file = OpenOptions::new().append(true).open(log.path()).unwrap();
+        writeln!(file).unwrap();
+
+        assert!(
+            log.record("trash", "managed", Path::new("/b"), None)
+                .is_err()
+        );
+        assert_eq!(
+            log.verify().unwrap(),
+            ChainStatus::Broken {
+                record: 2,
+                reason: ChainBreak::Malformed
+            }
+        );
+    }
+}
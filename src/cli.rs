@@ -1,12 +1,12 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use std::path::PathBuf;
 
 /// A safe rm replacement — moves files to trash instead of permanent deletion.
 #[derive(Parser, Debug)]
 #[command(name = "saferm", version, about)]
 pub struct Cli {
-    /// Files or directories to remove (or filter pattern when used with --restore)
-    #[arg(required_unless_present_any = ["cleanup", "restore"])]
+    /// Files or directories to remove (or filter pattern when used with --restore, --purge, --pin, --unpin, --list or --verify)
+    #[arg(required_unless_present_any = ["cleanup", "restore", "purge", "pin", "unpin", "list", "versions", "verify", "verify_log", "fsck", "migrate"])]
     pub targets: Vec<PathBuf>,
 
     /// Remove directories and their contents recursively
@@ -31,11 +31,157 @@ pub struct Cli {
     #[arg(short, long)]
     pub verbose: bool,
 
+    /// Use the named bin of the managed trash instead of the default one
+    #[arg(long, value_name = "NAME", value_parser = parse_bin)]
+    pub bin: Option<String>,
+
+    /// Record why the targets are trashed
+    #[arg(
+        long,
+        value_name = "TEXT",
+        conflicts_with_all = ["cleanup", "restore", "purge", "pin", "unpin", "list", "versions", "verify", "verify_log", "fsck", "migrate"]
+    )]
+    pub reason: Option<String>,
+
+    /// Tag the trashed targets (repeatable), or select items by tag with
+    /// --restore, --purge, --pin, --unpin and --list
+    #[arg(
+        long = "tag",
+        value_name = "TAG",
+        value_parser = parse_tag,
+        conflicts_with_all = ["cleanup", "versions", "verify", "verify_log", "fsck", "migrate", "ids"]
+    )]
+    pub tags: Vec<String>,
+
     /// Empty the trash
-    #[arg(long, conflicts_with = "restore")]
+    #[arg(long, group = "delete", conflicts_with = "restore")]
     pub cleanup: bool,
 
+    /// Permanently delete selected items from the trash
+    #[arg(long, group = "delete", group = "select", conflicts_with = "restore")]
+    pub purge: bool,
+
+    /// With --cleanup or --purge: overwrite file contents before deleting them
+    #[arg(long, requires = "delete")]
+    pub shred: bool,
+
+    /// Number of overwrite passes for --shred
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 3,
+        value_parser = clap::value_parser!(u32).range(1..),
+        requires = "shred"
+    )]
+    pub shred_passes: u32,
+
+    /// With --cleanup: also delete pinned items
+    #[arg(long, requires = "cleanup")]
+    pub include_pinned: bool,
+
     /// Restore files from the trash to their original location
-    #[arg(long)]
+    #[arg(long, group = "select")]
     pub restore: bool,
+
+    /// Pin trashed items so --cleanup keeps them
+    #[arg(long, group = "select", conflicts_with_all = ["cleanup", "list"])]
+    pub pin: bool,
+
+    /// Unpin trashed items
+    #[arg(long, group = "select", conflicts_with_all = ["cleanup", "list"])]
+    pub unpin: bool,
+
+    /// List trashed items (optionally filtered by a pattern)
+    #[arg(long, conflicts_with_all = ["cleanup", "restore", "purge"])]
+    pub list: bool,
+
+    /// Sort order for --list
+    #[arg(long, value_enum, default_value_t = SortOrder::Date, requires = "list")]
+    pub sort: SortOrder,
+
+    /// Show at most this many items with --list
+    #[arg(long, value_name = "N", requires = "list")]
+    pub limit: Option<usize>,
+
+    /// Skip this many items with --list
+    #[arg(long, value_name = "N", default_value_t = 0, requires = "list")]
+    pub offset: usize,
+
+    /// Select trash entries by id instead of a filter pattern (with --restore, --purge, --pin or --unpin)
+    #[arg(
+        long = "id",
+        value_name = "ID",
+        requires = "select",
+        conflicts_with = "targets"
+    )]
+    pub ids: Vec<String>,
+
+    /// List every trashed version of a path and restore one (the latest with -f)
+    #[arg(long, conflicts_with_all = ["cleanup", "restore", "purge", "pin", "unpin", "list"])]
+    pub versions: bool,
+
+    /// Check trashed items against the content hash recorded at trash time
+    #[arg(long, conflicts_with_all = ["cleanup", "restore", "purge", "pin", "unpin", "list", "versions"])]
+    pub verify: bool,
+
+    /// Check the trash for orphaned or broken entries and offer repairs
+    #[arg(long, conflicts_with_all = ["cleanup", "restore", "purge", "pin", "unpin", "list", "versions", "verify", "verify_log"])]
+    pub fsck: bool,
+
+    /// Check the audit log (SAFERM_AUDIT_LOG) for tampering
+    #[arg(long, conflicts_with_all = ["cleanup", "restore", "purge", "pin", "unpin", "list", "versions", "verify"])]
+    pub verify_log: bool,
+
+    /// Move trashed items from one backend to another, keeping their original
+    /// paths and deletion dates
+    #[arg(
+        long,
+        requires_all = ["from", "to"],
+        conflicts_with_all = ["cleanup", "restore", "purge", "pin", "unpin", "list", "versions", "verify", "verify_log", "fsck"]
+    )]
+    pub migrate: bool,
+
+    /// Backend to migrate items from
+    #[arg(long, value_enum, value_name = "BACKEND", requires = "migrate")]
+    pub from: Option<MigrateBackend>,
+
+    /// Backend to migrate items to
+    #[arg(long, value_enum, value_name = "BACKEND", requires = "migrate")]
+    pub to: Option<MigrateBackend>,
+
+    /// With --migrate: show what would move without moving anything
+    #[arg(long, requires = "migrate")]
+    pub dry_run: bool,
+}
+
+fn parse_bin(name: &str) -> Result<String, String> {
+    crate::trash::retention::validate_bin_name(name)
+        .map(|()| name.to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn parse_tag(tag: &str) -> Result<String, String> {
+    crate::trash::validate_tag(tag)
+        .map(|()| tag.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Sort order for `--list`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Newest first
+    Date,
+    /// Original path, A to Z
+    Path,
+    /// Largest first
+    Size,
+}
+
+/// Backends `--migrate` moves items between.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MigrateBackend {
+    /// saferm's own trash directory (the --bin bin, if given)
+    Managed,
+    /// The desktop trash
+    Os,
 }
@@ -0,0 +1,219 @@
+//! Errors of saferm's library API.
+//!
+//! Errors carry data rather than messages: match on the variant or on
+//! [`Error::kind`], and get the path and underlying [`io::Error`] from the
+//! error itself. Messages are only produced when an error is displayed, in
+//! the locale `rust_i18n` is set to (the CLI picks it from the system locale).
+
+use rust_i18n::t;
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Result of saferm's library API.
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// A target to remove does not exist.
+    NotFound { path: PathBuf },
+    /// A directory was given to remove without `-r` (or `-d`).
+    IsADirectory { path: PathBuf },
+    /// A directory given with `-d` alone is not empty.
+    DirectoryNotEmpty { path: PathBuf },
+    /// Moving `path` into or out of the trash failed.
+    Io { path: PathBuf, source: io::Error },
+    /// No trashed item has this id.
+    IdNotFound { id: OsString },
+    /// Something is already at `path`, where an item would be restored.
+    Conflict { path: PathBuf },
+    /// A trashed item no longer matches the hash recorded when it was trashed.
+    Corrupted { id: OsString },
+    /// A tag given with `--tag` that can't be stored.
+    InvalidTag { tag: String },
+    /// The backend can't do this.
+    Unsupported {
+        operation: &'static str,
+        backend: &'static str,
+    },
+    /// Anything else, with its context chain.
+    Other(anyhow::Error),
+}
+
+/// What went wrong, for callers that only need to branch on the cause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    NotFound,
+    IsADirectory,
+    DirectoryNotEmpty,
+    PermissionDenied,
+    /// A move across filesystems
+    CrossDevice,
+    /// Something is already at the destination
+    Conflict,
+    IdNotFound,
+    Corrupted,
+    InvalidTag,
+    Unsupported,
+    Other,
+}
+
+impl ErrorKind {
+    fn of_io(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => ErrorKind::NotFound,
+            io::ErrorKind::IsADirectory => ErrorKind::IsADirectory,
+            io::ErrorKind::DirectoryNotEmpty => ErrorKind::DirectoryNotEmpty,
+            io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem => {
+                ErrorKind::PermissionDenied
+            }
+            io::ErrorKind::CrossesDevices => ErrorKind::CrossDevice,
+            io::ErrorKind::AlreadyExists => ErrorKind::Conflict,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+impl Error {
+    /// An error with just a message, for failures that have no variant.
+    pub fn msg(message: impl fmt::Display) -> Self {
+        Error::Other(anyhow::anyhow!(message.to_string()))
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::NotFound { .. } => ErrorKind::NotFound,
+            Error::IsADirectory { .. } => ErrorKind::IsADirectory,
+            Error::DirectoryNotEmpty { .. } => ErrorKind::DirectoryNotEmpty,
+            Error::Io { source, .. } => ErrorKind::of_io(source),
+            Error::IdNotFound { .. } => ErrorKind::IdNotFound,
+            Error::Conflict { .. } => ErrorKind::Conflict,
+            Error::Corrupted { .. } => ErrorKind::Corrupted,
+            Error::InvalidTag { .. } => ErrorKind::InvalidTag,
+            Error::Unsupported { .. } => ErrorKind::Unsupported,
+            // The cause may sit below added context
+            Error::Other(error) => error
+                .chain()
+                .find_map(|cause| {
+                    if let Some(inner) = cause.downcast_ref::<Error>() {
+                        Some(inner.kind())
+                    } else {
+                        cause.downcast_ref::<io::Error>().map(ErrorKind::of_io)
+                    }
+                })
+                .unwrap_or(ErrorKind::Other),
+        }
+    }
+
+    /// The path the error is about, if it names one.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::NotFound { path }
+            | Error::IsADirectory { path }
+            | Error::DirectoryNotEmpty { path }
+            | Error::Io { path, .. }
+            | Error::Conflict { path } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The underlying I/O error, if there is one.
+    pub fn io_error(&self) -> Option<&io::Error> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Other(error) => error.chain().find_map(|cause| {
+                cause.downcast_ref::<io::Error>().or_else(|| {
+                    cause
+                        .downcast_ref::<Error>()
+                        .and_then(|inner| inner.io_error())
+                })
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Error::NotFound { path } => t!("error_not_found", name = path.display().to_string()),
+            Error::IsADirectory { path } => t!("error_is_dir", name = path.display().to_string()),
+            Error::DirectoryNotEmpty { path } => {
+                t!("error_dir_not_empty", name = path.display().to_string())
+            }
+            Error::Io { path, source } => t!(
+                "error_io",
+                path = path.display().to_string(),
+                reason = source.to_string()
+            ),
+            Error::IdNotFound { id } => t!("error_id_not_found", id = id.to_string_lossy()),
+            Error::Conflict { path } => {
+                t!(
+                    "error_destination_exists",
+                    path = path.display().to_string()
+                )
+            }
+            Error::Corrupted { id } => t!("error_integrity_mismatch", name = id.to_string_lossy()),
+            Error::InvalidTag { tag } => t!("error_invalid_tag", tag = tag),
+            Error::Unsupported { operation, backend } => t!(
+                "error_unsupported",
+                operation = operation,
+                backend = backend
+            ),
+            Error::Other(error) => return fmt::Display::fmt(error, f),
+        };
+        f.write_str(&message)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Other(error) => error.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(error: anyhow::Error) -> Self {
+        error.downcast::<Error>().unwrap_or_else(Error::Other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_kind_survives_context() {
+        let io = io::Error::from(io::ErrorKind::PermissionDenied);
+        let wrapped = Err::<(), _>(io).context("failed to trash 'x'").unwrap_err();
+        let error = Error::from(wrapped);
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+        assert!(error.io_error().is_some());
+        assert_eq!(error.to_string(), "failed to trash 'x'");
+
+        let typed = anyhow::Error::from(Error::IdNotFound { id: "x".into() });
+        assert!(matches!(Error::from(typed), Error::IdNotFound { .. }));
+
+        let nested = Err::<(), _>(Error::Io {
+            path: PathBuf::from("/a"),
+            source: io::Error::from(io::ErrorKind::CrossesDevices),
+        })
+        .context("while restoring")
+        .unwrap_err();
+        assert_eq!(Error::from(nested).kind(), ErrorKind::CrossDevice);
+
+        let conflict = Error::Conflict {
+            path: PathBuf::from("/a"),
+        };
+        assert_eq!(conflict.kind(), ErrorKind::Conflict);
+        assert_eq!(conflict.path(), Some(Path::new("/a")));
+    }
+}
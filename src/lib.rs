@@ -1,7 +1,16 @@
 rust_i18n::i18n!("locales", fallback = "en");
 
+pub mod audit;
+#[cfg(feature = "cli")]
 pub mod cli;
+pub mod error;
+#[cfg(feature = "cli")]
 pub mod i18n;
+pub mod observer;
 pub mod ops;
 pub mod prompt;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod trash;
+
+pub use error::{Error, ErrorKind, Result};
@@ -10,7 +10,7 @@ fn main() -> Result<()> {
     saferm::i18n::init();
 
     let cli = Cli::parse();
-    let handler = trash::create_handler();
+    let handler = trash::create_handler(cli.bin.as_deref());
     let prompter = InteractivePrompter;
 
     let all_ok = ops::run(&cli, handler.as_ref(), &prompter)?;
@@ -0,0 +1,226 @@
+//! Progress reporting for the core operations.
+//!
+//! [`crate::ops::remove`] and [`crate::ops::restore`] report each step to an
+//! [`Observer`]. The CLI's output for those steps comes from [`Printer`];
+//! other front ends can show progress their own way.
+
+use rust_i18n::t;
+use std::ffi::OsStr;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::trash::routing::DIRECT;
+use crate::trash::{RestorableItem, Route};
+
+/// A step of a removal or restore.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Event<'a> {
+    /// Work on a removal target begins.
+    TargetStarted { path: &'a Path },
+    /// The prompter is about to be asked whether to remove `path`.
+    PromptNeeded { path: &'a Path },
+    /// `path` is gone: trashed by `route`'s backend (as entry `id`, if the
+    /// backend reports one), deleted for good by the direct route, or, for a
+    /// symlink, removed.
+    Trashed {
+        path: &'a Path,
+        route: &'a Route,
+        id: Option<&'a OsStr>,
+        symlink: bool,
+    },
+    /// The removal target `path` was left alone.
+    Skipped { path: &'a Path, reason: SkipReason },
+    /// Removing `path` failed.
+    Failed { path: &'a Path, error: &'a Error },
+    /// A move to another filesystem has copied `copied` of `total` bytes of `path`.
+    BytesCopied {
+        path: &'a Path,
+        copied: u64,
+        total: u64,
+    },
+    /// Retention limits deleted `item` for good.
+    Evicted { item: &'a RestorableItem },
+    /// Something already exists where `item` would be restored.
+    RestoreConflict {
+        item: &'a RestorableItem,
+        destination: &'a Path,
+    },
+    /// `item` was not restored because of a conflict at `destination`.
+    RestoreSkipped {
+        item: &'a RestorableItem,
+        destination: &'a Path,
+        reason: SkipReason,
+    },
+    /// `item` is back, at `destination`.
+    Restored {
+        item: &'a RestorableItem,
+        destination: &'a Path,
+    },
+    /// Restoring `item` failed.
+    RestoreFailed {
+        item: &'a RestorableItem,
+        error: &'a Error,
+    },
+    /// No trashed item matches the restore filters.
+    NothingToRestore,
+    /// The prompter selected no items to restore.
+    RestoreCancelled,
+    /// No trashed item has the requested `id`.
+    UnknownId { id: &'a str },
+    /// Cleanup found nothing to delete, apart from `pinned` pinned entries.
+    NothingToCleanUp { pinned: usize },
+    /// The prompter declined to empty the trash.
+    CleanupCancelled,
+    /// The trash was emptied, except for `pinned_kept` pinned entries.
+    CleanedUp { pinned_kept: usize },
+    /// Something went wrong that doesn't fail the operation, such as applying
+    /// retention limits or removing a backup.
+    Warning { message: &'a str },
+}
+
+/// Why a target or item was left alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SkipReason {
+    /// The target doesn't exist (and `force` was set).
+    Missing,
+    /// The prompter said no.
+    Declined,
+    /// A restore would have overwritten something, without a prompt to ask.
+    Conflict,
+}
+
+/// Receives the [`Event`]s of an operation as they happen, possibly from
+/// several threads (see [`crate::ops::remove`]).
+pub trait Observer: Sync {
+    fn notify(&self, event: &Event<'_>);
+}
+
+impl<F: Fn(&Event<'_>) + Sync> Observer for F {
+    fn notify(&self, event: &Event<'_>) {
+        self(event)
+    }
+}
+
+/// Ignores every event.
+pub struct NoopObserver;
+
+impl Observer for NoopObserver {
+    fn notify(&self, _event: &Event<'_>) {}
+}
+
+/// Prints events the way the `saferm` command does: failures, warnings,
+/// skipped conflicts, restore and cleanup notices always, everything else
+/// with `verbose` (`-v`).
+pub struct Printer {
+    pub verbose: bool,
+}
+
+impl Observer for Printer {
+    fn notify(&self, event: &Event<'_>) {
+        match *event {
+            Event::Failed { error, .. } => eprintln!("saferm: {}", error),
+            Event::RestoreFailed { item, error } => eprintln!(
+                "saferm: {}",
+                t!(
+                    "error_restore_failed",
+                    name = item.display_name.to_string_lossy(),
+                    reason = error.to_string()
+                )
+            ),
+            Event::RestoreSkipped {
+                item,
+                reason: SkipReason::Conflict,
+                ..
+            } => eprintln!("{}", restore_skipped(item)),
+            Event::NothingToRestore => println!("{}", t!("restore_nothing")),
+            Event::RestoreCancelled => println!("{}", t!("restore_cancelled")),
+            Event::UnknownId { id } => eprintln!("saferm: {}", t!("error_id_not_found", id = id)),
+            Event::Warning { message } => eprintln!("saferm: warning: {}", message),
+            Event::NothingToCleanUp { pinned: 0 } => println!("{}", t!("cleanup_nothing")),
+            Event::NothingToCleanUp { pinned } => {
+                println!("{}", t!("cleanup_only_pinned", count = pinned))
+            }
+            Event::CleanupCancelled => println!("{}", t!("cleanup_cancelled")),
+            Event::CleanedUp { pinned_kept } => {
+                println!("{}", t!("cleanup_success"));
+                if pinned_kept > 0 {
+                    println!("{}", t!("cleanup_pinned_kept", count = pinned_kept));
+                }
+            }
+            _ if !self.verbose => {}
+            Event::Skipped {
+                path,
+                reason: SkipReason::Declined,
+            } => eprintln!("{}", t!("cancelled", name = path.display().to_string())),
+            Event::RestoreSkipped { item, .. } => eprintln!("{}", restore_skipped(item)),
+            Event::Trashed {
+                path,
+                route,
+                id,
+                symlink,
+            } => print_trashed(path, route, id, symlink),
+            Event::Evicted { item } => println!(
+                "{}",
+                t!(
+                    "verbose_evicted",
+                    name = item.original_path.display().to_string(),
+                    id = item.id.to_string_lossy()
+                )
+            ),
+            Event::Restored { item, destination } => println!(
+                "{}",
+                t!(
+                    "restore_success",
+                    name = item.display_name.to_string_lossy(),
+                    path = destination.display().to_string()
+                )
+            ),
+            _ => {}
+        }
+    }
+}
+
+fn restore_skipped(item: &RestorableItem) -> String {
+    t!(
+        "restore_skipped",
+        name = item.display_name.to_string_lossy()
+    )
+    .to_string()
+}
+
+fn print_trashed(path: &Path, route: &Route, id: Option<&OsStr>, symlink: bool) {
+    let name = path.display().to_string();
+    if let Some(reason) = &route.reason {
+        println!(
+            "{}",
+            t!(
+                "verbose_route",
+                name = name.clone(),
+                backend = route.backend,
+                reason = reason
+            )
+        );
+    }
+    let backend = route.backend;
+    let msg = if symlink {
+        t!("verbose_removed_symlink", name = name)
+    } else if backend == DIRECT {
+        t!("verbose_deleted_direct", name = name)
+    } else if let Some(id) = id {
+        t!(
+            "verbose_trashed_with_id",
+            name = name,
+            backend = backend,
+            id = id.to_string_lossy()
+        )
+    } else {
+        t!(
+            "verbose_trashed_with_backend",
+            name = name,
+            backend = backend
+        )
+    };
+    println!("{}", msg);
+}
@@ -0,0 +1,825 @@
+//! `saferm`'s command-line modes on top of the core operations.
+
+use rust_i18n::t;
+use std::path::{Path, PathBuf};
+
+use super::{
+    RemoveOptions, RestoreOptions, annotation_suffix, backend_labels, format_deleted_at,
+    item_labels, items_by_id, pinned_marker, remove, restore, restore_item,
+};
+use crate::audit::{AuditLog, ChainStatus};
+use crate::cli::{Cli, SortOrder};
+use crate::error::{Error, Result};
+use crate::observer::Printer;
+use crate::prompt::Prompter;
+use crate::trash::managed::{FALLBACK_DATA_DIR, ManagedTrash};
+use crate::trash::shred::Shredder;
+use crate::trash::{
+    Annotation, Integrity, ListQuery, RestorableItem, SortKey, TrashHandler, TrashProblem,
+};
+
+pub fn run(cli: &Cli, handler: &dyn TrashHandler, prompter: &dyn Prompter) -> Result<bool> {
+    if ManagedTrash::default_base_dir().starts_with(FALLBACK_DATA_DIR) {
+        eprintln!(
+            "saferm: warning: could not determine data directory, using {}",
+            ManagedTrash::default_base_dir().display()
+        );
+    }
+
+    if cli.cleanup {
+        let shredder = cli
+            .shred
+            .then(|| Shredder::new(cli.shred_passes, cli.verbose));
+        handler.cleanup_observed(
+            prompter,
+            shredder.as_ref(),
+            cli.include_pinned,
+            &Printer {
+                verbose: cli.verbose,
+            },
+        )?;
+        return Ok(true);
+    }
+
+    if cli.restore {
+        return run_restore(cli, handler, prompter);
+    }
+
+    if cli.purge {
+        return run_purge(cli, handler, prompter);
+    }
+
+    if cli.pin || cli.unpin {
+        return run_pin(cli, handler, prompter, cli.pin);
+    }
+
+    if cli.list {
+        return run_list(cli, handler);
+    }
+
+    if cli.versions {
+        return run_versions(cli, handler, prompter);
+    }
+
+    if cli.verify {
+        return run_verify(cli, handler);
+    }
+
+    if cli.verify_log {
+        return run_verify_log();
+    }
+
+    if cli.fsck {
+        return run_fsck(cli, handler, prompter);
+    }
+
+    if cli.migrate {
+        return run_migrate(cli, prompter);
+    }
+
+    let options = RemoveOptions {
+        recursive: cli.recursive,
+        dir: cli.dir,
+        force: cli.force,
+        interactive: is_tty(),
+        annotation: Annotation {
+            reason: cli.reason.clone(),
+            tags: cli.tags.clone(),
+        },
+        jobs: jobs(),
+    };
+    remove(
+        &cli.targets,
+        &options,
+        handler,
+        prompter,
+        &Printer {
+            verbose: cli.verbose,
+        },
+    )
+}
+
+fn is_tty() -> bool {
+    std::io::IsTerminal::is_terminal(&std::io::stdin())
+}
+
+/// Upper bound on the default number of parallel removals; more threads
+/// mostly queue on the same disk.
+const MAX_DEFAULT_JOBS: usize = 8;
+
+/// Parallel removals for non-interactive runs: `SAFERM_JOBS`, or the number
+/// of CPUs up to [`MAX_DEFAULT_JOBS`].
+fn jobs() -> usize {
+    let default = || {
+        std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(MAX_DEFAULT_JOBS)
+    };
+    match std::env::var("SAFERM_JOBS") {
+        Ok(value) => match value.trim().parse() {
+            Ok(jobs) if jobs > 0 => jobs,
+            _ => {
+                eprintln!("saferm: warning: invalid SAFERM_JOBS '{}', ignoring", value);
+                default()
+            }
+        },
+        Err(_) => default(),
+    }
+}
+
+/// Restore options for `--restore` and `--versions`.
+fn restore_options(cli: &Cli) -> RestoreOptions {
+    RestoreOptions {
+        filter: None,
+        tag: None,
+        ids: cli.ids.clone(),
+        force: cli.force,
+        interactive: is_tty(),
+    }
+}
+
+fn run_restore(cli: &Cli, handler: &dyn TrashHandler, prompter: &dyn Prompter) -> Result<bool> {
+    let mut options = restore_options(cli);
+    if options.ids.is_empty() {
+        options.filter = filter_pattern(cli, "--restore")?.map(str::to_string);
+        options.tag = filter_tag(cli)?.map(str::to_string);
+    }
+    restore(
+        &options,
+        handler,
+        prompter,
+        &Printer {
+            verbose: cli.verbose,
+        },
+    )
+}
+
+fn run_pin(
+    cli: &Cli,
+    handler: &dyn TrashHandler,
+    prompter: &dyn Prompter,
+    pinned: bool,
+) -> Result<bool> {
+    let is_tty = is_tty();
+    let flag = if pinned { "--pin" } else { "--unpin" };
+
+    let (items, mut all_ok) = if !cli.ids.is_empty() {
+        items_by_id(handler, &cli.ids, &Printer { verbose: false })?
+    } else {
+        let mut items = handler.list_restorable(filter_pattern(cli, flag)?, filter_tag(cli)?)?;
+        // Only offer items whose state would change
+        items.retain(|item| item.pinned != pinned);
+        if items.is_empty() {
+            println!("{}", t!("restore_nothing"));
+            return Ok(true);
+        }
+        if is_tty {
+            let defaults = vec![false; items.len()];
+            let title = if pinned {
+                t!("pin_select")
+            } else {
+                t!("unpin_select")
+            };
+            let selected = prompter.multi_select(&title, &item_labels(&items), &defaults)?;
+            let mut keep = (0..items.len()).map(|idx| selected.contains(&idx));
+            items.retain(|_| keep.next().unwrap_or(false));
+        } else if !cli.force {
+            // Non-TTY with -f: every matching item
+            return Err(Error::msg(t!("error_pin_non_interactive", flag = flag)));
+        }
+        (items, true)
+    };
+
+    for item in &items {
+        match handler.set_pinned(&item.id, pinned) {
+            Ok(()) => {
+                if cli.verbose {
+                    let key = if pinned {
+                        "verbose_pinned"
+                    } else {
+                        "verbose_unpinned"
+                    };
+                    println!(
+                        "{}",
+                        t!(
+                            key,
+                            name = item.original_path.display().to_string(),
+                            id = item.id.to_string_lossy()
+                        )
+                    );
+                }
+            }
+            Err(e) => {
+                eprintln!("saferm: {:#}", e);
+                all_ok = false;
+            }
+        }
+    }
+
+    Ok(all_ok)
+}
+
+fn run_purge(cli: &Cli, handler: &dyn TrashHandler, prompter: &dyn Prompter) -> Result<bool> {
+    let is_tty = is_tty();
+    let mut all_ok = true;
+
+    // Outside a TTY only explicit ids are purged, and only with -f
+    let items = if !cli.ids.is_empty() {
+        if !is_tty && !cli.force {
+            return Err(Error::msg(t!("error_purge_non_interactive")));
+        }
+        let (items, found) = items_by_id(handler, &cli.ids, &Printer { verbose: false })?;
+        all_ok = found;
+        items
+    } else {
+        if !is_tty {
+            return Err(Error::msg(t!("error_purge_non_interactive")));
+        }
+        let mut items =
+            handler.list_restorable(filter_pattern(cli, "--purge")?, filter_tag(cli)?)?;
+        if items.is_empty() {
+            println!("{}", t!("restore_nothing"));
+            return Ok(true);
+        }
+        let defaults = vec![false; items.len()];
+        let selected =
+            prompter.multi_select(&t!("purge_select"), &item_labels(&items), &defaults)?;
+        let mut keep = (0..items.len()).map(|idx| selected.contains(&idx));
+        items.retain(|_| keep.next().unwrap_or(false));
+        items
+    };
+
+    if items.is_empty() {
+        println!("{}", t!("purge_cancelled"));
+        return Ok(all_ok);
+    }
+
+    // Strong confirmation: show exactly what goes and make the user type it out
+    if is_tty {
+        for item in &items {
+            println!(
+                "  {}  {}",
+                item.id.to_string_lossy(),
+                item.original_path.display()
+            );
+        }
+        let question = t!("confirm_purge", count = items.len(), word = PURGE_WORD);
+        if !prompter.confirm_typed(&question, PURGE_WORD)? {
+            println!("{}", t!("purge_cancelled"));
+            return Ok(all_ok);
+        }
+    }
+
+    let shredder = cli
+        .shred
+        .then(|| Shredder::new(cli.shred_passes, cli.verbose));
+    for item in &items {
+        match handler.purge_observed(
+            &item.id,
+            shredder.as_ref(),
+            &Printer {
+                verbose: cli.verbose,
+            },
+        ) {
+            Ok(()) => {
+                if cli.verbose {
+                    println!(
+                        "{}",
+                        t!(
+                            "verbose_purged",
+                            name = item.original_path.display().to_string(),
+                            id = item.id.to_string_lossy()
+                        )
+                    );
+                }
+            }
+            Err(e) => {
+                eprintln!(
+                    "saferm: {}",
+                    t!(
+                        "error_purge_failed",
+                        name = item.display_name.to_string_lossy(),
+                        reason = format!("{:#}", e)
+                    )
+                );
+                all_ok = false;
+            }
+        }
+    }
+
+    Ok(all_ok)
+}
+
+/// What the user types to confirm `--purge`.
+const PURGE_WORD: &str = "purge";
+
+fn run_list(cli: &Cli, handler: &dyn TrashHandler) -> Result<bool> {
+    let query = ListQuery {
+        filter: filter_pattern(cli, "--list")?,
+        tag: filter_tag(cli)?,
+        sort: match cli.sort {
+            SortOrder::Date => SortKey::Date,
+            SortOrder::Path => SortKey::Path,
+            SortOrder::Size => SortKey::Size,
+        },
+        offset: cli.offset,
+        limit: cli.limit,
+    };
+    let page = handler.list_page(&query)?;
+
+    if page.total == 0 {
+        println!("{}", t!("restore_nothing"));
+        return Ok(true);
+    }
+
+    for (item, backend) in page.items.iter().zip(backend_labels(&page.items)) {
+        println!(
+            "{}  {}  {:>10}  {}{}{}{}",
+            item.id.to_string_lossy(),
+            format_deleted_at(item.deleted_at),
+            format_size(item.size),
+            backend,
+            item.original_path.display(),
+            pinned_marker(item),
+            annotation_suffix(item)
+        );
+    }
+    println!(
+        "{}",
+        t!(
+            "list_summary",
+            shown = page.items.len(),
+            total = page.total,
+            size = format_size(Some(page.total_size))
+        )
+    );
+    Ok(true)
+}
+
+fn format_size(size: Option<u64>) -> String {
+    let Some(bytes) = size else {
+        return "?".to_string();
+    };
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn run_versions(cli: &Cli, handler: &dyn TrashHandler, prompter: &dyn Prompter) -> Result<bool> {
+    let [target] = cli.targets.as_slice() else {
+        return Err(Error::msg(t!("error_versions_target")));
+    };
+    let original = resolve_original_path(target);
+    let name_filter = original.file_name().and_then(|n| n.to_str());
+
+    let mut versions: Vec<RestorableItem> = handler
+        .list_restorable(name_filter, None)?
+        .into_iter()
+        .filter(|item| item.original_path == original)
+        .collect();
+
+    if versions.is_empty() {
+        println!("{}", t!("restore_nothing"));
+        return Ok(true);
+    }
+
+    // Newest first, so the default choice is the latest version. Ids break
+    // ties within the same second (managed ids are ULIDs and sort by time).
+    versions.sort_by(|a, b| (b.deleted_at, &b.id).cmp(&(a.deleted_at, &a.id)));
+    let labels: Vec<String> = versions
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            format!(
+                "v{}  {}  {}  [{}]{}",
+                versions.len() - i,
+                format_deleted_at(item.deleted_at),
+                format_size(item.size),
+                item.id.to_string_lossy(),
+                pinned_marker(item)
+            )
+        })
+        .collect();
+    let path = original.display().to_string();
+
+    let chosen = if is_tty() {
+        let mut options = labels.clone();
+        options.push(t!("versions_cancel").to_string());
+        let choice = prompter.select(&t!("versions_select", path = path), &options, 0)?;
+        if choice >= versions.len() {
+            println!("{}", t!("restore_cancelled"));
+            return Ok(true);
+        }
+        choice
+    } else {
+        println!(
+            "{}",
+            t!("versions_header", path = path, count = versions.len())
+        );
+        for label in &labels {
+            println!("  {}", label);
+        }
+        // Non-TTY: list only, or restore the latest version with -f
+        if !cli.force {
+            return Ok(true);
+        }
+        0
+    };
+
+    restore_item(
+        &versions[chosen],
+        &restore_options(cli),
+        handler,
+        prompter,
+        &Printer {
+            verbose: cli.verbose,
+        },
+    )
+}
+
+/// Resolve a path the way backends record it: absolute, with the parent
+/// canonicalized (the file itself is usually gone by now).
+fn resolve_original_path(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    match (
+        absolute.parent().and_then(|p| p.canonicalize().ok()),
+        absolute.file_name(),
+    ) {
+        (Some(parent), Some(name)) => parent.join(name),
+        _ => absolute,
+    }
+}
+
+/// Use the first target as an optional filter pattern for `flag`.
+fn filter_pattern<'a>(cli: &'a Cli, flag: &str) -> Result<Option<&'a str>> {
+    // Reject multiple filter arguments
+    if cli.targets.len() > 1 {
+        return Err(Error::msg(format!(
+            "{} accepts at most one filter pattern",
+            flag
+        )));
+    }
+
+    // Reject non-UTF8 patterns
+    match cli.targets.first() {
+        Some(p) => match p.to_str() {
+            Some(s) => Ok(Some(s)),
+            None => Err(Error::msg(format!(
+                "{} filter pattern must be valid UTF-8",
+                flag
+            ))),
+        },
+        None => Ok(None),
+    }
+}
+
+/// The tag to filter by, if any; only one can be used as a filter.
+fn filter_tag(cli: &Cli) -> Result<Option<&str>> {
+    match cli.tags.as_slice() {
+        [] => Ok(None),
+        [tag] => Ok(Some(tag)),
+        _ => Err(Error::msg(
+            "--tag accepts one tag when selecting trashed items",
+        )),
+    }
+}
+
+fn run_verify(cli: &Cli, handler: &dyn TrashHandler) -> Result<bool> {
+    let filter = filter_pattern(cli, "--verify")?;
+    let results = handler.verify_integrity(filter)?;
+
+    if results.is_empty() {
+        println!("{}", t!("restore_nothing"));
+        return Ok(true);
+    }
+
+    let (mut intact, mut corrupted, mut unverified, mut unreadable) = (0, 0, 0, 0);
+    for (item, status) in &results {
+        let path = item.original_path.display().to_string();
+        match status {
+            Integrity::Intact => {
+                intact += 1;
+                if cli.verbose {
+                    println!("{}", t!("verify_intact", path = path));
+                }
+            }
+            Integrity::Corrupted => {
+                corrupted += 1;
+                eprintln!("saferm: {}", t!("verify_corrupted", path = path));
+            }
+            Integrity::Unverified => {
+                unverified += 1;
+                if cli.verbose {
+                    println!("{}", t!("verify_unverified", path = path));
+                }
+            }
+            Integrity::Unreadable { reason } => {
+                unreadable += 1;
+                eprintln!(
+                    "saferm: {}",
+                    t!("verify_unreadable", path = path, reason = reason)
+                );
+            }
+        }
+    }
+
+    println!(
+        "{}",
+        t!(
+            "verify_summary",
+            intact = intact,
+            corrupted = corrupted,
+            unverified = unverified,
+            unreadable = unreadable
+        )
+    );
+    Ok(corrupted == 0 && unreadable == 0)
+}
+
+fn run_fsck(cli: &Cli, handler: &dyn TrashHandler, prompter: &dyn Prompter) -> Result<bool> {
+    let problems = handler.fsck()?;
+
+    if problems.is_empty() {
+        println!("{}", t!("fsck_clean"));
+        return Ok(true);
+    }
+
+    for problem in &problems {
+        let path = problem.path().display().to_string();
+        let msg = match problem {
+            TrashProblem::Orphan { .. } => t!("fsck_orphan", path = path),
+            TrashProblem::DanglingInfo { .. } => t!("fsck_dangling", path = path),
+            TrashProblem::BrokenInfo { reason, .. } => {
+                t!("fsck_broken", path = path, reason = reason)
+            }
+            TrashProblem::Permission { .. } => t!("fsck_permission", path = path),
+        };
+        println!("{}", msg);
+    }
+
+    let repairable: Vec<_> = problems.iter().filter(|p| p.is_repairable()).collect();
+    if repairable.is_empty() {
+        return Ok(false);
+    }
+
+    // Same rules as removal: prompt on a TTY, require -f in scripts.
+    let repair = if is_tty() {
+        prompter.confirm(&t!("confirm_fsck_repair", count = repairable.len()))?
+    } else {
+        if !cli.force {
+            eprintln!("{}", t!("fsck_repair_hint"));
+        }
+        cli.force
+    };
+    if !repair {
+        return Ok(false);
+    }
+
+    let mut all_ok = repairable.len() == problems.len();
+    for problem in repairable {
+        let path = problem.path().display().to_string();
+        match handler.repair(problem) {
+            Ok(()) => {
+                if cli.verbose {
+                    println!("{}", t!("fsck_repaired", path = path));
+                }
+            }
+            Err(e) => {
+                eprintln!(
+                    "saferm: {}",
+                    t!(
+                        "error_fsck_repair_failed",
+                        path = path,
+                        reason = e.to_string()
+                    )
+                );
+                all_ok = false;
+            }
+        }
+    }
+
+    Ok(all_ok)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn run_migrate(cli: &Cli, prompter: &dyn Prompter) -> Result<bool> {
+    use crate::cli::MigrateBackend;
+    use crate::trash::migrate::{Migrator, Side};
+
+    let side = |backend| match backend {
+        Some(MigrateBackend::Managed) => Side::Managed,
+        _ => Side::Os,
+    };
+    let (from, to) = (side(cli.from), side(cli.to));
+    if from == to {
+        return Err(Error::msg(t!("error_migrate_same", backend = from.name())));
+    }
+
+    let migrator = Migrator::new(cli.bin.as_deref())?;
+    let items = migrator.pending(from, filter_pattern(cli, "--migrate")?)?;
+    if items.is_empty() {
+        println!("{}", t!("migrate_nothing", backend = from.name()));
+        return Ok(true);
+    }
+
+    if cli.dry_run {
+        for item in &items {
+            println!(
+                "{}",
+                t!(
+                    "migrate_would_move",
+                    path = item.original_path.display().to_string(),
+                    id = item.id.to_string_lossy()
+                )
+            );
+        }
+        println!(
+            "{}",
+            t!(
+                "migrate_dry_run_summary",
+                count = items.len(),
+                from = from.name(),
+                to = to.name()
+            )
+        );
+        return Ok(true);
+    }
+
+    // Same rules as removal: prompt on a TTY, require -f in scripts.
+    if is_tty() {
+        let question = t!(
+            "confirm_migrate",
+            count = items.len(),
+            from = from.name(),
+            to = to.name()
+        );
+        if !prompter.confirm(&question)? {
+            println!("{}", t!("migrate_cancelled"));
+            return Ok(true);
+        }
+    } else if !cli.force {
+        return Err(Error::msg(t!("error_migrate_non_interactive")));
+    }
+
+    let pinned = items.iter().filter(|item| item.pinned).count();
+    if to == Side::Os && pinned > 0 {
+        eprintln!(
+            "saferm: warning: {} pinned item(s) lose their pin in the os trash",
+            pinned
+        );
+    }
+
+    let mut moved = 0;
+    for item in &items {
+        match migrator.migrate(item, to) {
+            Ok(()) => {
+                moved += 1;
+                if cli.verbose {
+                    println!(
+                        "{}",
+                        t!(
+                            "verbose_migrated",
+                            path = item.original_path.display().to_string(),
+                            to = to.name()
+                        )
+                    );
+                }
+            }
+            Err(e) => {
+                eprintln!(
+                    "saferm: {}",
+                    t!(
+                        "error_migrate_failed",
+                        path = item.original_path.display().to_string(),
+                        reason = format!("{:#}", e)
+                    )
+                );
+            }
+        }
+    }
+
+    println!(
+        "{}",
+        t!(
+            "migrate_summary",
+            count = moved,
+            from = from.name(),
+            to = to.name()
+        )
+    );
+    Ok(moved == items.len())
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn run_migrate(_cli: &Cli, _prompter: &dyn Prompter) -> Result<bool> {
+    Err(Error::Unsupported {
+        operation: "--migrate",
+        backend: "os",
+    })
+}
+
+fn run_verify_log() -> Result<bool> {
+    let Some(log) = AuditLog::from_env()? else {
+        return Err(Error::msg(t!("error_audit_not_configured")));
+    };
+
+    match log.verify()? {
+        ChainStatus::Intact { records } => {
+            println!("{}", t!("verify_log_intact", count = records));
+            Ok(true)
+        }
+        ChainStatus::Broken { record, reason } => {
+            eprintln!(
+                "saferm: {}",
+                t!(
+                    "verify_log_broken",
+                    record = record,
+                    reason = reason.message()
+                )
+            );
+            Ok(false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::AutoConfirmPrompter;
+    use crate::testing::MemoryTrash;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn make_cli(targets: Vec<PathBuf>, force: bool, recursive: bool, verbose: bool) -> Cli {
+        Cli {
+            targets,
+            recursive,
+            force,
+            interactive: false,
+            dir: false,
+            verbose,
+            cleanup: false,
+            purge: false,
+            include_pinned: false,
+            pin: false,
+            bin: None,
+            reason: None,
+            tags: vec![],
+            unpin: false,
+            shred: false,
+            shred_passes: 3,
+            restore: false,
+            list: false,
+            sort: SortOrder::Date,
+            limit: None,
+            offset: 0,
+            ids: vec![],
+            versions: false,
+            verify: false,
+            verify_log: false,
+            fsck: false,
+            migrate: false,
+            from: None,
+            to: None,
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn test_run_maps_flags_onto_options() {
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("notes.txt");
+        fs::write(&file, "hello").unwrap();
+        let handler = MemoryTrash::new();
+
+        let mut cli = make_cli(vec![file.clone()], true, false, false);
+        cli.reason = Some("done".to_string());
+        assert!(run(&cli, &handler, &AutoConfirmPrompter).unwrap());
+        let items = handler.items();
+        assert_eq!(items[0].annotation.reason.as_deref(), Some("done"));
+        // The memory trash leaves the file in place
+        fs::remove_file(&file).unwrap();
+
+        let mut cli = make_cli(vec![PathBuf::from("notes")], true, false, false);
+        cli.restore = true;
+        assert!(run(&cli, &handler, &AutoConfirmPrompter).unwrap());
+        assert!(handler.items().is_empty());
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(None), "?");
+        assert_eq!(format_size(Some(512)), "512 B");
+        assert_eq!(format_size(Some(1536)), "1.5 KiB");
+        assert_eq!(format_size(Some(3 * 1024 * 1024)), "3.0 MiB");
+    }
+}
@@ -0,0 +1,1286 @@
+//! Removal and restore, the trash semantics behind the `saferm` command.
+//!
+//! [`remove`] and [`restore`] take plain option structs, so they work without
+//! the `cli` feature. With it, `run` maps parsed command-line arguments onto
+//! them and on the other modes (`--purge`, `--list`, `--fsck`, ...).
+
+use rust_i18n::t;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, mpsc};
+
+use crate::error::{Error, Result};
+use crate::observer::{Event, Observer, SkipReason};
+use crate::prompt::Prompter;
+use crate::trash::routing::DIRECT;
+use crate::trash::{Annotation, RestorableItem, Route, TrashHandler};
+
+#[cfg(feature = "cli")]
+mod commands;
+#[cfg(feature = "cli")]
+pub use commands::run;
+
+/// How [`remove`] treats its targets; the fields mirror `rm`'s flags.
+#[derive(Debug, Clone, Default)]
+pub struct RemoveOptions {
+    /// Remove directories and their contents (`-r`)
+    pub recursive: bool,
+    /// Remove empty directories (`-d`)
+    pub dir: bool,
+    /// Ignore missing targets, and allow removal without prompting (`-f`)
+    pub force: bool,
+    /// Ask the prompter before each removal. Without it, `force` is required.
+    pub interactive: bool,
+    /// Reason and tags recorded with every trashed target
+    pub annotation: Annotation,
+    /// How many targets to work on at once when not `interactive`; 0 and 1
+    /// mean one after another. Results are reported in argument order either way.
+    pub jobs: usize,
+}
+
+/// Which items [`restore`] brings back and how it resolves conflicts.
+#[derive(Debug, Clone, Default)]
+pub struct RestoreOptions {
+    /// Only items whose original path contains this pattern
+    pub filter: Option<String>,
+    /// Only items with this tag
+    pub tag: Option<String>,
+    /// Restore exactly these entries, in order, instead of selecting
+    pub ids: Vec<String>,
+    /// Without `interactive`: restore every match and skip conflicts
+    pub force: bool,
+    /// Let the prompter select items and resolve conflicts. Without it,
+    /// `force` is required unless `ids` are given.
+    pub interactive: bool,
+}
+
+/// Move `targets` to the trash, then apply retention limits. Errors for
+/// single targets go to `observer` as [`Event::Failed`]; returns false if any
+/// target failed.
+///
+/// With `jobs` above 1 and no prompting, targets are trashed on up to `jobs`
+/// threads. A target that is the same as, inside or above an earlier one waits
+/// for it, so the result matches a run one after another. [`Event::TargetStarted`]
+/// and [`Event::BytesCopied`] then arrive as the work happens; the outcome of
+/// each target (trashed, skipped or failed) is still reported in argument order.
+pub fn remove(
+    targets: &[impl AsRef<Path> + Sync],
+    options: &RemoveOptions,
+    handler: &dyn TrashHandler,
+    prompter: &dyn Prompter,
+    observer: &dyn Observer,
+) -> Result<bool> {
+    let mut all_ok = true;
+    let mut report = |path: &Path, outcome: Result<Outcome>| {
+        if !report_outcome(path, outcome, observer) {
+            all_ok = false;
+        }
+    };
+
+    let jobs = options.jobs.min(targets.len());
+    if jobs > 1 && !options.interactive {
+        let waits_for = overlapping_earlier(targets);
+        let done = (Mutex::new(vec![false; targets.len()]), Condvar::new());
+        let next = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+        std::thread::scope(|scope| {
+            for _ in 0..jobs {
+                let sender = sender.clone();
+                let (next, waits_for, done) = (&next, &waits_for, &done);
+                scope.spawn(move || {
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(target) = targets.get(index) else {
+                            break;
+                        };
+                        // Targets are taken in order, so the earliest unfinished
+                        // one never waits and this always makes progress
+                        let (finished, changed) = done;
+                        let mut guard = finished.lock().unwrap_or_else(|e| e.into_inner());
+                        while waits_for[index].iter().any(|&earlier| !guard[earlier]) {
+                            guard = changed.wait(guard).unwrap_or_else(|e| e.into_inner());
+                        }
+                        drop(guard);
+
+                        let path = target.as_ref();
+                        observer.notify(&Event::TargetStarted { path });
+                        let outcome = process_target(path, options, handler, prompter, observer);
+                        finished.lock().unwrap_or_else(|e| e.into_inner())[index] = true;
+                        changed.notify_all();
+                        if sender.send((index, outcome)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            // Hold back results that finish early until those before them are in
+            let mut pending: Vec<Option<Result<Outcome>>> = std::iter::repeat_with(|| None)
+                .take(targets.len())
+                .collect();
+            let mut reported = 0;
+            for (index, outcome) in receiver {
+                pending[index] = Some(outcome);
+                while let Some(outcome) = pending.get_mut(reported).and_then(Option::take) {
+                    report(targets[reported].as_ref(), outcome);
+                    reported += 1;
+                }
+            }
+        });
+    } else {
+        for target in targets {
+            let path = target.as_ref();
+            observer.notify(&Event::TargetStarted { path });
+            report(
+                path,
+                process_target(path, options, handler, prompter, observer),
+            );
+        }
+    }
+
+    // Apply retention and quota limits now that the new entries are in
+    match handler.evict_observed(observer) {
+        Ok(evicted) => {
+            for item in &evicted {
+                observer.notify(&Event::Evicted { item });
+            }
+        }
+        Err(e) => observer.notify(&Event::Warning {
+            message: &format!("failed to apply trash retention: {:#}", e),
+        }),
+    }
+
+    Ok(all_ok)
+}
+
+/// For each target, the earlier targets that are the same path, inside it or
+/// above it. Removing one of those changes what the target refers to, so
+/// they have to finish first.
+fn overlapping_earlier(targets: &[impl AsRef<Path>]) -> Vec<Vec<usize>> {
+    // Latest index per path; an earlier duplicate is covered by waiting on the
+    // later one, which waits on it in turn
+    let mut seen: BTreeMap<PathBuf, usize> = BTreeMap::new();
+    let mut waits_for = Vec::with_capacity(targets.len());
+    for (index, target) in targets.iter().enumerate() {
+        let path = comparable_path(target.as_ref());
+        let mut earlier: Vec<usize> = path
+            .ancestors()
+            .filter_map(|ancestor| seen.get(ancestor).copied())
+            .collect();
+        // Paths order by component, so everything inside `path` directly follows it
+        earlier.extend(
+            seen.range::<Path, _>((Bound::Excluded(path.as_path()), Bound::Unbounded))
+                .take_while(|(other, _)| other.starts_with(&path))
+                .map(|(_, &other)| other),
+        );
+        waits_for.push(earlier);
+        seen.insert(path, index);
+    }
+    waits_for
+}
+
+/// `path` made absolute with its parent resolved, so different spellings of
+/// the same location (`./a`, `dir/../a`, a symlinked parent) compare equal.
+fn comparable_path(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    match (absolute.parent(), absolute.file_name()) {
+        (Some(parent), Some(name)) => parent
+            .canonicalize()
+            .map(|parent| parent.join(name))
+            .unwrap_or(absolute),
+        _ => absolute,
+    }
+}
+
+/// What became of a target that didn't fail.
+#[derive(Debug)]
+enum Outcome {
+    Trashed {
+        route: Route,
+        id: Option<OsString>,
+        symlink: bool,
+    },
+    Skipped(SkipReason),
+}
+
+/// Tell `observer` how `path` went; returns false if it failed.
+fn report_outcome(path: &Path, outcome: Result<Outcome>, observer: &dyn Observer) -> bool {
+    match outcome {
+        Ok(Outcome::Trashed { route, id, symlink }) => observer.notify(&Event::Trashed {
+            path,
+            route: &route,
+            id: id.as_deref(),
+            symlink,
+        }),
+        Ok(Outcome::Skipped(reason)) => observer.notify(&Event::Skipped { path, reason }),
+        Err(error) => {
+            observer.notify(&Event::Failed {
+                path,
+                error: &error,
+            });
+            return false;
+        }
+    }
+    true
+}
+
+fn process_target(
+    target: &Path,
+    options: &RemoveOptions,
+    handler: &dyn TrashHandler,
+    prompter: &dyn Prompter,
+    observer: &dyn Observer,
+) -> Result<Outcome> {
+    let name = target.display().to_string();
+    let is_symlink = target.is_symlink();
+    let is_dir = target.is_dir() && !is_symlink;
+
+    // Check existence
+    if !target.exists() && !is_symlink {
+        if options.force {
+            return Ok(Outcome::Skipped(SkipReason::Missing));
+        }
+        return Err(Error::NotFound {
+            path: target.to_path_buf(),
+        });
+    }
+
+    // Directory check — symlinks to directories are treated as symlinks, not directories.
+    // Real rm removes symlinks without -r regardless of what they point to.
+    if is_dir {
+        if !options.recursive && !options.dir {
+            return Err(Error::IsADirectory {
+                path: target.to_path_buf(),
+            });
+        }
+        // -d flag only works for empty directories
+        if options.dir && !options.recursive {
+            let mut entries = target.read_dir().map_err(|source| Error::Io {
+                path: target.to_path_buf(),
+                source,
+            })?;
+            if entries.next().is_some() {
+                return Err(Error::DirectoryNotEmpty {
+                    path: target.to_path_buf(),
+                });
+            }
+        }
+    }
+
+    // Non-interactive without -f: refuse with a clear error (never attempt a prompt)
+    if !options.interactive && !options.force {
+        return Err(Error::msg(t!("error_non_interactive", name = name.clone())));
+    }
+
+    let route = handler.route(target);
+    let direct = route.backend == DIRECT && !is_symlink;
+
+    // Interactive: always prompt (even with -f — saferm's core safety feature)
+    if options.interactive {
+        let msg = if is_symlink {
+            t!("confirm_delete_symlink", name = name.clone())
+        } else if direct {
+            t!("confirm_delete_direct", name = name.clone())
+        } else if is_dir {
+            t!("confirm_trash_dir", name = name.clone())
+        } else {
+            t!("confirm_trash", name = name.clone())
+        };
+
+        observer.notify(&Event::PromptNeeded { path: target });
+        if !prompter.confirm(&msg)? {
+            return Ok(Outcome::Skipped(SkipReason::Declined));
+        }
+    }
+    // Non-interactive with -f: skip prompt (script/CI usage)
+
+    // Move to trash
+    let id = handler.trash_observed(target, &options.annotation, observer)?;
+    Ok(Outcome::Trashed {
+        route,
+        id,
+        symlink: is_symlink,
+    })
+}
+
+/// Restore trashed items to their original locations. Returns false if an
+/// id was unknown or a restore failed.
+pub fn restore(
+    options: &RestoreOptions,
+    handler: &dyn TrashHandler,
+    prompter: &dyn Prompter,
+    observer: &dyn Observer,
+) -> Result<bool> {
+    // Explicit ids: no filtering and no selection prompt
+    if !options.ids.is_empty() {
+        let (items, mut all_ok) = items_by_id(handler, &options.ids, observer)?;
+        for item in &items {
+            if !restore_item(item, options, handler, prompter, observer)? {
+                all_ok = false;
+            }
+        }
+        return Ok(all_ok);
+    }
+
+    let items = handler.list_restorable(options.filter.as_deref(), options.tag.as_deref())?;
+
+    if items.is_empty() {
+        observer.notify(&Event::NothingToRestore);
+        return Ok(true);
+    }
+
+    let display_options = item_labels(&items);
+
+    // Select items to restore
+    let selected = if options.interactive {
+        let defaults = vec![false; display_options.len()];
+        let sel = prompter.multi_select(&t!("restore_select"), &display_options, &defaults)?;
+        if sel.is_empty() {
+            observer.notify(&Event::RestoreCancelled);
+            return Ok(true);
+        }
+        sel
+    } else if options.force {
+        // Non-interactive with -f: select all
+        (0..items.len()).collect()
+    } else {
+        return Err(Error::msg(t!("error_restore_non_interactive")));
+    };
+
+    let mut all_ok = true;
+
+    for idx in selected {
+        if !restore_item(&items[idx], options, handler, prompter, observer)? {
+            all_ok = false;
+        }
+    }
+
+    Ok(all_ok)
+}
+
+/// Selection labels for trashed items; repeated trashes of the same path are
+/// numbered as versions.
+fn item_labels(items: &[RestorableItem]) -> Vec<String> {
+    let versions = version_numbers(items);
+    let backends = backend_labels(items);
+    items
+        .iter()
+        .zip(&versions)
+        .zip(&backends)
+        .map(|((item, version), backend)| {
+            let label = format!(
+                "{}{} ({})",
+                backend,
+                item.original_path.display(),
+                format_deleted_at(item.deleted_at)
+            );
+            let label = match version {
+                Some((n, total)) => format!("{} [v{}/{}]", label, n, total),
+                None => label,
+            };
+            format!(
+                "{}{}{}",
+                label,
+                pinned_marker(item),
+                annotation_suffix(item)
+            )
+        })
+        .collect()
+}
+
+/// Tags and reason of an item for listings: ` #tag "reason"`.
+fn annotation_suffix(item: &RestorableItem) -> String {
+    let mut suffix = String::new();
+    for tag in &item.annotation.tags {
+        suffix.push_str(&format!(" #{}", tag));
+    }
+    if let Some(reason) = &item.annotation.reason {
+        suffix.push_str(&format!(" \"{}\"", reason));
+    }
+    suffix
+}
+
+/// Prefix naming each item's backend, empty unless `items` come from more
+/// than one.
+fn backend_labels(items: &[RestorableItem]) -> Vec<String> {
+    let mixed = items
+        .windows(2)
+        .any(|pair| pair[0].backend != pair[1].backend);
+    items
+        .iter()
+        .map(|item| {
+            if mixed {
+                format!("[{}] ", item.backend)
+            } else {
+                String::new()
+            }
+        })
+        .collect()
+}
+
+/// Suffix that marks pinned items in listings.
+fn pinned_marker(item: &RestorableItem) -> String {
+    if item.pinned {
+        format!(" {}", t!("pinned_marker"))
+    } else {
+        String::new()
+    }
+}
+
+/// Number items that share an original path as versions, oldest first: `Some((n, total))`.
+/// Items that were trashed only once get `None`.
+fn version_numbers(items: &[RestorableItem]) -> Vec<Option<(usize, usize)>> {
+    let mut by_path: HashMap<&Path, Vec<usize>> = HashMap::new();
+    for (idx, item) in items.iter().enumerate() {
+        by_path.entry(&item.original_path).or_default().push(idx);
+    }
+
+    let mut versions = vec![None; items.len()];
+    for indices in by_path.values_mut().filter(|v| v.len() > 1) {
+        indices.sort_by_key(|&idx| (items[idx].deleted_at, &items[idx].id));
+        for (n, &idx) in indices.iter().enumerate() {
+            versions[idx] = Some((n + 1, indices.len()));
+        }
+    }
+    versions
+}
+
+fn format_deleted_at(deleted_at: Option<i64>) -> String {
+    deleted_at
+        .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Restore one item to its original location, resolving conflicts with the user.
+/// Returns false if the restore itself failed.
+fn restore_item(
+    item: &RestorableItem,
+    options: &RestoreOptions,
+    handler: &dyn TrashHandler,
+    prompter: &dyn Prompter,
+    observer: &dyn Observer,
+) -> Result<bool> {
+    let mut dest = item.original_path.clone();
+
+    // Ensure parent directory exists
+    if let Some(parent) = dest.parent()
+        && !parent.exists()
+    {
+        std::fs::create_dir_all(parent).map_err(|source| Error::Io {
+            path: parent.to_path_buf(),
+            source,
+        })?;
+    }
+
+    // Conflict handling
+    let mut backup_path: Option<std::path::PathBuf> = None;
+    if dest.exists() {
+        observer.notify(&Event::RestoreConflict {
+            item,
+            destination: &dest,
+        });
+        if !options.interactive && options.force {
+            // Non-interactive: skip on conflict (safe default)
+            observer.notify(&Event::RestoreSkipped {
+                item,
+                destination: &dest,
+                reason: SkipReason::Conflict,
+            });
+            return Ok(true);
+        }
+
+        let name_str = item.display_name.to_string_lossy().to_string();
+        let rename_dest = generate_rename_path(&dest);
+        let rename_label = t!(
+            "restore_conflict_rename",
+            name = rename_dest.display().to_string()
+        );
+
+        let choices: Vec<String> = vec![
+            t!("restore_conflict_overwrite").to_string(),
+            t!("restore_conflict_skip").to_string(),
+            rename_label.to_string(),
+        ];
+
+        let choice = prompter.select(
+            &t!("restore_conflict", name = name_str),
+            &choices,
+            1, // default to Skip
+        )?;
+
+        match choice {
+            0 => {
+                // Overwrite: move existing to temp backup (rollback on failure)
+                let tmp = generate_backup_path(&dest);
+                std::fs::rename(&dest, &tmp).map_err(|source| Error::Io {
+                    path: dest.clone(),
+                    source,
+                })?;
+                backup_path = Some(tmp);
+            }
+            1 => {
+                // Skip
+                observer.notify(&Event::RestoreSkipped {
+                    item,
+                    destination: &dest,
+                    reason: SkipReason::Declined,
+                });
+                return Ok(true);
+            }
+            _ => {
+                // Rename
+                dest = rename_dest;
+            }
+        }
+    }
+
+    match handler.restore_observed(&item.id, &dest, observer) {
+        Ok(()) => {
+            // Restore succeeded — delete backup if we had one
+            if let Some(bp) = backup_path {
+                let cleanup_result = match std::fs::symlink_metadata(&bp) {
+                    Ok(m) if m.is_dir() => std::fs::remove_dir_all(&bp),
+                    _ => std::fs::remove_file(&bp),
+                };
+                if let Err(e) = cleanup_result {
+                    observer.notify(&Event::Warning {
+                        message: &format!("failed to remove backup '{}': {}", bp.display(), e),
+                    });
+                }
+            }
+            observer.notify(&Event::Restored {
+                item,
+                destination: &dest,
+            });
+        }
+        Err(error) => {
+            // Restore failed — rollback: move backup back to dest
+            if let Some(bp) = backup_path
+                && let Err(re) = std::fs::rename(&bp, &dest)
+            {
+                observer.notify(&Event::Warning {
+                    message: &format!("rollback failed for '{}': {}", dest.display(), re),
+                });
+            }
+            observer.notify(&Event::RestoreFailed {
+                item,
+                error: &error,
+            });
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Look up trash entries by id, in the order given. Unknown ids are reported to
+/// `observer` and make the second value false.
+fn items_by_id(
+    handler: &dyn TrashHandler,
+    ids: &[String],
+    observer: &dyn Observer,
+) -> Result<(Vec<RestorableItem>, bool)> {
+    let mut available = handler.list_restorable(None, None)?;
+    let mut items = vec![];
+    let mut all_found = true;
+
+    for id in ids {
+        match available.iter().position(|item| item.id == id.as_str()) {
+            Some(pos) => items.push(available.swap_remove(pos)),
+            None => {
+                observer.notify(&Event::UnknownId { id });
+                all_found = false;
+            }
+        }
+    }
+
+    Ok((items, all_found))
+}
+
+/// Generate a temporary backup path for safe overwrite.
+fn generate_backup_path(path: &Path) -> std::path::PathBuf {
+    let parent = path.parent().unwrap_or(Path::new("."));
+    let name = path.file_name().unwrap_or(std::ffi::OsStr::new("file"));
+    let mut backup = parent.join(format!(
+        ".saferm-backup-{}-{}",
+        std::process::id(),
+        name.to_string_lossy()
+    ));
+    let mut counter = 0u64;
+    while backup.exists() {
+        counter += 1;
+        backup = parent.join(format!(
+            ".saferm-backup-{}-{}-{}",
+            std::process::id(),
+            counter,
+            name.to_string_lossy()
+        ));
+    }
+    backup
+}
+
+/// Generate a rename path by appending ".restored" or a counter suffix.
+fn generate_rename_path(path: &Path) -> std::path::PathBuf {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("file");
+    let ext = path.extension().and_then(|s| s.to_str());
+    let parent = path.parent().unwrap_or(Path::new("."));
+
+    for i in 1u64.. {
+        let candidate = match ext {
+            Some(e) => parent.join(format!(
+                "{}.restored{}.{}",
+                stem,
+                if i == 1 {
+                    String::new()
+                } else {
+                    format!("{}", i)
+                },
+                e
+            )),
+            None => parent.join(format!(
+                "{}.restored{}",
+                stem,
+                if i == 1 {
+                    String::new()
+                } else {
+                    format!("{}", i)
+                }
+            )),
+        };
+        if !candidate.exists() {
+            return candidate;
+        }
+    }
+    unreachable!()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::observer::NoopObserver;
+    use crate::prompt::AutoConfirmPrompter;
+    use crate::testing::{Answer, MemoryTrash, ScriptedPrompter};
+    use anyhow::Result;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    struct DenyPrompter;
+
+    impl Prompter for DenyPrompter {
+        fn confirm(&self, _message: &str) -> Result<bool> {
+            Ok(false)
+        }
+
+        fn select(&self, _message: &str, _options: &[String], default: usize) -> Result<usize> {
+            Ok(default)
+        }
+
+        fn multi_select(
+            &self,
+            _message: &str,
+            _options: &[String],
+            _defaults: &[bool],
+        ) -> Result<Vec<usize>> {
+            Ok(vec![])
+        }
+    }
+
+    struct CaptureConfirmPrompter {
+        message: Mutex<Option<String>>,
+    }
+
+    impl CaptureConfirmPrompter {
+        fn new() -> Self {
+            Self {
+                message: Mutex::new(None),
+            }
+        }
+
+        fn confirm_message(&self) -> Option<String> {
+            self.message.lock().unwrap().clone()
+        }
+    }
+
+    impl Prompter for CaptureConfirmPrompter {
+        fn confirm(&self, message: &str) -> Result<bool> {
+            self.message.lock().unwrap().replace(message.to_string());
+            Ok(false)
+        }
+
+        fn select(&self, _message: &str, _options: &[String], default: usize) -> Result<usize> {
+            Ok(default)
+        }
+
+        fn multi_select(
+            &self,
+            _message: &str,
+            _options: &[String],
+            _defaults: &[bool],
+        ) -> Result<Vec<usize>> {
+            Ok(vec![])
+        }
+    }
+
+    fn options(force: bool, recursive: bool) -> RemoveOptions {
+        RemoveOptions {
+            recursive,
+            force,
+            ..RemoveOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_trash_file() {
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("test.txt");
+        fs::write(&file, "hello").unwrap();
+
+        let handler = MemoryTrash::new();
+        let result = remove(
+            &[&file],
+            &options(true, false),
+            &handler,
+            &AutoConfirmPrompter,
+            &NoopObserver,
+        )
+        .unwrap();
+
+        assert!(result);
+        assert_eq!(handler.trashed_paths(), vec![file]);
+    }
+
+    #[test]
+    fn test_nonexistent_file_without_force() {
+        let handler = MemoryTrash::new();
+        let result = remove(
+            &[PathBuf::from("/nonexistent/file.txt")],
+            &options(false, false),
+            &handler,
+            &AutoConfirmPrompter,
+            &NoopObserver,
+        )
+        .unwrap();
+
+        assert!(!result);
+        assert!(handler.trashed_paths().is_empty());
+    }
+
+    #[test]
+    fn test_nonexistent_file_with_force() {
+        let handler = MemoryTrash::new();
+        let result = remove(
+            &[PathBuf::from("/nonexistent/file.txt")],
+            &options(true, false),
+            &handler,
+            &AutoConfirmPrompter,
+            &NoopObserver,
+        )
+        .unwrap();
+
+        assert!(result);
+        assert!(handler.trashed_paths().is_empty());
+    }
+
+    #[test]
+    fn test_directory_without_recursive() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("mydir");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("file.txt"), "hello").unwrap();
+
+        let handler = MemoryTrash::new();
+        let result = remove(
+            &[dir],
+            &options(false, false),
+            &handler,
+            &AutoConfirmPrompter,
+            &NoopObserver,
+        )
+        .unwrap();
+
+        assert!(!result);
+        assert!(handler.trashed_paths().is_empty());
+    }
+
+    #[test]
+    fn test_directory_with_recursive() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("mydir");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("file.txt"), "hello").unwrap();
+
+        let handler = MemoryTrash::new();
+        let result = remove(
+            &[&dir],
+            &options(true, true),
+            &handler,
+            &AutoConfirmPrompter,
+            &NoopObserver,
+        )
+        .unwrap();
+
+        assert!(result);
+        assert_eq!(handler.trashed_paths(), vec![dir]);
+    }
+
+    #[test]
+    fn test_denied_prompt() {
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("test.txt");
+        fs::write(&file, "hello").unwrap();
+
+        let handler = MemoryTrash::new();
+        let options = RemoveOptions {
+            interactive: true,
+            ..RemoveOptions::default()
+        };
+        let result = process_target(&file, &options, &handler, &DenyPrompter, &NoopObserver);
+
+        assert!(result.is_ok());
+        assert!(handler.trashed_paths().is_empty());
+    }
+
+    #[test]
+    fn test_symlink_prompt_uses_delete_message() {
+        rust_i18n::set_locale("en");
+
+        let tmp = TempDir::new().unwrap();
+        let target = tmp.path().join("target.txt");
+        let link = tmp.path().join("link.txt");
+        fs::write(&target, "hello").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let handler = MemoryTrash::new();
+        let options = RemoveOptions {
+            interactive: true,
+            ..RemoveOptions::default()
+        };
+        let prompter = CaptureConfirmPrompter::new();
+        let expected = format!(
+            "Delete symbolic link '{}'? The target will not be removed.",
+            link.display()
+        );
+
+        let result = process_target(&link, &options, &handler, &prompter, &NoopObserver);
+
+        assert!(result.is_ok());
+        assert_eq!(
+            prompter.confirm_message().as_deref(),
+            Some(expected.as_str())
+        );
+        assert!(handler.trashed_paths().is_empty());
+    }
+
+    #[test]
+    fn test_non_tty_without_force_refuses() {
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("test.txt");
+        fs::write(&file, "hello").unwrap();
+
+        let handler = MemoryTrash::new();
+        // Non-interactive without -f should refuse with an error
+        let result = process_target(
+            &file,
+            &options(false, false),
+            &handler,
+            &AutoConfirmPrompter,
+            &NoopObserver,
+        );
+
+        assert!(result.is_err());
+        assert!(handler.trashed_paths().is_empty());
+    }
+
+    #[test]
+    fn test_multiple_targets_partial_failure() {
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("exists.txt");
+        fs::write(&file, "hello").unwrap();
+        // A non-empty directory without -r will fail
+        let dir = tmp.path().join("mydir");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("inner.txt"), "data").unwrap();
+
+        let handler = MemoryTrash::new();
+        // force=true for non-TTY, recursive=false so directory fails
+        let result = remove(
+            &[file.clone(), dir],
+            &options(true, false),
+            &handler,
+            &AutoConfirmPrompter,
+            &NoopObserver,
+        )
+        .unwrap();
+
+        // Should return false (partial failure from dir) but still trash the file
+        assert!(!result);
+        assert_eq!(handler.trashed_paths(), vec![file]);
+    }
+
+    #[test]
+    fn test_symlink_to_dir_without_recursive() {
+        let tmp = TempDir::new().unwrap();
+        let real_dir = tmp.path().join("realdir");
+        fs::create_dir(&real_dir).unwrap();
+        let link = tmp.path().join("linkdir");
+        std::os::unix::fs::symlink(&real_dir, &link).unwrap();
+
+        let handler = MemoryTrash::new();
+        // No -r flag — symlink to directory should still be accepted
+        let result = remove(
+            &[&link],
+            &options(true, false),
+            &handler,
+            &AutoConfirmPrompter,
+            &NoopObserver,
+        )
+        .unwrap();
+
+        assert!(result);
+        assert_eq!(handler.trashed_paths(), vec![link]);
+    }
+
+    fn item(path: &str, deleted_at: i64) -> RestorableItem {
+        RestorableItem {
+            id: path.into(),
+            original_path: PathBuf::from(path),
+            display_name: path.into(),
+            deleted_at: Some(deleted_at),
+            size: None,
+            pinned: false,
+            annotation: Annotation::default(),
+            backend: "managed",
+        }
+    }
+
+    #[test]
+    fn test_version_numbers() {
+        let items = vec![
+            item("/a/cfg.toml", 300),
+            item("/a/other.txt", 200),
+            item("/a/cfg.toml", 100),
+            item("/a/cfg.toml", 200),
+        ];
+
+        assert_eq!(
+            version_numbers(&items),
+            vec![Some((3, 3)), None, Some((1, 3)), Some((2, 3))]
+        );
+    }
+
+    #[test]
+    fn test_annotation_and_failures_reach_backend() {
+        let tmp = TempDir::new().unwrap();
+        let kept = tmp.path().join("kept.txt");
+        let locked = tmp.path().join("locked.txt");
+        fs::write(&kept, "a").unwrap();
+        fs::write(&locked, "b").unwrap();
+
+        let handler = MemoryTrash::new();
+        handler.fail_on(&locked);
+        let mut options = options(true, false);
+        options.annotation.tags = vec!["old".to_string()];
+        let targets = [kept.clone(), locked.clone()];
+        let result = remove(
+            &targets,
+            &options,
+            &handler,
+            &ScriptedPrompter::new([]),
+            &NoopObserver,
+        )
+        .unwrap();
+
+        assert!(!result);
+        assert_eq!(handler.trashed_paths(), vec![kept.clone(), locked]);
+        let items = handler.list_restorable(None, Some("old")).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].original_path, kept);
+    }
+
+    #[test]
+    fn test_events_follow_each_target() {
+        let tmp = TempDir::new().unwrap();
+        let kept = tmp.path().join("kept.txt");
+        let locked = tmp.path().join("locked.txt");
+        let missing = tmp.path().join("missing.txt");
+        fs::write(&kept, "a").unwrap();
+        fs::write(&locked, "b").unwrap();
+
+        let handler = MemoryTrash::new();
+        handler.fail_on(&locked);
+        let events = Mutex::new(Vec::new());
+        let observer = |event: &Event<'_>| {
+            let step = match event {
+                Event::TargetStarted { path } => format!("started {}", path.display()),
+                Event::Trashed { path, route, .. } => {
+                    format!("trashed {} by {}", path.display(), route.backend)
+                }
+                Event::Skipped { path, reason } => {
+                    format!("skipped {} {:?}", path.display(), reason)
+                }
+                Event::Failed { path, error } => {
+                    format!("failed {} {:?}", path.display(), error.kind())
+                }
+                _ => return,
+            };
+            events.lock().unwrap().push(step);
+        };
+        let targets = [kept.clone(), missing.clone(), locked.clone()];
+        let result = remove(
+            &targets,
+            &options(true, false),
+            &handler,
+            &ScriptedPrompter::new([]),
+            &observer,
+        )
+        .unwrap();
+
+        assert!(!result);
+        assert_eq!(
+            events.into_inner().unwrap(),
+            vec![
+                format!("started {}", kept.display()),
+                format!("trashed {} by memory", kept.display()),
+                format!("started {}", missing.display()),
+                format!("skipped {} Missing", missing.display()),
+                format!("started {}", locked.display()),
+                format!("failed {} PermissionDenied", locked.display()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_restore_notices_go_to_observer() {
+        let handler = MemoryTrash::new();
+        let events = Mutex::new(Vec::new());
+        let observer = |event: &Event<'_>| match event {
+            Event::NothingToRestore => events.lock().unwrap().push("nothing".to_string()),
+            Event::UnknownId { id } => events.lock().unwrap().push(format!("unknown {}", id)),
+            _ => {}
+        };
+        let prompter = ScriptedPrompter::new([]);
+
+        let options = RestoreOptions {
+            force: true,
+            ..RestoreOptions::default()
+        };
+        assert!(restore(&options, &handler, &prompter, &observer).unwrap());
+        let options = RestoreOptions {
+            ids: vec!["missing".to_string()],
+            ..RestoreOptions::default()
+        };
+        assert!(!restore(&options, &handler, &prompter, &observer).unwrap());
+
+        assert_eq!(
+            events.into_inner().unwrap(),
+            vec!["nothing", "unknown missing"]
+        );
+    }
+
+    #[test]
+    fn test_restore_conflict_skip_is_reported_for_item() {
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("file.txt");
+        fs::write(&file, "x").unwrap();
+        let handler = MemoryTrash::new();
+        // MemoryTrash leaves the file in place, so restoring it conflicts
+        let id = handler.trash(&file).unwrap().unwrap();
+
+        let skipped = Mutex::new(Vec::new());
+        let observer = |event: &Event<'_>| {
+            if let Event::RestoreSkipped {
+                item,
+                destination,
+                reason,
+            } = event
+            {
+                skipped
+                    .lock()
+                    .unwrap()
+                    .push((item.id.clone(), destination.to_path_buf(), *reason));
+            }
+        };
+        let options = RestoreOptions {
+            ids: vec![id.to_string_lossy().to_string()],
+            interactive: true,
+            ..RestoreOptions::default()
+        };
+        let prompter = ScriptedPrompter::new([Answer::Select(1)]);
+        assert!(restore(&options, &handler, &prompter, &observer).unwrap());
+
+        assert_eq!(
+            skipped.into_inner().unwrap(),
+            vec![(id, file, SkipReason::Declined)]
+        );
+    }
+
+    #[test]
+    fn test_declined_prompt_is_reported() {
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("file.txt");
+        fs::write(&file, "x").unwrap();
+        let options = RemoveOptions {
+            interactive: true,
+            ..RemoveOptions::default()
+        };
+
+        let events = Mutex::new(Vec::new());
+        let observer = |event: &Event<'_>| match event {
+            Event::PromptNeeded { .. } => events.lock().unwrap().push("prompt".to_string()),
+            Event::Skipped { reason, .. } => events.lock().unwrap().push(format!("{:?}", reason)),
+            _ => {}
+        };
+        let handler = MemoryTrash::new();
+        remove(&[&file], &options, &handler, &DenyPrompter, &observer).unwrap();
+
+        assert_eq!(events.into_inner().unwrap(), vec!["prompt", "Declined"]);
+        assert!(handler.calls().is_empty());
+    }
+
+    #[test]
+    fn test_parallel_outcomes_in_argument_order() {
+        let tmp = TempDir::new().unwrap();
+        let handler = MemoryTrash::new();
+        let mut targets = Vec::new();
+        for i in 0..40 {
+            let file = tmp.path().join(format!("file{}.txt", i));
+            if i % 7 != 3 {
+                fs::write(&file, "x").unwrap();
+            }
+            if i % 5 == 0 {
+                handler.fail_on(&file);
+            }
+            targets.push(file);
+        }
+
+        let outcomes = Mutex::new(Vec::new());
+        let observer = |event: &Event<'_>| match event {
+            Event::Trashed { path, .. } | Event::Skipped { path, .. } => {
+                outcomes.lock().unwrap().push((path.to_path_buf(), true))
+            }
+            Event::Failed { path, .. } => {
+                outcomes.lock().unwrap().push((path.to_path_buf(), false))
+            }
+            _ => {}
+        };
+        let options = RemoveOptions {
+            jobs: 4,
+            ..options(true, false)
+        };
+        let result = remove(
+            &targets,
+            &options,
+            &handler,
+            &ScriptedPrompter::new([]),
+            &observer,
+        )
+        .unwrap();
+
+        assert!(!result);
+        let expected: Vec<_> = targets
+            .iter()
+            .enumerate()
+            .map(|(i, path)| (path.clone(), i % 5 != 0 || i % 7 == 3))
+            .collect();
+        assert_eq!(outcomes.into_inner().unwrap(), expected);
+        let mut trashed = handler.trashed_paths();
+        trashed.sort();
+        let mut attempted: Vec<_> = targets.into_iter().filter(|p| p.exists()).collect();
+        attempted.sort();
+        assert_eq!(trashed, attempted);
+    }
+
+    #[test]
+    fn test_overlapping_targets_wait_for_earlier_ones() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("d");
+        fs::create_dir(&dir).unwrap();
+        let targets = [
+            dir.join("a"),
+            dir.clone(),
+            tmp.path().join("other"),
+            dir.join("b"),
+            tmp.path().join("d/../d/b"),
+            tmp.path().join("d-x"),
+        ];
+
+        assert_eq!(
+            overlapping_earlier(&targets),
+            vec![vec![], vec![0], vec![], vec![1], vec![3, 1], vec![]]
+        );
+    }
+
+    #[test]
+    fn test_interactive_ignores_jobs() {
+        let tmp = TempDir::new().unwrap();
+        let first = tmp.path().join("first.txt");
+        let second = tmp.path().join("second.txt");
+        fs::write(&first, "a").unwrap();
+        fs::write(&second, "b").unwrap();
+        let options = RemoveOptions {
+            interactive: true,
+            jobs: 4,
+            ..RemoveOptions::default()
+        };
+
+        let handler = MemoryTrash::new();
+        let prompter = ScriptedPrompter::new([Answer::Confirm(false), Answer::Confirm(true)]);
+        let result = remove(
+            &[&first, &second],
+            &options,
+            &handler,
+            &prompter,
+            &NoopObserver,
+        )
+        .unwrap();
+
+        assert!(result);
+        assert_eq!(handler.trashed_paths(), vec![second]);
+    }
+
+    #[test]
+    fn test_target_errors_have_kinds() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("dir");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("file"), "x").unwrap();
+        let missing = tmp.path().join("missing");
+        let handler = MemoryTrash::new();
+        let check = |options: &RemoveOptions, target: &Path| {
+            process_target(
+                target,
+                options,
+                &handler,
+                &AutoConfirmPrompter,
+                &NoopObserver,
+            )
+            .unwrap_err()
+        };
+
+        let error = check(&options(false, false), &missing);
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+        assert_eq!(error.path(), Some(missing.as_path()));
+        let mut options = options(true, false);
+        assert_eq!(check(&options, &dir).kind(), ErrorKind::IsADirectory);
+
+        options.dir = true;
+        assert_eq!(check(&options, &dir).kind(), ErrorKind::DirectoryNotEmpty);
+        assert!(handler.calls().is_empty());
+    }
+
+    #[test]
+    fn test_backend_labels_only_when_mixed() {
+        let mut items = vec![item("/a/x", 2), item("/a/y", 1)];
+        assert_eq!(backend_labels(&items), vec!["", ""]);
+        items[1].backend = "os";
+        assert_eq!(backend_labels(&items), vec!["[managed] ", "[os] "]);
+        assert!(backend_labels(&[]).is_empty());
+    }
+}
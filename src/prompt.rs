@@ -1,7 +1,10 @@
 use anyhow::Result;
-use dialoguer::{Confirm, MultiSelect, Select};
+#[cfg(feature = "cli")]
+use dialoguer::{Confirm, Input, MultiSelect, Select};
 
-pub trait Prompter {
+/// Asks the user. Shared with [`crate::ops::remove`]'s worker threads, which
+/// never prompt.
+pub trait Prompter: Sync {
     fn confirm(&self, message: &str) -> Result<bool>;
     fn select(&self, message: &str, options: &[String], default: usize) -> Result<usize>;
     fn multi_select(
@@ -10,10 +13,19 @@ pub trait Prompter {
         options: &[String],
         defaults: &[bool],
     ) -> Result<Vec<usize>>;
+
+    /// Confirm an irreversible action by having the user type `expected`.
+    /// Prompters that cannot read text fall back to [`Prompter::confirm`].
+    fn confirm_typed(&self, message: &str, _expected: &str) -> Result<bool> {
+        self.confirm(message)
+    }
 }
 
+/// Prompts on the terminal.
+#[cfg(feature = "cli")]
 pub struct InteractivePrompter;
 
+#[cfg(feature = "cli")]
 impl Prompter for InteractivePrompter {
     fn confirm(&self, message: &str) -> Result<bool> {
         let result = Confirm::new()
@@ -45,6 +57,14 @@ impl Prompter for InteractivePrompter {
             .interact()?;
         Ok(result)
     }
+
+    fn confirm_typed(&self, message: &str, expected: &str) -> Result<bool> {
+        let answer: String = Input::new()
+            .with_prompt(message)
+            .allow_empty(true)
+            .interact_text()?;
+        Ok(answer.trim() == expected)
+    }
 }
 
 pub struct AutoConfirmPrompter;
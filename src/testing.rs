@@ -0,0 +1,419 @@
+//! Test doubles for code built on saferm (enable the `testing` feature).
+//!
+//! [`MemoryTrash`] is a [`TrashHandler`] that keeps its items in memory and
+//! records every call, and [`ScriptedPrompter`] answers prompts from a script.
+//! Together they let [`crate::ops::remove`] and [`crate::ops::restore`] be
+//! exercised without touching the
+//! real trash. For a backend on disk, use
+//! [`ManagedTrash::with_base_dir`](crate::trash::managed::ManagedTrash::with_base_dir)
+//! with a temporary directory.
+
+use anyhow::Result;
+use chrono::Local;
+use std::collections::{HashSet, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::Error;
+use crate::prompt::Prompter;
+use crate::trash::shred::Shredder;
+use crate::trash::{Annotation, RestorableItem, TrashHandler};
+
+/// A call made to a [`MemoryTrash`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Call {
+    Trash {
+        path: PathBuf,
+        annotation: Annotation,
+    },
+    Cleanup {
+        include_pinned: bool,
+    },
+    Restore {
+        id: OsString,
+        destination: PathBuf,
+    },
+    Purge {
+        id: OsString,
+    },
+    SetPinned {
+        id: OsString,
+        pinned: bool,
+    },
+}
+
+#[derive(Default)]
+struct State {
+    items: Vec<RestorableItem>,
+    calls: Vec<Call>,
+    failing: HashSet<PathBuf>,
+    next_id: u64,
+}
+
+/// A trash that only exists in memory.
+///
+/// Trashing records the path and lists it as an item, restoring and purging
+/// drop the item again. Files on disk are left alone, so tests can run against
+/// any path. Calls that fail (see [`MemoryTrash::fail_on`]) are recorded too.
+#[derive(Default)]
+pub struct MemoryTrash {
+    state: Mutex<State>,
+}
+
+impl MemoryTrash {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        // A panicking test must not hide the calls recorded so far
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Put an item in the trash as if `original_path` had been trashed at
+    /// unix time `deleted_at`, and return its id.
+    pub fn insert(&self, original_path: impl Into<PathBuf>, deleted_at: i64) -> OsString {
+        let mut state = self.state();
+        Self::add_item(
+            &mut state,
+            original_path.into(),
+            deleted_at,
+            Annotation::default(),
+        )
+    }
+
+    fn add_item(
+        state: &mut State,
+        original_path: PathBuf,
+        deleted_at: i64,
+        annotation: Annotation,
+    ) -> OsString {
+        state.next_id += 1;
+        let id = OsString::from(format!("mem-{}", state.next_id));
+        state.items.push(RestorableItem {
+            id: id.clone(),
+            display_name: original_path
+                .file_name()
+                .unwrap_or(original_path.as_os_str())
+                .to_os_string(),
+            original_path,
+            deleted_at: Some(deleted_at),
+            size: None,
+            pinned: false,
+            annotation,
+            backend: "memory",
+        });
+        id
+    }
+
+    /// Make trashing `path` fail with a permission error.
+    pub fn fail_on(&self, path: impl Into<PathBuf>) {
+        self.state().failing.insert(path.into());
+    }
+
+    /// Every call so far, in order.
+    pub fn calls(&self) -> Vec<Call> {
+        self.state().calls.clone()
+    }
+
+    /// Paths passed to [`TrashHandler::trash`], in order.
+    pub fn trashed_paths(&self) -> Vec<PathBuf> {
+        self.calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                Call::Trash { path, .. } => Some(path),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Items currently in the trash.
+    pub fn items(&self) -> Vec<RestorableItem> {
+        self.state().items.clone()
+    }
+
+    /// Remove item `id`, or fail like a real backend when it is unknown.
+    fn take(state: &mut State, id: &OsStr) -> crate::Result<RestorableItem> {
+        match state.items.iter().position(|item| item.id == id) {
+            Some(idx) => Ok(state.items.remove(idx)),
+            None => Err(Error::IdNotFound {
+                id: id.to_os_string(),
+            }),
+        }
+    }
+}
+
+impl TrashHandler for MemoryTrash {
+    fn trash(&self, path: &Path) -> crate::Result<Option<OsString>> {
+        self.trash_annotated(path, &Annotation::default())
+    }
+
+    fn trash_annotated(
+        &self,
+        path: &Path,
+        annotation: &Annotation,
+    ) -> crate::Result<Option<OsString>> {
+        let mut state = self.state();
+        state.calls.push(Call::Trash {
+            path: path.to_path_buf(),
+            annotation: annotation.clone(),
+        });
+        if state.failing.contains(path) {
+            return Err(Error::Io {
+                path: path.to_path_buf(),
+                source: io::Error::from(io::ErrorKind::PermissionDenied),
+            });
+        }
+        let id = Self::add_item(
+            &mut state,
+            path.to_path_buf(),
+            Local::now().timestamp(),
+            annotation.clone(),
+        );
+        Ok(Some(id))
+    }
+
+    /// Empties the trash without prompting, so scripts don't need an answer for it.
+    fn cleanup(&self, _prompter: &dyn Prompter, include_pinned: bool) -> crate::Result<()> {
+        let mut state = self.state();
+        state.calls.push(Call::Cleanup { include_pinned });
+        state.items.retain(|item| item.pinned && !include_pinned);
+        Ok(())
+    }
+
+    fn cleanup_shred(
+        &self,
+        prompter: &dyn Prompter,
+        _shredder: &Shredder,
+        include_pinned: bool,
+    ) -> crate::Result<()> {
+        self.cleanup(prompter, include_pinned)
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn list_restorable(
+        &self,
+        filter: Option<&str>,
+        tag: Option<&str>,
+    ) -> crate::Result<Vec<RestorableItem>> {
+        Ok(self
+            .state()
+            .items
+            .iter()
+            .filter(|item| {
+                filter.is_none_or(|pat| item.original_path.to_string_lossy().contains(pat))
+                    && item.annotation.matches(tag)
+            })
+            .cloned()
+            .collect())
+    }
+
+    fn restore_to(&self, item_id: &OsStr, destination: &Path) -> crate::Result<()> {
+        let mut state = self.state();
+        state.calls.push(Call::Restore {
+            id: item_id.to_os_string(),
+            destination: destination.to_path_buf(),
+        });
+        Self::take(&mut state, item_id).map(|_| ())
+    }
+
+    fn purge(&self, item_id: &OsStr, _shredder: Option<&Shredder>) -> crate::Result<()> {
+        let mut state = self.state();
+        state.calls.push(Call::Purge {
+            id: item_id.to_os_string(),
+        });
+        Self::take(&mut state, item_id).map(|_| ())
+    }
+
+    fn set_pinned(&self, item_id: &OsStr, pinned: bool) -> crate::Result<()> {
+        let mut state = self.state();
+        state.calls.push(Call::SetPinned {
+            id: item_id.to_os_string(),
+            pinned,
+        });
+        match state.items.iter_mut().find(|item| item.id == item_id) {
+            Some(item) => {
+                item.pinned = pinned;
+                Ok(())
+            }
+            None => Err(Error::IdNotFound {
+                id: item_id.to_os_string(),
+            }),
+        }
+    }
+}
+
+/// One scripted answer for [`ScriptedPrompter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Answer {
+    /// Answer to [`Prompter::confirm`]
+    Confirm(bool),
+    /// Index chosen in [`Prompter::select`]
+    Select(usize),
+    /// Indices chosen in [`Prompter::multi_select`]
+    MultiSelect(Vec<usize>),
+    /// Text typed for [`Prompter::confirm_typed`]
+    Typed(String),
+}
+
+/// A prompter that replays answers in order and remembers what it was asked.
+///
+/// A prompt whose kind doesn't match the next answer, or one asked after the
+/// script ran out, fails, so a test notices when the flow changes.
+pub struct ScriptedPrompter {
+    answers: Mutex<VecDeque<Answer>>,
+    messages: Mutex<Vec<String>>,
+}
+
+impl ScriptedPrompter {
+    pub fn new(answers: impl IntoIterator<Item = Answer>) -> Self {
+        Self {
+            answers: Mutex::new(answers.into_iter().collect()),
+            messages: Mutex::new(vec![]),
+        }
+    }
+
+    /// The messages of every prompt so far, in order.
+    pub fn messages(&self) -> Vec<String> {
+        self.messages
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Whether every scripted answer was used.
+    pub fn is_finished(&self) -> bool {
+        self.answers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_empty()
+    }
+
+    fn next(&self, kind: &str, message: &str) -> Result<Answer> {
+        self.messages
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(message.to_string());
+        self.answers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front()
+            .ok_or_else(|| anyhow::anyhow!("unexpected {} prompt: {}", kind, message))
+    }
+}
+
+impl Prompter for ScriptedPrompter {
+    fn confirm(&self, message: &str) -> Result<bool> {
+        match self.next("confirm", message)? {
+            Answer::Confirm(answer) => Ok(answer),
+            other => anyhow::bail!("expected {:?}, got a confirm prompt: {}", other, message),
+        }
+    }
+
+    fn select(&self, message: &str, options: &[String], _default: usize) -> Result<usize> {
+        match self.next("select", message)? {
+            Answer::Select(idx) if idx < options.len() => Ok(idx),
+            other => anyhow::bail!(
+                "expected {:?}, got a select prompt with {} options: {}",
+                other,
+                options.len(),
+                message
+            ),
+        }
+    }
+
+    fn multi_select(
+        &self,
+        message: &str,
+        options: &[String],
+        _defaults: &[bool],
+    ) -> Result<Vec<usize>> {
+        match self.next("multi-select", message)? {
+            Answer::MultiSelect(selected) if selected.iter().all(|&idx| idx < options.len()) => {
+                Ok(selected)
+            }
+            other => anyhow::bail!(
+                "expected {:?}, got a multi-select prompt with {} options: {}",
+                other,
+                options.len(),
+                message
+            ),
+        }
+    }
+
+    fn confirm_typed(&self, message: &str, expected: &str) -> Result<bool> {
+        match self.next("typed confirmation", message)? {
+            Answer::Typed(text) => Ok(text.trim() == expected),
+            Answer::Confirm(answer) => Ok(answer),
+            other => anyhow::bail!(
+                "expected {:?}, got a typed confirmation prompt: {}",
+                other,
+                message
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn test_memory_trash_records_calls() {
+        let trash = MemoryTrash::new();
+        let old = trash.insert("/srv/old.log", 100);
+        trash.fail_on("/srv/locked");
+
+        let id = trash.trash(Path::new("/srv/new.log")).unwrap().unwrap();
+        let error = trash.trash(Path::new("/srv/locked")).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(trash.list_restorable(Some("log"), None).unwrap().len(), 2);
+
+        trash.set_pinned(&old, true).unwrap();
+        trash.restore_to(&id, Path::new("/srv/new.log")).unwrap();
+        let error = trash.purge(&id, None).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::IdNotFound);
+        trash.cleanup(&ScriptedPrompter::new([]), false).unwrap();
+        assert_eq!(trash.items().len(), 1);
+
+        assert_eq!(
+            trash.trashed_paths(),
+            vec![PathBuf::from("/srv/new.log"), PathBuf::from("/srv/locked")]
+        );
+        assert_eq!(trash.calls().len(), 6);
+        assert_eq!(trash.calls()[4], Call::Purge { id: id.clone() });
+    }
+
+    #[test]
+    fn test_scripted_prompter_replays_answers() {
+        let prompter = ScriptedPrompter::new([
+            Answer::Confirm(true),
+            Answer::MultiSelect(vec![1]),
+            Answer::Typed("purge".to_string()),
+        ]);
+        let options = vec!["a".to_string(), "b".to_string()];
+        assert!(prompter.confirm("go?").unwrap());
+        assert_eq!(
+            prompter
+                .multi_select("pick", &options, &[false, false])
+                .unwrap(),
+            vec![1]
+        );
+        assert!(prompter.confirm_typed("type purge", "purge").unwrap());
+        assert!(prompter.is_finished());
+        assert!(prompter.confirm("again?").is_err());
+        assert_eq!(
+            prompter.messages(),
+            vec!["go?", "pick", "type purge", "again?"]
+        );
+
+        let mismatched = ScriptedPrompter::new([Answer::Select(0)]);
+        assert!(mismatched.confirm("go?").is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use crate::error::Result;
+use crate::observer::{NoopObserver, Observer};
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+use super::routing::DIRECT;
+use super::shred::Shredder;
+use super::{
+    Annotation, Integrity, ListPage, ListQuery, RestorableItem, Route, TrashHandler, TrashProblem,
+};
+use crate::audit::AuditLog;
+use crate::prompt::Prompter;
+
+/// Wraps another backend and records every successful operation in the audit log.
+///
+/// Audit failures are reported as warnings and never fail the operation itself.
+pub struct AuditedTrash {
+    inner: Box<dyn TrashHandler>,
+    log: AuditLog,
+}
+
+impl AuditedTrash {
+    pub fn new(inner: Box<dyn TrashHandler>, log: AuditLog) -> Self {
+        Self { inner, log }
+    }
+
+    fn record(&self, op: &str, path: &Path, id: Option<&OsStr>) {
+        self.record_in(op, self.inner.backend_name(), path, id);
+    }
+
+    fn record_in(&self, op: &str, backend: &str, path: &Path, id: Option<&OsStr>) {
+        if let Err(e) = self.log.record(op, backend, path, id) {
+            eprintln!(
+                "saferm: warning: failed to write audit log '{}': {:#}",
+                self.log.path().display(),
+                e
+            );
+        }
+    }
+
+    fn record_trashed(
+        &self,
+        path: &Path,
+        trash: impl FnOnce() -> Result<Option<OsString>>,
+    ) -> Result<Option<OsString>> {
+        // Resolve before the item disappears; symlinks are logged as the link itself.
+        let is_symlink = path.is_symlink();
+        let logged_path: PathBuf = if is_symlink {
+            std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+        } else {
+            path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+        };
+
+        let backend = self.inner.route(path).backend;
+        let id = trash()?;
+        let op = if is_symlink {
+            "unlink"
+        } else if backend == DIRECT {
+            "delete"
+        } else {
+            "trash"
+        };
+        self.record_in(op, backend, &logged_path, id.as_deref());
+        Ok(id)
+    }
+
+    /// Run a purge and log the entries that are actually gone afterwards; the
+    /// backend may decline (empty trash, cancelled prompt).
+    fn record_purged(&self, purge: impl FnOnce() -> Result<()>) -> Result<()> {
+        let before = self.inner.list_restorable(None, None).unwrap_or_default();
+        let result = purge();
+        let remaining: HashSet<OsString> = self
+            .inner
+            .list_restorable(None, None)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|item| item.id)
+            .collect();
+
+        for item in before.iter().filter(|i| !remaining.contains(&i.id)) {
+            self.record("purge", &item.original_path, Some(&item.id));
+        }
+        result
+    }
+}
+
+impl TrashHandler for AuditedTrash {
+    fn trash(&self, path: &Path) -> Result<Option<OsString>> {
+        self.record_trashed(path, || self.inner.trash(path))
+    }
+
+    fn trash_annotated(&self, path: &Path, annotation: &Annotation) -> Result<Option<OsString>> {
+        self.record_trashed(path, || self.inner.trash_annotated(path, annotation))
+    }
+
+    fn trash_observed(
+        &self,
+        path: &Path,
+        annotation: &Annotation,
+        observer: &dyn Observer,
+    ) -> Result<Option<OsString>> {
+        self.record_trashed(path, || {
+            self.inner.trash_observed(path, annotation, observer)
+        })
+    }
+
+    fn cleanup(&self, prompter: &dyn Prompter, include_pinned: bool) -> Result<()> {
+        self.record_purged(|| self.inner.cleanup(prompter, include_pinned))
+    }
+
+    fn cleanup_shred(
+        &self,
+        prompter: &dyn Prompter,
+        shredder: &Shredder,
+        include_pinned: bool,
+    ) -> Result<()> {
+        self.record_purged(|| self.inner.cleanup_shred(prompter, shredder, include_pinned))
+    }
+
+    fn cleanup_observed(
+        &self,
+        prompter: &dyn Prompter,
+        shredder: Option<&Shredder>,
+        include_pinned: bool,
+        observer: &dyn Observer,
+    ) -> Result<()> {
+        self.record_purged(|| {
+            self.inner
+                .cleanup_observed(prompter, shredder, include_pinned, observer)
+        })
+    }
+
+    fn backend_name(&self) -> &'static str {
+        self.inner.backend_name()
+    }
+
+    fn route(&self, path: &Path) -> Route {
+        self.inner.route(path)
+    }
+
+    fn purge(&self, item_id: &OsStr, shredder: Option<&Shredder>) -> Result<()> {
+        self.record_purged(|| self.inner.purge(item_id, shredder))
+    }
+
+    fn purge_observed(
+        &self,
+        item_id: &OsStr,
+        shredder: Option<&Shredder>,
+        observer: &dyn Observer,
+    ) -> Result<()> {
+        self.record_purged(|| self.inner.purge_observed(item_id, shredder, observer))
+    }
+
+    fn evict(&self) -> Result<Vec<RestorableItem>> {
+        self.evict_observed(&NoopObserver)
+    }
+
+    fn evict_observed(&self, observer: &dyn Observer) -> Result<Vec<RestorableItem>> {
+        let evicted = self.inner.evict_observed(observer)?;
+        for item in &evicted {
+            self.record("evict", &item.original_path, Some(&item.id));
+        }
+        Ok(evicted)
+    }
+
+    fn set_pinned(&self, item_id: &OsStr, pinned: bool) -> Result<()> {
+        let original_path = self
+            .inner
+            .list_restorable(None, None)?
+            .into_iter()
+            .find(|item| item.id == item_id)
+            .map(|item| item.original_path);
+        self.inner.set_pinned(item_id, pinned)?;
+        if let Some(path) = original_path {
+            self.record(if pinned { "pin" } else { "unpin" }, &path, Some(item_id));
+        }
+        Ok(())
+    }
+
+    fn list_restorable(
+        &self,
+        filter: Option<&str>,
+        tag: Option<&str>,
+    ) -> Result<Vec<RestorableItem>> {
+        self.inner.list_restorable(filter, tag)
+    }
+
+    fn list_page(&self, query: &ListQuery) -> Result<ListPage> {
+        self.inner.list_page(query)
+    }
+
+    fn restore_to(&self, item_id: &OsStr, destination: &Path) -> Result<()> {
+        self.restore_observed(item_id, destination, &NoopObserver)
+    }
+
+    fn restore_observed(
+        &self,
+        item_id: &OsStr,
+        destination: &Path,
+        observer: &dyn Observer,
+    ) -> Result<()> {
+        self.inner
+            .restore_observed(item_id, destination, observer)?;
+        self.record("restore", destination, Some(item_id));
+        Ok(())
+    }
+
+    fn verify_integrity(&self, filter: Option<&str>) -> Result<Vec<(RestorableItem, Integrity)>> {
+        self.inner.verify_integrity(filter)
+    }
+
+    fn fsck(&self) -> Result<Vec<TrashProblem>> {
+        self.inner.fsck()
+    }
+
+    fn repair(&self, problem: &TrashProblem) -> Result<()> {
+        self.inner.repair(problem)
+    }
+}
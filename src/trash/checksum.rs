@@ -0,0 +1,192 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::Read;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// Algorithm prefix stored in front of every content hash.
+const ALGORITHM: &str = "sha256";
+
+/// Compute the content hash of a trashed file, directory or symlink.
+///
+/// Files hash their bytes, symlinks their target. Directories hash a
+/// manifest of the whole tree: one entry per descendant, in sorted order,
+/// holding its kind, relative path and content hash. Modes and timestamps are
+/// not covered, so only content changes are reported.
+pub fn content_hash(path: &Path) -> Result<String> {
+    let meta = fs::symlink_metadata(path)
+        .with_context(|| format!("failed to read metadata: {:?}", path))?;
+    let digest = if meta.is_dir() {
+        let mut manifest = Sha256::new();
+        hash_tree(path, Path::new(""), &mut manifest)?;
+        manifest.finalize().to_vec()
+    } else {
+        hash_entry(path, &meta)?
+    };
+    Ok(format!("{}:{}", ALGORITHM, to_hex(&digest)))
+}
+
+fn hash_tree(root: &Path, relative: &Path, manifest: &mut Sha256) -> Result<()> {
+    let dir = root.join(relative);
+    let mut names: Vec<_> = fs::read_dir(&dir)
+        .with_context(|| format!("failed to read dir: {:?}", dir))?
+        .map(|e| e.map(|e| e.file_name()))
+        .collect::<std::io::Result<_>>()?;
+    names.sort();
+
+    for name in names {
+        let rel = relative.join(&name);
+        let path = root.join(&rel);
+        let meta = fs::symlink_metadata(&path)
+            .with_context(|| format!("failed to read metadata: {:?}", path))?;
+
+        let kind: &[u8] = if meta.is_dir() {
+            b"d"
+        } else if meta.is_symlink() {
+            b"l"
+        } else {
+            b"f"
+        };
+        manifest.update(kind);
+        manifest.update(path_bytes(&rel));
+        manifest.update([0]);
+        if meta.is_dir() {
+            manifest.update([0]);
+            hash_tree(root, &rel, manifest)?;
+        } else {
+            manifest.update(hash_entry(&path, &meta)?);
+            manifest.update([0]);
+        }
+    }
+    Ok(())
+}
+
+fn hash_entry(path: &Path, meta: &fs::Metadata) -> Result<Vec<u8>> {
+    let mut hasher = Sha256::new();
+    if meta.is_symlink() {
+        let target =
+            fs::read_link(path).with_context(|| format!("failed to read symlink: {:?}", path))?;
+        hasher.update(path_bytes(&target));
+    } else if !meta.is_file() {
+        // FIFOs, sockets and devices are never read: opening a FIFO blocks
+        hasher.update(special_identity(meta));
+    } else {
+        let mut file = File::open(path).with_context(|| format!("failed to open: {:?}", path))?;
+        let mut buf = [0u8; 64 * 1024];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// File type and device number of a FIFO, socket or device node.
+#[cfg(unix)]
+fn special_identity(meta: &fs::Metadata) -> Vec<u8> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    let file_type = meta.file_type();
+    let kind = if file_type.is_fifo() {
+        b'p'
+    } else if file_type.is_socket() {
+        b's'
+    } else if file_type.is_char_device() {
+        b'c'
+    } else {
+        b'b'
+    };
+    let mut identity = vec![kind];
+    identity.extend_from_slice(&meta.rdev().to_le_bytes());
+    identity
+}
+
+#[cfg(not(unix))]
+fn special_identity(_meta: &fs::Metadata) -> Vec<u8> {
+    Vec::new()
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().as_bytes().to_vec()
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_file_hash_is_sha256() {
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("a.txt");
+        fs::write(&file, "hello").unwrap();
+
+        assert_eq!(
+            content_hash(&file).unwrap(),
+            "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[test]
+    fn test_directory_hash_is_deterministic() {
+        let tmp = TempDir::new().unwrap();
+        let a = tmp.path().join("a");
+        let b = tmp.path().join("b");
+        for dir in [&a, &b] {
+            fs::create_dir_all(dir.join("sub")).unwrap();
+        }
+        // Create entries in different orders; the manifest is sorted.
+        fs::write(a.join("x.txt"), "x").unwrap();
+        fs::write(a.join("sub/y.txt"), "y").unwrap();
+        fs::write(b.join("sub/y.txt"), "y").unwrap();
+        fs::write(b.join("x.txt"), "x").unwrap();
+
+        assert_eq!(content_hash(&a).unwrap(), content_hash(&b).unwrap());
+    }
+
+    #[test]
+    fn test_directory_hash_detects_changes() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("d");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/y.txt"), "y").unwrap();
+        let before = content_hash(&dir).unwrap();
+
+        fs::write(dir.join("sub/y.txt"), "z").unwrap();
+        let edited = content_hash(&dir).unwrap();
+        assert_ne!(before, edited);
+
+        fs::rename(dir.join("sub/y.txt"), dir.join("sub/w.txt")).unwrap();
+        assert_ne!(edited, content_hash(&dir).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fifo_is_hashed_without_reading() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let tmp = TempDir::new().unwrap();
+        let fifo = tmp.path().join("p");
+        let c_path = std::ffi::CString::new(fifo.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+
+        // Opening the FIFO would block with no writer
+        let hash = content_hash(&fifo).unwrap();
+        assert_ne!(hash, content_hash(tmp.path()).unwrap());
+    }
+}
@@ -0,0 +1,386 @@
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use super::crypto::Cipher;
+
+/// How the content of a managed trash entry is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Compression {
+    /// A single zstd-compressed file
+    Zstd,
+    /// A zstd-compressed tar archive of a directory
+    ZstdTar,
+}
+
+impl Compression {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Compression::Zstd => "zstd",
+            Compression::ZstdTar => "zstd-tar",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "zstd" => Some(Compression::Zstd),
+            "zstd-tar" => Some(Compression::ZstdTar),
+            _ => None,
+        }
+    }
+}
+
+/// Which items `ManagedTrash` compresses instead of renaming.
+///
+/// Configured by `SAFERM_COMPRESS_MIN_SIZE` (bytes, or with a K/M/G suffix) and
+/// `SAFERM_COMPRESS_PATTERNS` (comma-separated file name patterns with `*` and
+/// `?`). Without either, nothing is compressed.
+#[derive(Debug, Default)]
+pub(crate) struct CompressionPolicy {
+    min_size: Option<u64>,
+    patterns: Vec<String>,
+}
+
+impl CompressionPolicy {
+    pub fn from_env() -> Self {
+        let min_size = std::env::var("SAFERM_COMPRESS_MIN_SIZE")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .and_then(|v| {
+                let size = parse_size(&v);
+                if size.is_none() {
+                    eprintln!(
+                        "saferm: warning: invalid SAFERM_COMPRESS_MIN_SIZE '{}', ignoring",
+                        v
+                    );
+                }
+                size
+            });
+        let patterns = std::env::var("SAFERM_COMPRESS_PATTERNS")
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|p| !p.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { min_size, patterns }
+    }
+
+    #[cfg(test)]
+    pub fn matching(patterns: &[&str]) -> Self {
+        Self {
+            min_size: None,
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    /// True if an item named `name` with a total size of `size` bytes should be compressed.
+    pub fn applies(&self, name: &str, size: u64) -> bool {
+        self.min_size.is_some_and(|min| size >= min)
+            || self.patterns.iter().any(|p| wildcard_match(p, name))
+    }
+}
+
+/// Parse a size like `4096`, `512K`, `10M` or `1G` (binary units).
+pub(crate) fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (digits, shift) = match s.char_indices().last()? {
+        (i, 'k' | 'K') => (&s[..i], 10),
+        (i, 'm' | 'M') => (&s[..i], 20),
+        (i, 'g' | 'G') => (&s[..i], 30),
+        _ => (s, 0),
+    };
+    digits.trim().parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+/// Match `name` against a pattern where `*` is any run of characters and `?` one character.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` and the name index it currently absorbs up to
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, absorbed)) => {
+                    p = star + 1;
+                    n = absorbed + 1;
+                    backtrack = Some((star, n));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Compress `src` into the new file `dest`: files as plain zstd, directories as
+/// a zstd tar that keeps modes, mtimes and symlinks of everything inside.
+/// With a `cipher`, the compressed stream is encrypted before it hits the disk.
+/// Anything else, such as a symlink or a FIFO, is refused rather than read.
+pub(crate) fn compress(src: &Path, dest: &Path, cipher: Option<&Cipher>) -> Result<Compression> {
+    let meta = fs::symlink_metadata(src)?;
+    if !meta.is_dir() && !meta.is_file() {
+        anyhow::bail!("only files and directories can be compressed: {:?}", src);
+    }
+    let out = BufWriter::new(
+        create_private(dest).with_context(|| format!("failed to create: {:?}", dest))?,
+    );
+    let sink = match cipher {
+        Some(cipher) => Sink::Encrypted(cipher.encrypt(out)?),
+        None => Sink::Plain(out),
+    };
+    let mut encoder = zstd::Encoder::new(sink, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+
+    let kind = if meta.is_dir() {
+        let mut builder = tar::Builder::new(&mut encoder);
+        builder.follow_symlinks(false);
+        builder
+            .append_dir_all(".", src)
+            .with_context(|| format!("failed to archive: {:?}", src))?;
+        builder.finish()?;
+        Compression::ZstdTar
+    } else {
+        let mut input =
+            BufReader::new(File::open(src).with_context(|| format!("failed to open: {:?}", src))?);
+        std::io::copy(&mut input, &mut encoder)
+            .with_context(|| format!("failed to compress: {:?}", src))?;
+        Compression::Zstd
+    };
+
+    encoder
+        .finish()?
+        .finish()?
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+    Ok(kind)
+}
+
+/// Unpack `src` (stored as `kind`) into the new path `dest`.
+///
+/// Only the content and everything inside a directory is restored here; the
+/// mode and mtime of `dest` itself are applied by [`apply_metadata`].
+pub(crate) fn decompress(
+    src: &Path,
+    kind: Compression,
+    dest: &Path,
+    cipher: Option<&Cipher>,
+) -> Result<()> {
+    let input =
+        BufReader::new(File::open(src).with_context(|| format!("failed to open: {:?}", src))?);
+    let input: Box<dyn Read> = match cipher {
+        Some(cipher) => Box::new(cipher.decrypt(input)?),
+        None => Box::new(input),
+    };
+    let mut decoder = zstd::Decoder::new(input)?;
+    match kind {
+        Compression::Zstd => {
+            let mut out =
+                create_private(dest).with_context(|| format!("failed to create: {:?}", dest))?;
+            std::io::copy(&mut decoder, &mut out)
+                .with_context(|| format!("failed to decompress: {:?}", src))?;
+        }
+        Compression::ZstdTar => {
+            fs::create_dir(dest).with_context(|| format!("failed to create: {:?}", dest))?;
+            let mut archive = tar::Archive::new(decoder);
+            archive.set_preserve_permissions(true);
+            archive.set_preserve_mtime(true);
+            archive
+                .unpack(dest)
+                .with_context(|| format!("failed to unpack: {:?}", src))?;
+        }
+    }
+    Ok(())
+}
+
+/// Output of [`compress`], optionally encrypting on the way to the file.
+enum Sink<W: Write> {
+    Plain(W),
+    Encrypted(age::stream::StreamWriter<W>),
+}
+
+impl<W: Write> Sink<W> {
+    fn finish(self) -> io::Result<W> {
+        match self {
+            Sink::Plain(w) => Ok(w),
+            Sink::Encrypted(w) => w.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Sink<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Plain(w) => w.write(buf),
+            Sink::Encrypted(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Plain(w) => w.flush(),
+            Sink::Encrypted(w) => w.flush(),
+        }
+    }
+}
+
+/// Create (or truncate) `path` readable only by the owner, so trashed secrets
+/// don't become world-readable through the umask while they are stored.
+pub(crate) fn create_private(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
+
+/// Permission bits of `meta`, where the platform has them.
+#[cfg(unix)]
+pub(crate) fn file_mode(meta: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(meta.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn file_mode(_meta: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// Modification time of `meta` as unix seconds.
+pub(crate) fn file_mtime(meta: &fs::Metadata) -> Option<i64> {
+    let mtime = meta.modified().ok()?;
+    Some(chrono::DateTime::<chrono::Utc>::from(mtime).timestamp())
+}
+
+/// Set the permission bits and modification time (unix seconds) of `path`.
+pub(crate) fn apply_metadata(path: &Path, mode: Option<u32>, mtime: Option<i64>) -> Result<()> {
+    if let Some(mtime) = mtime.and_then(|ts| chrono::DateTime::from_timestamp(ts, 0)) {
+        File::open(path)
+            .and_then(|f| f.set_modified(mtime.into()))
+            .with_context(|| format!("failed to set mtime: {:?}", path))?;
+    }
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+            .with_context(|| format!("failed to set permissions: {:?}", path))?;
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_archive_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().join("secret.txt");
+        fs::write(&src, "token").unwrap();
+        fs::set_permissions(&src, fs::Permissions::from_mode(0o600)).unwrap();
+        let dest = tmp.path().join("secret.zst");
+
+        compress(&src, &dest, None).unwrap();
+        let mode = fs::metadata(&dest).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096"), Some(4096));
+        assert_eq!(parse_size("512K"), Some(512 * 1024));
+        assert_eq!(parse_size("10m"), Some(10 * 1024 * 1024));
+        assert_eq!(parse_size("1G"), Some(1 << 30));
+        assert_eq!(parse_size("lots"), None);
+        assert_eq!(parse_size(""), None);
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*.log", "app.log"));
+        assert!(wildcard_match("target", "target"));
+        assert!(wildcard_match("build-??", "build-01"));
+        assert!(wildcard_match("*a*b*", "xxaxxbxx"));
+        assert!(!wildcard_match("*.log", "app.log.txt"));
+        assert!(!wildcard_match("build-??", "build-1"));
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = CompressionPolicy {
+            min_size: Some(1024),
+            patterns: vec!["*.log".to_string()],
+        };
+        assert!(policy.applies("big.bin", 4096));
+        assert!(policy.applies("tiny.log", 1));
+        assert!(!policy.applies("tiny.bin", 1));
+        assert!(!CompressionPolicy::default().applies("big.log", u64::MAX));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_directory_roundtrip_keeps_metadata() {
+        use std::os::unix::fs::{PermissionsExt, symlink};
+
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().join("src");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("sub/run.sh"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(src.join("sub/run.sh"), fs::Permissions::from_mode(0o750)).unwrap();
+        symlink("sub/run.sh", src.join("link")).unwrap();
+        let old = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        File::options()
+            .write(true)
+            .open(src.join("sub/run.sh"))
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        let blob = tmp.path().join("blob");
+        assert_eq!(compress(&src, &blob, None).unwrap(), Compression::ZstdTar);
+        let out = tmp.path().join("out");
+        decompress(&blob, Compression::ZstdTar, &out, None).unwrap();
+
+        let script = fs::metadata(out.join("sub/run.sh")).unwrap();
+        assert_eq!(script.permissions().mode() & 0o777, 0o750);
+        assert_eq!(script.modified().unwrap(), old);
+        assert_eq!(
+            fs::read_link(out.join("link")).unwrap(),
+            Path::new("sub/run.sh")
+        );
+    }
+
+    #[test]
+    fn test_file_roundtrip() {
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().join("app.log");
+        fs::write(&src, "line\n".repeat(1000)).unwrap();
+
+        let blob = tmp.path().join("blob");
+        assert_eq!(compress(&src, &blob, None).unwrap(), Compression::Zstd);
+        assert!(fs::metadata(&blob).unwrap().len() < 5000);
+
+        let out = tmp.path().join("out");
+        decompress(&blob, Compression::Zstd, &out, None).unwrap();
+        assert_eq!(fs::read(&out).unwrap(), fs::read(&src).unwrap());
+    }
+}
@@ -0,0 +1,164 @@
+use age::secrecy::SecretString;
+use anyhow::Result;
+use rust_i18n::t;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+/// Where the managed trash gets the key for encrypting content at rest.
+///
+/// Configured by `SAFERM_ENCRYPT_KEY_FILE` (an age X25519 identity, as written
+/// by `age-keygen`) or `SAFERM_ENCRYPT_PASSPHRASE_FILE` (a file holding a
+/// passphrase). The key file wins if both are set. The key is only read when
+/// content is actually encrypted or decrypted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum KeySource {
+    KeyFile(PathBuf),
+    PassphraseFile(PathBuf),
+}
+
+impl KeySource {
+    pub fn from_env() -> Option<Self> {
+        let var = |name| std::env::var_os(name).filter(|v| !v.is_empty());
+        if let Some(path) = var("SAFERM_ENCRYPT_KEY_FILE") {
+            return Some(KeySource::KeyFile(PathBuf::from(path)));
+        }
+        var("SAFERM_ENCRYPT_PASSPHRASE_FILE").map(|p| KeySource::PassphraseFile(PathBuf::from(p)))
+    }
+
+    pub fn load(&self) -> Result<Cipher> {
+        let (KeySource::KeyFile(path) | KeySource::PassphraseFile(path)) = self;
+        let fail = |reason: String| {
+            anyhow::anyhow!(t!(
+                "error_encryption_key",
+                path = path.display().to_string(),
+                reason = reason
+            ))
+        };
+        let content = std::fs::read_to_string(path).map_err(|e| fail(e.to_string()))?;
+
+        match self {
+            KeySource::KeyFile(_) => {
+                let line = content
+                    .lines()
+                    .map(str::trim)
+                    .find(|l| !l.is_empty() && !l.starts_with('#'))
+                    .ok_or_else(|| fail("no age identity found".to_string()))?;
+                let identity = line
+                    .parse::<age::x25519::Identity>()
+                    .map_err(|e| fail(e.to_string()))?;
+                Ok(Cipher::Identity(identity))
+            }
+            KeySource::PassphraseFile(_) => {
+                let passphrase = content.trim_end_matches(['\n', '\r']);
+                if passphrase.is_empty() {
+                    return Err(fail("passphrase is empty".to_string()));
+                }
+                Ok(Cipher::Passphrase(SecretString::from(passphrase)))
+            }
+        }
+    }
+}
+
+/// A loaded key that encrypts and decrypts age streams.
+pub(crate) enum Cipher {
+    Identity(age::x25519::Identity),
+    Passphrase(SecretString),
+}
+
+impl Cipher {
+    /// Wrap `output` so everything written to it is encrypted. The returned
+    /// writer must be finished with `finish()` to write the last chunk.
+    pub fn encrypt<W: Write>(&self, output: W) -> Result<age::stream::StreamWriter<W>> {
+        let encryptor = match self {
+            Cipher::Identity(identity) => {
+                let recipient = identity.to_public();
+                age::Encryptor::with_recipients(std::iter::once(&recipient as &dyn age::Recipient))?
+            }
+            Cipher::Passphrase(passphrase) => {
+                age::Encryptor::with_user_passphrase(passphrase.clone())
+            }
+        };
+        Ok(encryptor.wrap_output(output)?)
+    }
+
+    /// Decrypt an age stream; a wrong key or a damaged header is reported as
+    /// a localized error.
+    pub fn decrypt<R: BufRead>(&self, input: R) -> Result<age::stream::StreamReader<R>> {
+        let failed = |_| anyhow::anyhow!(t!("error_decrypt_failed"));
+        let decryptor = age::Decryptor::new_buffered(input).map_err(failed)?;
+        match self {
+            Cipher::Identity(identity) => {
+                decryptor.decrypt(std::iter::once(identity as &dyn age::Identity))
+            }
+            Cipher::Passphrase(passphrase) => {
+                let identity = age::scrypt::Identity::new(passphrase.clone());
+                decryptor.decrypt(std::iter::once(&identity as &dyn age::Identity))
+            }
+        }
+        .map_err(failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use tempfile::TempDir;
+
+    fn roundtrip(cipher: &Cipher, plaintext: &[u8]) -> Vec<u8> {
+        let mut writer = cipher.encrypt(vec![]).unwrap();
+        writer.write_all(plaintext).unwrap();
+        let ciphertext = writer.finish().unwrap();
+        assert!(!ciphertext.windows(plaintext.len()).any(|w| w == plaintext));
+
+        let mut out = vec![];
+        cipher
+            .decrypt(ciphertext.as_slice())
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn test_key_file_roundtrip() {
+        let tmp = TempDir::new().unwrap();
+        let key = tmp.path().join("key.txt");
+        let identity = age::x25519::Identity::generate();
+        std::fs::write(
+            &key,
+            format!(
+                "# created: now\n{}\n",
+                age::secrecy::ExposeSecret::expose_secret(&identity.to_string())
+            ),
+        )
+        .unwrap();
+
+        let cipher = KeySource::KeyFile(key).load().unwrap();
+        assert_eq!(roundtrip(&cipher, b"API_TOKEN=secret"), b"API_TOKEN=secret");
+
+        // Another key cannot read it
+        let other = Cipher::Identity(age::x25519::Identity::generate());
+        let mut writer = cipher.encrypt(vec![]).unwrap();
+        writer.write_all(b"x").unwrap();
+        let ciphertext = writer.finish().unwrap();
+        assert!(other.decrypt(ciphertext.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_missing_or_invalid_key() {
+        let tmp = TempDir::new().unwrap();
+        assert!(
+            KeySource::KeyFile(tmp.path().join("missing"))
+                .load()
+                .is_err()
+        );
+
+        let bad = tmp.path().join("bad.txt");
+        std::fs::write(&bad, "# only a comment\n").unwrap();
+        assert!(KeySource::KeyFile(bad.clone()).load().is_err());
+
+        std::fs::write(&bad, "\n").unwrap();
+        assert!(KeySource::PassphraseFile(bad).load().is_err());
+    }
+}
@@ -0,0 +1,501 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use super::checksum::content_hash;
+use super::compress::{apply_metadata, file_mode, file_mtime};
+use super::encoding::{escape_field, unescape_field};
+
+/// First line of a manifest. Bump the version when the line format changes.
+const HEADER: &str = "saferm-manifest 1";
+
+/// Content-addressed store behind deduplicated managed trash entries.
+///
+/// Every regular file is kept once under `objects/<2 hex>/<62 hex>`, named by
+/// the SHA-256 of its bytes, and entries become manifests of their tree. Blobs
+/// are hard links of the trashed files where possible, so ingesting costs no
+/// copies; files that have other links are copied instead. Enabled with
+/// `SAFERM_DEDUP=1`.
+pub(crate) struct ObjectStore {
+    dir: PathBuf,
+    lock_path: PathBuf,
+}
+
+/// One node of a manifest; `path` is relative to the entry root (`.` for the root).
+#[derive(Debug, PartialEq, Eq)]
+enum Node {
+    Dir {
+        path: String,
+        mode: Option<u32>,
+        mtime: Option<i64>,
+    },
+    File {
+        path: String,
+        mode: Option<u32>,
+        mtime: Option<i64>,
+        hash: String,
+    },
+    Symlink {
+        path: String,
+        target: String,
+    },
+}
+
+impl ObjectStore {
+    pub fn new(base_dir: &Path) -> Self {
+        Self {
+            dir: base_dir.join("objects"),
+            lock_path: base_dir.join("objects.lock"),
+        }
+    }
+
+    pub fn enabled_from_env() -> bool {
+        std::env::var("SAFERM_DEDUP").is_ok_and(|v| matches!(v.as_str(), "1" | "true" | "yes"))
+    }
+
+    /// Serialize ingests against garbage collection: a blob must not be
+    /// collected between being linked and its manifest being written.
+    pub fn lock(&self) -> Result<File> {
+        if let Some(parent) = self.lock_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create trash dir: {:?}", parent))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&self.lock_path)
+            .with_context(|| format!("failed to open object lock: {:?}", self.lock_path))?;
+        file.lock()
+            .with_context(|| format!("failed to lock objects: {:?}", self.lock_path))?;
+        Ok(file)
+    }
+
+    /// Store every file below `root` and return the manifest describing the tree.
+    /// `root` itself is left in place; the caller removes it once the manifest is saved.
+    /// The caller must hold [`lock`](Self::lock).
+    pub fn ingest(&self, root: &Path) -> Result<String> {
+        let mut nodes = vec![];
+        self.ingest_node(root, ".".to_string(), &mut nodes)?;
+
+        let mut manifest = format!("{}\n", HEADER);
+        for node in &nodes {
+            manifest.push_str(&format_node(node));
+            manifest.push('\n');
+        }
+        Ok(manifest)
+    }
+
+    fn ingest_node(&self, path: &Path, rel: String, nodes: &mut Vec<Node>) -> Result<()> {
+        let meta = fs::symlink_metadata(path)
+            .with_context(|| format!("failed to read metadata: {:?}", path))?;
+        if meta.is_symlink() {
+            let target = fs::read_link(path)
+                .with_context(|| format!("failed to read symlink: {:?}", path))?;
+            nodes.push(Node::Symlink {
+                path: rel,
+                target: utf8(&target)?.to_string(),
+            });
+        } else if meta.is_dir() {
+            nodes.push(Node::Dir {
+                path: rel.clone(),
+                mode: file_mode(&meta),
+                mtime: file_mtime(&meta),
+            });
+            let mut children: Vec<_> = fs::read_dir(path)
+                .with_context(|| format!("failed to read dir: {:?}", path))?
+                .map(|e| e.map(|e| e.file_name()))
+                .collect::<std::io::Result<_>>()?;
+            children.sort();
+            for name in children {
+                let child_rel = match rel.as_str() {
+                    "." => utf8(Path::new(&name))?.to_string(),
+                    _ => format!("{}/{}", rel, utf8(Path::new(&name))?),
+                };
+                self.ingest_node(&path.join(&name), child_rel, nodes)?;
+            }
+        } else if !meta.is_file() {
+            // Rebuilding would have to read a FIFO or device back out of the store
+            anyhow::bail!("cannot deduplicate special file: {:?}", path);
+        } else {
+            let hash = self.store_blob(path)?;
+            nodes.push(Node::File {
+                path: rel,
+                mode: file_mode(&meta),
+                mtime: file_mtime(&meta),
+                hash,
+            });
+        }
+        Ok(())
+    }
+
+    /// Link (or copy) `path` into the store unless identical content is already there.
+    fn store_blob(&self, path: &Path) -> Result<String> {
+        let hash = content_hash(path)?;
+        let hex = hash
+            .strip_prefix("sha256:")
+            .context("unexpected hash algorithm")?
+            .to_string();
+        let object = self.object_path(&hex);
+        if object.exists() {
+            return Ok(hex);
+        }
+        if let Some(parent) = object.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create object dir: {:?}", parent))?;
+        }
+        // A file with links outside the trash can still be edited through
+        // them, which would change the blob under every entry sharing it
+        let linked = only_link(path)
+            && match fs::hard_link(path, &object) {
+                Ok(()) => true,
+                Err(e) => e.kind() == std::io::ErrorKind::AlreadyExists,
+            };
+        if !linked {
+            // Copy, then publish atomically
+            let tmp = object.with_extension(format!("tmp-{}", std::process::id()));
+            fs::copy(path, &tmp).with_context(|| format!("failed to store: {:?}", path))?;
+            fs::rename(&tmp, &object).with_context(|| format!("failed to store: {:?}", object))?;
+        }
+        Ok(hex)
+    }
+
+    fn object_path(&self, hex: &str) -> PathBuf {
+        let (fan_out, rest) = hex.split_at(2.min(hex.len()));
+        self.dir.join(fan_out).join(rest)
+    }
+
+    /// Recreate the tree described by `manifest` at the new path `dest`.
+    /// Blobs are copied, never linked, so edits to the result can't reach the store.
+    pub fn rebuild(&self, manifest: &str, dest: &Path) -> Result<()> {
+        let nodes = parse_manifest(manifest)?;
+        let target = |rel: &str| match rel {
+            "." => dest.to_path_buf(),
+            _ => dest.join(rel),
+        };
+
+        let mut dirs = vec![];
+        for node in &nodes {
+            match node {
+                Node::Dir { path, mode, mtime } => {
+                    let dir = target(path);
+                    fs::create_dir(&dir).with_context(|| format!("failed to create: {:?}", dir))?;
+                    dirs.push((dir, *mode, *mtime));
+                }
+                Node::File {
+                    path,
+                    mode,
+                    mtime,
+                    hash,
+                } => {
+                    let file = target(path);
+                    fs::copy(self.object_path(hash), &file)
+                        .with_context(|| format!("missing object {} for {:?}", hash, file))?;
+                    apply_metadata(&file, *mode, *mtime)?;
+                }
+                Node::Symlink { path, target: link } => {
+                    let file = target(path);
+                    make_symlink(Path::new(link), &file)?;
+                }
+            }
+        }
+        // Directories last and deepest first: filling them updates their mtime,
+        // and a read-only mode would block the children.
+        for (dir, mode, mtime) in dirs.into_iter().rev() {
+            apply_metadata(&dir, mode, mtime)?;
+        }
+        Ok(())
+    }
+
+    /// Remove every blob with `remove` (used when the whole trash is emptied).
+    pub fn purge(&self, remove: impl Fn(&Path) -> Result<()>) -> Result<()> {
+        if !self.dir.exists() {
+            return Ok(());
+        }
+        let _lock = self.lock()?;
+        remove(&self.dir)
+    }
+
+    /// Remove blobs no manifest returned by `manifests` refers to with
+    /// `remove`. Returns how many were removed.
+    ///
+    /// `manifests` runs under the lock, so an ingest can't add a blob and a
+    /// manifest between the scan and the sweep.
+    pub fn gc(
+        &self,
+        manifests: impl FnOnce() -> Result<Vec<String>>,
+        remove: impl Fn(&Path) -> Result<()>,
+    ) -> Result<usize> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+        let _lock = self.lock()?;
+        let mut live = HashSet::new();
+        for manifest in manifests()? {
+            for node in parse_manifest(&manifest)? {
+                if let Node::File { hash, .. } = node {
+                    live.insert(hash);
+                }
+            }
+        }
+
+        let mut removed = 0;
+        for fan_out in fs::read_dir(&self.dir)? {
+            let fan_out = fan_out?;
+            let prefix = fan_out.file_name().to_string_lossy().into_owned();
+            for object in fs::read_dir(fan_out.path())? {
+                let object = object?;
+                let hex = format!("{}{}", prefix, object.file_name().to_string_lossy());
+                if !live.contains(&hex) {
+                    remove(&object.path())?;
+                    removed += 1;
+                }
+            }
+            // Only succeeds once the fan-out dir is empty
+            let _ = fs::remove_dir(fan_out.path());
+        }
+        Ok(removed)
+    }
+}
+
+/// Whether `path` is the only name of its file, so linking it into the store
+/// can't expose the blob to outside edits.
+#[cfg(unix)]
+fn only_link(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).is_ok_and(|meta| meta.nlink() == 1)
+}
+
+#[cfg(not(unix))]
+fn only_link(_path: &Path) -> bool {
+    false
+}
+
+fn utf8(path: &Path) -> Result<&str> {
+    path.to_str()
+        .with_context(|| format!("unsupported file name: {:?}", path))
+}
+
+#[cfg(unix)]
+fn make_symlink(target: &Path, link: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(target, link)
+        .with_context(|| format!("failed to create symlink: {:?}", link))
+}
+
+#[cfg(not(unix))]
+fn make_symlink(_target: &Path, link: &Path) -> Result<()> {
+    anyhow::bail!("symlinks are not supported here: {:?}", link)
+}
+
+fn format_node(node: &Node) -> String {
+    let number = |n: Option<i64>| n.map(|n| n.to_string()).unwrap_or_default();
+    let mode = |m: Option<u32>| m.map(|m| format!("{:o}", m)).unwrap_or_default();
+    match node {
+        Node::Dir {
+            path,
+            mode: m,
+            mtime,
+        } => format!(
+            "d\t{}\t{}\t{}",
+            mode(*m),
+            number(*mtime),
+            escape_field(path)
+        ),
+        Node::File {
+            path,
+            mode: m,
+            mtime,
+            hash,
+        } => format!(
+            "f\t{}\t{}\t{}\t{}",
+            mode(*m),
+            number(*mtime),
+            hash,
+            escape_field(path)
+        ),
+        Node::Symlink { path, target } => {
+            format!("l\t{}\t{}", escape_field(target), escape_field(path))
+        }
+    }
+}
+
+fn parse_manifest(manifest: &str) -> Result<Vec<Node>> {
+    let mut lines = manifest.lines();
+    if lines.next() != Some(HEADER) {
+        anyhow::bail!("invalid manifest: unknown header");
+    }
+    lines
+        .map(|line| parse_node(line).with_context(|| format!("invalid manifest line: {}", line)))
+        .collect()
+}
+
+fn parse_node(line: &str) -> Option<Node> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    let mode = |f: &str| u32::from_str_radix(f, 8).ok();
+    let mtime = |f: &str| f.parse().ok();
+    match fields.as_slice() {
+        ["d", m, t, path] => Some(Node::Dir {
+            path: unescape_field(path),
+            mode: mode(m),
+            mtime: mtime(t),
+        }),
+        ["f", m, t, hash, path] => Some(Node::File {
+            path: unescape_field(path),
+            mode: mode(m),
+            mtime: mtime(t),
+            hash: hash.to_string(),
+        }),
+        ["l", target, path] => Some(Node::Symlink {
+            path: unescape_field(path),
+            target: unescape_field(target),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make_tree(dir: &Path) {
+        fs::create_dir_all(dir.join("lib/a")).unwrap();
+        fs::write(dir.join("lib/a/index.js"), "module.exports = 1;").unwrap();
+        fs::write(dir.join("lib/copy.js"), "module.exports = 1;").unwrap();
+        fs::write(dir.join("tab\tname.txt"), "odd").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("lib/a/index.js", dir.join("main.js")).unwrap();
+    }
+
+    fn remove(path: &Path) -> Result<()> {
+        Ok(fs::remove_file(path)?)
+    }
+
+    fn object_count(store_dir: &Path) -> usize {
+        fs::read_dir(store_dir.join("objects"))
+            .map(|d| {
+                d.map(|e| fs::read_dir(e.unwrap().path()).unwrap().count())
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_ingest_dedups_and_rebuilds() {
+        let tmp = TempDir::new().unwrap();
+        let store = ObjectStore::new(tmp.path());
+        let first = tmp.path().join("first");
+        let second = tmp.path().join("second");
+        make_tree(&first);
+        make_tree(&second);
+
+        let m1 = store.ingest(&first).unwrap();
+        let m2 = store.ingest(&second).unwrap();
+        // Two identical trees share the same two blobs
+        assert_eq!(object_count(tmp.path()), 2);
+
+        let out = tmp.path().join("out");
+        store.rebuild(&m2, &out).unwrap();
+        assert_eq!(content_hash(&out).unwrap(), content_hash(&second).unwrap());
+        assert_eq!(
+            fs::read_to_string(out.join("tab\tname.txt")).unwrap(),
+            "odd"
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            fs::read_link(out.join("main.js")).unwrap(),
+            Path::new("lib/a/index.js")
+        );
+
+        // Blobs referenced by any manifest survive, the rest are collected
+        assert_eq!(store.gc(|| Ok(vec![m1.clone()]), remove).unwrap(), 0);
+        assert_eq!(store.gc(|| Ok(vec![]), remove).unwrap(), 2);
+        assert_eq!(object_count(tmp.path()), 0);
+    }
+
+    #[test]
+    fn test_ingest_during_gc_scan_survives() {
+        let tmp = TempDir::new().unwrap();
+        let store = ObjectStore::new(tmp.path());
+        let old = tmp.path().join("old.txt");
+        fs::write(&old, "old").unwrap();
+        store.ingest(&old).unwrap();
+        let new = tmp.path().join("new.txt");
+        fs::write(&new, "new").unwrap();
+
+        let (scanning, scanned) = std::sync::mpsc::channel();
+        let ingester = {
+            let base = tmp.path().to_path_buf();
+            let new = new.clone();
+            std::thread::spawn(move || {
+                scanned.recv().unwrap();
+                let store = ObjectStore::new(&base);
+                let _lock = store.lock().unwrap();
+                store.ingest(&new).unwrap()
+            })
+        };
+        let removed = store
+            .gc(
+                || {
+                    // Another process ingests right after the scan
+                    scanning.send(()).unwrap();
+                    std::thread::sleep(std::time::Duration::from_millis(100));
+                    Ok(vec![])
+                },
+                remove,
+            )
+            .unwrap();
+        let manifest = ingester.join().unwrap();
+
+        assert_eq!(removed, 1);
+        let out = tmp.path().join("out.txt");
+        store.rebuild(&manifest, &out).unwrap();
+        assert_eq!(fs::read_to_string(out).unwrap(), "new");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_with_outside_links_is_copied() {
+        let tmp = TempDir::new().unwrap();
+        let store = ObjectStore::new(tmp.path());
+        let file = tmp.path().join("a.txt");
+        let outside = tmp.path().join("outside.txt");
+        fs::write(&file, "hello").unwrap();
+        fs::hard_link(&file, &outside).unwrap();
+
+        let manifest = store.ingest(&file).unwrap();
+        fs::write(&outside, "changed").unwrap();
+
+        let out = tmp.path().join("out.txt");
+        store.rebuild(&manifest, &out).unwrap();
+        assert_eq!(fs::read_to_string(out).unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_single_file_manifest() {
+        let tmp = TempDir::new().unwrap();
+        let store = ObjectStore::new(tmp.path());
+        let file = tmp.path().join("a.txt");
+        fs::write(&file, "hello").unwrap();
+
+        let manifest = store.ingest(&file).unwrap();
+        assert_eq!(manifest.lines().count(), 2);
+
+        let out = tmp.path().join("b.txt");
+        store.rebuild(&manifest, &out).unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_manifest_roundtrip() {
+        let node = Node::File {
+            path: "dir/with\ttab".to_string(),
+            mode: Some(0o644),
+            mtime: Some(1_700_000_000),
+            hash: "ab".repeat(32),
+        };
+        assert_eq!(parse_node(&format_node(&node)), Some(node));
+        assert!(parse_manifest("not a manifest\n").is_err());
+    }
+}
@@ -0,0 +1,109 @@
+/// Percent-encode the characters that would break a line of tab-separated fields.
+pub(crate) fn escape_field(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '%' => out.push_str("%25"),
+            '\t' => out.push_str("%09"),
+            '\n' => out.push_str("%0A"),
+            '\r' => out.push_str("%0D"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Reverse [`escape_field`].
+pub(crate) fn unescape_field(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(pos) = rest.find('%') {
+        out.push_str(&rest[..pos]);
+        let code = rest.get(pos + 1..pos + 3);
+        match code.and_then(|c| u8::from_str_radix(c, 16).ok()) {
+            Some(byte) if byte.is_ascii() => {
+                out.push(byte as char);
+                rest = &rest[pos + 3..];
+            }
+            _ => {
+                out.push('%');
+                rest = &rest[pos + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Percent-encode a path the way FreeDesktop `.trashinfo` files store it:
+/// every byte but unreserved URI characters and `/` becomes `%XX`.
+pub(crate) fn encode_uri_path(path: &std::path::Path) -> String {
+    let mut out = String::new();
+    for &byte in path.as_os_str().as_encoded_bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+/// Reverse [`encode_uri_path`]; malformed escapes are kept as they are.
+pub(crate) fn decode_uri_path(value: &str) -> std::path::PathBuf {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        std::ffi::OsString::from_vec(out).into()
+    }
+    #[cfg(not(unix))]
+    {
+        String::from_utf8_lossy(&out).into_owned().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_roundtrip() {
+        let value = "a\tb\nc\rd%e 日本";
+        let escaped = escape_field(value);
+        assert!(!escaped.contains(['\t', '\n', '\r']));
+        assert_eq!(unescape_field(&escaped), value);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_uri_path_roundtrip() {
+        let path = std::path::Path::new("/home/u/a b/100%/日本.txt");
+        let encoded = encode_uri_path(path);
+        assert_eq!(encoded, "/home/u/a%20b/100%25/%E6%97%A5%E6%9C%AC.txt");
+        assert_eq!(decode_uri_path(&encoded), path);
+        assert_eq!(
+            decode_uri_path("/tmp/50%zz"),
+            std::path::Path::new("/tmp/50%zz")
+        );
+    }
+}
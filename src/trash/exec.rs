@@ -0,0 +1,393 @@
+use anyhow::{Context, Result};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use std::ffi::{OsStr, OsString};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use super::{Annotation, RestorableItem, TrashHandler};
+use crate::prompt::Prompter;
+
+/// Version of the JSON protocol spoken with helpers.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Seconds a helper may take per request unless `SAFERM_EXEC_TIMEOUT` says otherwise.
+const DEFAULT_TIMEOUT: u64 = 30;
+
+/// A backend implemented by an external helper (`SAFERM_TRASH_BACKEND=exec:/path/to/helper`).
+///
+/// Each operation runs the helper once. saferm writes one JSON request to its
+/// stdin and closes it; the helper answers with one JSON object on stdout and
+/// exits with status 0:
+///
+/// ```text
+/// {"protocol":1,"op":"trash","path":"/abs/file","reason":null,"tags":[]}  -> {"ok":true,"id":"..."}
+/// {"protocol":1,"op":"cleanup","include_pinned":false}                    -> {"ok":true}
+/// {"protocol":1,"op":"list_restorable","filter":null,"tag":null}          -> {"ok":true,"items":[...]}
+/// {"protocol":1,"op":"restore_to","id":"...","destination":"/abs/file"}   -> {"ok":true}
+/// {"protocol":1,"op":"backend_name"}                                      -> {"ok":true,"name":"..."}
+/// ```
+///
+/// Listed items carry `id` and `original_path`, and optionally `deleted_at`
+/// (unix seconds), `size`, `pinned`, `reason` and `tags`. A helper reports a
+/// failure as `{"ok":false,"error":"..."}`. Its stderr is passed through.
+pub struct ExecTrash {
+    helper: PathBuf,
+    timeout: Duration,
+    name: OnceLock<&'static str>,
+    /// Held for each request: helpers are not expected to cope with
+    /// concurrent calls, even when saferm removes targets in parallel
+    busy: Mutex<()>,
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    protocol: u32,
+    #[serde(flatten)]
+    request: Request<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Request<'a> {
+    Trash {
+        path: &'a str,
+        reason: Option<&'a str>,
+        tags: &'a [String],
+    },
+    Cleanup {
+        include_pinned: bool,
+    },
+    ListRestorable {
+        filter: Option<&'a str>,
+        tag: Option<&'a str>,
+    },
+    RestoreTo {
+        id: &'a str,
+        destination: &'a str,
+    },
+    BackendName,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    ok: bool,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    items: Vec<Item>,
+}
+
+#[derive(Deserialize)]
+struct Item {
+    id: String,
+    original_path: PathBuf,
+    #[serde(default)]
+    deleted_at: Option<i64>,
+    #[serde(default)]
+    size: Option<u64>,
+    #[serde(default)]
+    pinned: bool,
+    #[serde(default)]
+    reason: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+impl ExecTrash {
+    pub fn new(helper: PathBuf) -> Self {
+        let timeout = match std::env::var("SAFERM_EXEC_TIMEOUT") {
+            Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+                eprintln!(
+                    "saferm: warning: invalid SAFERM_EXEC_TIMEOUT '{}', ignoring",
+                    value
+                );
+                DEFAULT_TIMEOUT
+            }),
+            Err(_) => DEFAULT_TIMEOUT,
+        };
+        Self::with_timeout(helper, Duration::from_secs(timeout))
+    }
+
+    pub fn with_timeout(helper: PathBuf, timeout: Duration) -> Self {
+        Self {
+            helper,
+            timeout,
+            name: OnceLock::new(),
+            busy: Mutex::new(()),
+        }
+    }
+
+    fn helper_name(&self) -> String {
+        self.helper.display().to_string()
+    }
+
+    /// Send one request and wait for the answer, at most `self.timeout`.
+    fn call(&self, request: Request) -> Result<Response> {
+        let _busy = self.busy.lock().unwrap_or_else(|e| e.into_inner());
+        let helper = self.helper_name();
+        let fail = |reason: String| {
+            anyhow::anyhow!(t!("error_exec_failed", helper = helper, reason = reason))
+        };
+
+        let mut input = serde_json::to_string(&Envelope {
+            protocol: PROTOCOL_VERSION,
+            request,
+        })?;
+        input.push('\n');
+
+        let mut child = Command::new(&self.helper)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| fail(e.to_string()))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // A helper that exits without reading its request is judged by its answer
+            let _ = stdin.write_all(input.as_bytes());
+        }
+        let mut stdout = child
+            .stdout
+            .take()
+            .context("helper stdout is not captured")?;
+        let reader = std::thread::spawn(move || {
+            let mut output = String::new();
+            stdout.read_to_string(&mut output).map(|_| output)
+        });
+
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                anyhow::bail!(t!(
+                    "error_exec_timeout",
+                    helper = helper,
+                    seconds = self.timeout.as_secs()
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        let output = reader
+            .join()
+            .map_err(|_| fail("failed to read its output".to_string()))?
+            .map_err(|e| fail(e.to_string()))?;
+        if !status.success() {
+            return Err(fail(status.to_string()));
+        }
+
+        let response: Response = serde_json::from_str(output.trim()).map_err(|e| {
+            anyhow::anyhow!(t!(
+                "error_exec_protocol",
+                helper = helper,
+                reason = e.to_string()
+            ))
+        })?;
+        if !response.ok {
+            let reason = response
+                .error
+                .unwrap_or_else(|| "no reason given".to_string());
+            return Err(fail(reason));
+        }
+        Ok(response)
+    }
+}
+
+/// Paths travel as JSON strings, so they must be valid UTF-8.
+fn utf8<'a>(value: &'a OsStr, what: &str) -> Result<&'a str> {
+    value
+        .to_str()
+        .with_context(|| format!("{} is not valid UTF-8: {:?}", what, value))
+}
+
+impl TrashHandler for ExecTrash {
+    fn trash(&self, path: &Path) -> crate::Result<Option<OsString>> {
+        self.trash_annotated(path, &Annotation::default())
+    }
+
+    fn trash_annotated(
+        &self,
+        path: &Path,
+        annotation: &Annotation,
+    ) -> crate::Result<Option<OsString>> {
+        // Absolute without resolving symlinks, so the helper sees the link itself
+        let absolute = std::path::absolute(path)
+            .with_context(|| format!("failed to resolve path: {:?}", path))?;
+        let response = self.call(Request::Trash {
+            path: utf8(absolute.as_os_str(), "path")?,
+            reason: annotation.reason.as_deref(),
+            tags: &annotation.tags,
+        })?;
+        Ok(response.id.map(OsString::from))
+    }
+
+    fn cleanup(&self, prompter: &dyn Prompter, include_pinned: bool) -> crate::Result<()> {
+        if !prompter.confirm(&t!("confirm_cleanup"))? {
+            println!("{}", t!("cleanup_cancelled"));
+            return Ok(());
+        }
+        self.call(Request::Cleanup { include_pinned })?;
+        println!("{}", t!("cleanup_success"));
+        Ok(())
+    }
+
+    fn backend_name(&self) -> &'static str {
+        self.name.get_or_init(|| {
+            match self
+                .call(Request::BackendName)
+                .map(|response| response.name)
+            {
+                // Leaked once per process; items and routes need a static label
+                Ok(Some(name)) if !name.is_empty() => Box::leak(name.into_boxed_str()),
+                Ok(_) => "exec",
+                Err(e) => {
+                    eprintln!("saferm: warning: {:#}", e);
+                    "exec"
+                }
+            }
+        })
+    }
+
+    fn list_restorable(
+        &self,
+        filter: Option<&str>,
+        tag: Option<&str>,
+    ) -> crate::Result<Vec<RestorableItem>> {
+        let response = self.call(Request::ListRestorable { filter, tag })?;
+        let backend = self.backend_name();
+        Ok(response
+            .items
+            .into_iter()
+            .map(|item| RestorableItem {
+                display_name: item
+                    .original_path
+                    .file_name()
+                    .unwrap_or(OsStr::new(&item.id))
+                    .to_os_string(),
+                id: OsString::from(item.id),
+                original_path: item.original_path,
+                deleted_at: item.deleted_at,
+                size: item.size,
+                pinned: item.pinned,
+                annotation: Annotation {
+                    reason: item.reason,
+                    tags: item.tags,
+                },
+                backend,
+            })
+            .collect())
+    }
+
+    fn restore_to(&self, item_id: &OsStr, destination: &Path) -> crate::Result<()> {
+        self.call(Request::RestoreTo {
+            id: utf8(item_id, "item id")?,
+            destination: utf8(destination.as_os_str(), "destination")?,
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::prompt::AutoConfirmPrompter;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    /// A helper script that logs its request and runs `body`.
+    fn helper(dir: &TempDir, body: &str) -> PathBuf {
+        let path = dir.path().join("helper");
+        fs::write(
+            &path,
+            format!("#!/bin/sh\ncat > \"$(dirname \"$0\")/request\"\n{}\n", body),
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn request(dir: &TempDir) -> serde_json::Value {
+        serde_json::from_str(&fs::read_to_string(dir.path().join("request")).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_requests_and_responses() {
+        let dir = TempDir::new().unwrap();
+        let trash = ExecTrash::new(helper(
+            &dir,
+            r#"echo '{"ok":true,"id":"snap-1","name":"snapshots","items":[{"id":"snap-1","original_path":"/srv/a.txt","deleted_at":5,"tags":["x"]}]}'"#,
+        ));
+
+        let annotation = Annotation {
+            reason: Some("old".to_string()),
+            tags: vec!["x".to_string()],
+        };
+        let id = trash
+            .trash_annotated(Path::new("/srv/a.txt"), &annotation)
+            .unwrap();
+        assert_eq!(id, Some(OsString::from("snap-1")));
+        assert_eq!(
+            request(&dir),
+            serde_json::json!({
+                "protocol": 1, "op": "trash", "path": "/srv/a.txt", "reason": "old", "tags": ["x"]
+            })
+        );
+
+        let items = trash.list_restorable(Some("a"), None).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].original_path, Path::new("/srv/a.txt"));
+        assert_eq!(items[0].display_name, "a.txt");
+        assert_eq!(items[0].backend, "snapshots");
+        assert_eq!(items[0].annotation.tags, vec!["x".to_string()]);
+
+        trash
+            .restore_to(OsStr::new("snap-1"), Path::new("/srv/b.txt"))
+            .unwrap();
+        assert_eq!(request(&dir)["op"], "restore_to");
+        assert_eq!(request(&dir)["destination"], "/srv/b.txt");
+
+        trash.cleanup(&AutoConfirmPrompter, true).unwrap();
+        assert_eq!(request(&dir)["include_pinned"], true);
+    }
+
+    #[test]
+    fn test_helper_failures() {
+        let dir = TempDir::new().unwrap();
+        let refusing = ExecTrash::new(helper(&dir, r#"echo '{"ok":false,"error":"disk full"}'"#));
+        let err = refusing.trash(Path::new("/tmp/x")).unwrap_err();
+        assert!(format!("{:#}", err).contains("disk full"));
+        assert_eq!(refusing.backend_name(), "exec");
+
+        let garbled = ExecTrash::new(helper(&dir, "echo hello"));
+        assert!(garbled.list_restorable(None, None).is_err());
+
+        let crashing = ExecTrash::new(helper(&dir, "exit 3"));
+        assert!(crashing.list_restorable(None, None).is_err());
+
+        let missing = ExecTrash::new(dir.path().join("no-such-helper"));
+        assert!(missing.list_restorable(None, None).is_err());
+    }
+
+    #[test]
+    fn test_timeout_kills_helper() {
+        let dir = TempDir::new().unwrap();
+        let slow = ExecTrash::with_timeout(
+            helper(&dir, "sleep 5; echo '{\"ok\":true}'"),
+            Duration::from_millis(200),
+        );
+        let start = Instant::now();
+        assert!(slow.list_restorable(None, None).is_err());
+        assert!(start.elapsed() < Duration::from_secs(4));
+    }
+}
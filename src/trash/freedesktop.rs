@@ -0,0 +1,354 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use super::Annotation;
+use super::encoding::{decode_uri_path, encode_uri_path};
+use super::managed::move_across;
+#[cfg(all(unix, not(target_os = "macos")))]
+use super::os_trash::OsTrash;
+
+/// The FreeDesktop home trash (`$XDG_DATA_HOME/Trash`), read and written
+/// directly where the OS trash API can't keep an item's original path and
+/// deletion date, or isn't used at all (headless servers with items from
+/// `trash-cli` or `gio trash`).
+pub struct HomeTrash {
+    dir: PathBuf,
+}
+
+/// One item of a FreeDesktop trash directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// File name under `files/`
+    pub name: OsString,
+    /// The `.trashinfo` file, which also serves as the item id of the OS trash
+    pub info_path: PathBuf,
+    pub original_path: PathBuf,
+    pub deleted_at: Option<i64>,
+}
+
+impl HomeTrash {
+    pub fn new() -> Option<Self> {
+        dirs::data_dir().map(|dir| Self::at(dir.join("Trash")))
+    }
+
+    pub fn at(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn files_dir(&self) -> PathBuf {
+        self.dir.join("files")
+    }
+
+    fn info_dir(&self) -> PathBuf {
+        self.dir.join("info")
+    }
+
+    fn info_path(&self, name: &OsStr) -> PathBuf {
+        let mut file_name = name.to_os_string();
+        file_name.push(".trashinfo");
+        self.info_dir().join(file_name)
+    }
+
+    pub fn content_path(&self, name: &OsStr) -> PathBuf {
+        self.files_dir().join(name)
+    }
+
+    /// Whether `item_id` names an item of this trash (its `.trashinfo` path).
+    pub fn owns(&self, item_id: &OsStr) -> bool {
+        Path::new(item_id).parent() == Some(self.info_dir().as_path())
+    }
+
+    /// Every item with a readable `.trashinfo`, whether or not its content is there.
+    pub fn entries(&self) -> Result<Vec<Entry>> {
+        let read_dir = match fs::read_dir(self.info_dir()) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("failed to read dir: {:?}", self.info_dir()));
+            }
+        };
+        let mut entries = vec![];
+        for dir_entry in read_dir {
+            if let Some(entry) = self.entry(&dir_entry?.path()) {
+                entries.push(entry);
+            }
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+
+    /// The item whose `.trashinfo` is at `info_path`, if that file is readable.
+    pub fn entry(&self, info_path: &Path) -> Option<Entry> {
+        if info_path.extension() != Some(OsStr::new("trashinfo")) {
+            return None;
+        }
+        let name = info_path.file_stem()?.to_os_string();
+        let content = fs::read_to_string(info_path).ok()?;
+        let (original_path, deleted_at) = self.parse_info(&content)?;
+        Some(Entry {
+            name,
+            info_path: info_path.to_path_buf(),
+            original_path,
+            deleted_at,
+        })
+    }
+
+    /// The newest item trashed from `original_path`, looked up under the names
+    /// the OS trash gives an item (`name`, `name.2`, `name.3`, ...) up to the
+    /// first free one, without reading the rest of the trash.
+    pub fn newest_from(&self, original_path: &Path) -> Option<Entry> {
+        let name = original_path.file_name()?;
+        (1..)
+            .map(|n| {
+                let mut candidate = name.to_os_string();
+                if n > 1 {
+                    candidate.push(format!(".{}", n));
+                }
+                self.info_path(&candidate)
+            })
+            .take_while(|info_path| info_path.exists())
+            .filter_map(|info_path| self.entry(&info_path))
+            .filter(|entry| entry.original_path == original_path)
+            .max_by_key(|entry| entry.deleted_at)
+    }
+
+    /// Sizes of trashed directories from the `directorysizes` cache. A line
+    /// only counts while its mtime matches the item's `.trashinfo`, as the
+    /// spec requires; other directories have to be measured.
+    pub fn directory_sizes(&self) -> HashMap<OsString, u64> {
+        let Ok(content) = fs::read_to_string(self.dir.join("directorysizes")) else {
+            return HashMap::new();
+        };
+        content
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, ' ');
+                let size = fields.next()?.parse().ok()?;
+                let mtime: u64 = fields.next()?.parse().ok()?;
+                let name = decode_uri_path(fields.next()?).into_os_string();
+                let info_mtime = fs::metadata(self.info_path(&name))
+                    .and_then(|meta| meta.modified())
+                    .ok()?
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .ok()?
+                    .as_secs();
+                (info_mtime == mtime).then_some((name, size))
+            })
+            .collect()
+    }
+
+    /// Reason and tags saferm recorded for an item it trashed through the OS trash.
+    pub fn annotation(entry: &Entry) -> Annotation {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            OsTrash::read_note(entry.info_path.as_os_str())
+        }
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        {
+            let _ = entry;
+            Annotation::default()
+        }
+    }
+
+    /// Move the content of `entry` to `destination` and drop its metadata.
+    pub fn take(&self, entry: &Entry, destination: &Path) -> Result<()> {
+        move_across(&self.content_path(&entry.name), destination)
+            .with_context(|| format!("failed to move {:?} to {:?}", entry.name, destination))?;
+        self.drop_entry(entry)
+    }
+
+    /// Drop the metadata of `entry` and any note saferm kept for it.
+    pub fn drop_entry(&self, entry: &Entry) -> Result<()> {
+        self.forget(&entry.name)?;
+        #[cfg(all(unix, not(target_os = "macos")))]
+        OsTrash::remove_note(entry.info_path.as_os_str());
+        Ok(())
+    }
+
+    fn parse_info(&self, content: &str) -> Option<(PathBuf, Option<i64>)> {
+        let mut lines = content.lines();
+        if lines.next()?.trim() != "[Trash Info]" {
+            return None;
+        }
+        let mut path = None;
+        let mut date = None;
+        for line in lines {
+            if let Some(value) = line.strip_prefix("Path=") {
+                let decoded = decode_uri_path(value.trim());
+                // Relative paths are relative to the directory holding the trash
+                path = Some(match (decoded.is_relative(), self.dir.parent()) {
+                    (true, Some(top)) => top.join(decoded),
+                    _ => decoded,
+                });
+            } else if let Some(value) = line.strip_prefix("DeletionDate=")
+                && let Ok(dt) =
+                    chrono::NaiveDateTime::parse_from_str(value.trim(), "%Y-%m-%dT%H:%M:%S")
+                && let chrono::LocalResult::Single(local_dt) = dt.and_local_timezone(Local)
+            {
+                date = Some(local_dt.timestamp());
+            }
+        }
+        Some((path?, date))
+    }
+
+    /// Write the `.trashinfo` of item `name` before its content is moved in.
+    ///
+    /// The file is created exclusively, so this fails rather than claim a
+    /// name another process already reserved.
+    pub fn reserve(&self, name: &str, original_path: &Path, deleted_at: i64) -> Result<Entry> {
+        fs::create_dir_all(self.files_dir())
+            .with_context(|| format!("failed to create dir: {:?}", self.files_dir()))?;
+        fs::create_dir_all(self.info_dir())
+            .with_context(|| format!("failed to create dir: {:?}", self.info_dir()))?;
+        let name = OsString::from(name);
+        let info_path = self.info_path(&name);
+        let mut content = format!("[Trash Info]\nPath={}\n", encode_uri_path(original_path));
+        if let Some(date) = chrono::DateTime::from_timestamp(deleted_at, 0) {
+            content.push_str(&format!(
+                "DeletionDate={}\n",
+                date.with_timezone(&Local).format("%Y-%m-%dT%H:%M:%S")
+            ));
+        }
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .with_context(|| format!("failed to write trashinfo: {:?}", info_path))?;
+        Ok(Entry {
+            name,
+            info_path,
+            original_path: original_path.to_path_buf(),
+            deleted_at: Some(deleted_at),
+        })
+    }
+
+    /// Drop the metadata of item `name` once its content is gone.
+    pub fn forget(&self, name: &OsStr) -> Result<()> {
+        let info_path = self.info_path(name);
+        match fs::remove_file(&info_path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                return Err(e)
+                    .with_context(|| format!("failed to remove trashinfo: {:?}", info_path));
+            }
+            _ => {}
+        }
+        self.forget_directory_size(name);
+        Ok(())
+    }
+
+    /// Remove `name` from the `directorysizes` cache, if it is listed there.
+    fn forget_directory_size(&self, name: &OsStr) {
+        let path = self.dir.join("directorysizes");
+        let Ok(content) = fs::read_to_string(&path) else {
+            return;
+        };
+        let encoded = encode_uri_path(Path::new(name));
+        let kept: String = content
+            .lines()
+            .filter(|line| line.splitn(3, ' ').nth(2) != Some(encoded.as_str()))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        if kept != content {
+            let _ = fs::write(&path, kept);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_reserve_and_list_entries() {
+        let tmp = TempDir::new().unwrap();
+        let trash = HomeTrash::at(tmp.path().join("Trash"));
+        assert!(trash.entries().unwrap().is_empty());
+
+        let original = Path::new("/home/u/my notes.txt");
+        let entry = trash.reserve("01ABC", original, 1_700_000_000).unwrap();
+        let info = fs::read_to_string(&entry.info_path).unwrap();
+        assert!(info.contains("Path=/home/u/my%20notes.txt\n"));
+        assert_eq!(trash.entries().unwrap(), vec![entry.clone()]);
+        // A name already reserved is not claimed again
+        assert!(trash.reserve("01ABC", Path::new("/other"), 0).is_err());
+        assert_eq!(fs::read_to_string(&entry.info_path).unwrap(), info);
+
+        fs::write(
+            tmp.path().join("Trash/directorysizes"),
+            "4096 1700000000 01ABC\n10 1700000000 other\n",
+        )
+        .unwrap();
+        trash.forget(&entry.name).unwrap();
+        assert!(trash.entries().unwrap().is_empty());
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("Trash/directorysizes")).unwrap(),
+            "10 1700000000 other\n"
+        );
+        // Forgetting twice is fine
+        trash.forget(&entry.name).unwrap();
+    }
+
+    #[test]
+    fn test_entries_from_other_tools() {
+        let tmp = TempDir::new().unwrap();
+        let trash = HomeTrash::at(tmp.path().join("Trash"));
+        let info_dir = tmp.path().join("Trash/info");
+        fs::create_dir_all(&info_dir).unwrap();
+        fs::create_dir_all(tmp.path().join("Trash/files/old dir")).unwrap();
+        fs::write(
+            info_dir.join("old dir.trashinfo"),
+            "[Trash Info]\nPath=/srv/app/old%20dir\nDeletionDate=2020-05-01T10:00:00\n",
+        )
+        .unwrap();
+        fs::write(info_dir.join("junk.trashinfo"), "not a trashinfo").unwrap();
+
+        let entries = trash.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "old dir");
+        assert_eq!(entries[0].original_path, Path::new("/srv/app/old dir"));
+        assert!(entries[0].deleted_at.is_some());
+        assert!(trash.owns(entries[0].info_path.as_os_str()));
+        assert!(!trash.owns(OsStr::new("old dir")));
+
+        let mtime = fs::metadata(&entries[0].info_path)
+            .unwrap()
+            .modified()
+            .unwrap()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        fs::write(
+            tmp.path().join("Trash/directorysizes"),
+            format!("8192 {} old%20dir\n123 1 stale\n", mtime),
+        )
+        .unwrap();
+        let sizes = trash.directory_sizes();
+        assert_eq!(sizes.get(OsStr::new("old dir")), Some(&8192));
+        assert_eq!(sizes.len(), 1);
+    }
+
+    #[test]
+    fn test_newest_from() {
+        let tmp = TempDir::new().unwrap();
+        let trash = HomeTrash::at(tmp.path().join("Trash"));
+        let original = Path::new("/srv/app/a.log");
+        assert_eq!(trash.newest_from(original), None);
+
+        trash.reserve("a.log", original, 1_700_000_000).unwrap();
+        trash
+            .reserve("a.log.2", Path::new("/srv/other/a.log"), 1_700_000_100)
+            .unwrap();
+        let newest = trash.reserve("a.log.3", original, 1_700_000_200).unwrap();
+        // Past the first free name, nothing is read
+        trash.reserve("a.log.5", original, 1_700_000_300).unwrap();
+        assert_eq!(trash.newest_from(original), Some(newest));
+    }
+}
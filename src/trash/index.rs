@@ -0,0 +1,408 @@
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::Annotation;
+use super::encoding::{escape_field, unescape_field};
+
+/// First line of the index file. Bump the version when the line format changes;
+/// an index with any other header is rebuilt from the `.trashinfo` files.
+const HEADER: &str = "saferm-index 3";
+
+/// Listing data of one managed trash entry, as cached in the index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct IndexEntry {
+    pub id: String,
+    pub original_path: PathBuf,
+    pub deleted_at: Option<i64>,
+    pub size: Option<u64>,
+    pub pinned: bool,
+    pub annotation: Annotation,
+}
+
+/// Persistent cache of the managed trash metadata, so listing doesn't have to
+/// open and parse every `.trashinfo` file.
+///
+/// The `.trashinfo` files stay the source of truth. The index is rewritten
+/// atomically (temp file + rename) under a lock on every change, and is
+/// reconciled against `info/` whenever that directory changed after the index
+/// was last written (e.g. after a crash between the two updates).
+pub(crate) struct TrashIndex {
+    path: PathBuf,
+    lock_path: PathBuf,
+}
+
+impl TrashIndex {
+    pub fn new(base_dir: &Path) -> Self {
+        Self {
+            path: base_dir.join("index"),
+            lock_path: base_dir.join("index.lock"),
+        }
+    }
+
+    /// Load all entries, rebuilding or reconciling against `info_dir` first if needed.
+    ///
+    /// `read_entry` parses the `.trashinfo` of one id and returns None for
+    /// entries that are not restorable.
+    pub fn load(
+        &self,
+        info_dir: &Path,
+        read_entry: impl Fn(&str) -> Option<IndexEntry>,
+    ) -> Result<Vec<IndexEntry>> {
+        if !info_dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let _lock = self.lock()?;
+        let cached = self.read();
+        if let Some(entries) = &cached
+            && !is_newer(info_dir, &self.path)
+        {
+            return Ok(entries.clone());
+        }
+        let entries = reconcile(cached.unwrap_or_default(), info_dir, &read_entry)?;
+        self.write(&entries)?;
+        Ok(entries)
+    }
+
+    /// Apply a change to the index after the `.trashinfo` files were updated.
+    /// Without an index there is nothing to update; the next load rebuilds it.
+    ///
+    /// An index that is already behind `info_dir` (e.g. after a crash) is
+    /// reconciled first, since writing it marks it current.
+    pub fn update(
+        &self,
+        info_dir: &Path,
+        read_entry: impl Fn(&str) -> Option<IndexEntry>,
+        change: impl FnOnce(&mut Vec<IndexEntry>),
+    ) -> Result<()> {
+        let _lock = self.lock()?;
+        let Some(mut entries) = self.read() else {
+            return Ok(());
+        };
+        if is_newer(info_dir, &self.path) {
+            entries = reconcile(entries, info_dir, &read_entry)?;
+        }
+        change(&mut entries);
+        self.write(&entries)
+    }
+
+    pub fn insert(
+        &self,
+        info_dir: &Path,
+        read_entry: impl Fn(&str) -> Option<IndexEntry>,
+        entry: IndexEntry,
+    ) -> Result<()> {
+        self.update(info_dir, read_entry, |entries| {
+            entries.retain(|e| e.id != entry.id);
+            entries.push(entry);
+        })
+    }
+
+    pub fn remove(
+        &self,
+        info_dir: &Path,
+        read_entry: impl Fn(&str) -> Option<IndexEntry>,
+        ids: &[&str],
+    ) -> Result<()> {
+        let ids: HashSet<&str> = ids.iter().copied().collect();
+        self.update(info_dir, read_entry, |entries| {
+            entries.retain(|e| !ids.contains(e.id.as_str()))
+        })
+    }
+
+    /// Drop the index; the next load rebuilds it from the `.trashinfo` files.
+    pub fn invalidate(&self) -> Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("failed to remove index: {:?}", self.path))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn lock(&self) -> Result<File> {
+        if let Some(parent) = self.lock_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create trash dir: {:?}", parent))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&self.lock_path)
+            .with_context(|| format!("failed to open index lock: {:?}", self.lock_path))?;
+        file.lock()
+            .with_context(|| format!("failed to lock index: {:?}", self.lock_path))?;
+        Ok(file)
+    }
+
+    /// Read the index file; None if it is missing, from another version, or corrupt.
+    fn read(&self) -> Option<Vec<IndexEntry>> {
+        let file = File::open(&self.path).ok()?;
+        let mut lines = BufReader::new(file).lines();
+        if lines.next()?.ok()? != HEADER {
+            return None;
+        }
+        lines.map(|line| parse_line(&line.ok()?)).collect()
+    }
+
+    fn write(&self, entries: &[IndexEntry]) -> Result<()> {
+        let tmp = self
+            .path
+            .with_file_name(format!("index.tmp-{}", std::process::id()));
+        {
+            let mut out = BufWriter::new(
+                File::create(&tmp).with_context(|| format!("failed to write index: {:?}", tmp))?,
+            );
+            writeln!(out, "{}", HEADER)?;
+            for entry in entries {
+                writeln!(out, "{}", format_line(entry))?;
+            }
+            out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        }
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("failed to replace index: {:?}", self.path))
+    }
+}
+
+fn format_line(entry: &IndexEntry) -> String {
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        escape_field(&entry.id),
+        entry.deleted_at.map(|d| d.to_string()).unwrap_or_default(),
+        entry.size.map(|s| s.to_string()).unwrap_or_default(),
+        if entry.pinned { "pinned" } else { "" },
+        entry.annotation.tags.join(","),
+        // A leading '=' tells an empty reason apart from none
+        entry
+            .annotation
+            .reason
+            .as_ref()
+            .map(|r| format!("={}", escape_field(r)))
+            .unwrap_or_default(),
+        escape_field(&entry.original_path.to_string_lossy()),
+    )
+}
+
+fn parse_line(line: &str) -> Option<IndexEntry> {
+    let mut fields = line.split('\t');
+    let id = unescape_field(fields.next()?);
+    let deleted_at = optional(fields.next()?)?;
+    let size = optional(fields.next()?)?;
+    let pinned = match fields.next()? {
+        "" => false,
+        "pinned" => true,
+        _ => return None,
+    };
+    let tags = fields.next()?;
+    let reason = fields.next()?;
+    let annotation = Annotation {
+        reason: reason.strip_prefix('=').map(unescape_field),
+        tags: tags
+            .split(',')
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect(),
+    };
+    let original_path = PathBuf::from(unescape_field(fields.next()?));
+    Some(IndexEntry {
+        id,
+        original_path,
+        deleted_at,
+        size,
+        pinned,
+        annotation,
+    })
+}
+
+/// Parse an optional numeric field: empty is None, garbage fails the whole line.
+fn optional<T: std::str::FromStr>(field: &str) -> Option<Option<T>> {
+    if field.is_empty() {
+        Some(None)
+    } else {
+        field.parse().ok().map(Some)
+    }
+}
+
+/// Ids of all `.trashinfo` files in `info_dir`.
+fn info_ids(info_dir: &Path) -> Result<Vec<String>> {
+    let mut ids = vec![];
+    for entry in fs::read_dir(info_dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|e| e != "trashinfo") {
+            continue;
+        }
+        if let Some(id) = path.file_stem().and_then(|s| s.to_str()) {
+            ids.push(id.to_string());
+        }
+    }
+    Ok(ids)
+}
+
+/// Bring `cached` in line with `info_dir`: keep entries that still have a
+/// `.trashinfo`, and parse only the ones the index doesn't know yet.
+fn reconcile(
+    cached: Vec<IndexEntry>,
+    info_dir: &Path,
+    read_entry: impl Fn(&str) -> Option<IndexEntry>,
+) -> Result<Vec<IndexEntry>> {
+    let mut known: HashMap<String, IndexEntry> =
+        cached.into_iter().map(|e| (e.id.clone(), e)).collect();
+    let mut entries = vec![];
+    for id in info_ids(info_dir)? {
+        match known.remove(&id) {
+            Some(entry) => entries.push(entry),
+            None => entries.extend(read_entry(&id)),
+        }
+    }
+    Ok(entries)
+}
+
+/// True if `dir` may have changed since `file` was written (or `file`
+/// doesn't exist). Equal mtimes count: on filesystems with coarse timestamps
+/// a change in the same tick as the write would otherwise go unnoticed.
+fn is_newer(dir: &Path, file: &Path) -> bool {
+    let mtime = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    match (mtime(dir), mtime(file)) {
+        (Some(d), Some(f)) => d >= f,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(id: &str) -> IndexEntry {
+        IndexEntry {
+            id: id.to_string(),
+            original_path: PathBuf::from(format!("/home/u/{}\tx.txt", id)),
+            deleted_at: Some(1_700_000_000),
+            size: None,
+            pinned: id == "b",
+            annotation: Annotation {
+                reason: (id == "a").then(|| "old\tschema".to_string()),
+                tags: vec!["migration".to_string()],
+            },
+        }
+    }
+
+    fn write_info(info_dir: &Path, id: &str) {
+        fs::write(info_dir.join(format!("{}.trashinfo", id)), "").unwrap();
+    }
+
+    #[test]
+    fn test_rebuild_and_reuse() {
+        let tmp = TempDir::new().unwrap();
+        let info_dir = tmp.path().join("info");
+        fs::create_dir(&info_dir).unwrap();
+        write_info(&info_dir, "a");
+        write_info(&info_dir, "b");
+        let index = TrashIndex::new(tmp.path());
+
+        let mut entries = index.load(&info_dir, |id| Some(entry(id))).unwrap();
+        entries.sort_by(|x, y| x.id.cmp(&y.id));
+        assert_eq!(entries, vec![entry("a"), entry("b")]);
+
+        // A fresh index is served without parsing any trashinfo
+        let cached = index
+            .load(&info_dir, |_| panic!("should not parse"))
+            .unwrap();
+        assert_eq!(cached.len(), 2);
+    }
+
+    #[test]
+    fn test_update_and_reconcile() {
+        let tmp = TempDir::new().unwrap();
+        let info_dir = tmp.path().join("info");
+        fs::create_dir(&info_dir).unwrap();
+        write_info(&info_dir, "a");
+        let index = TrashIndex::new(tmp.path());
+        index.load(&info_dir, |id| Some(entry(id))).unwrap();
+
+        write_info(&info_dir, "b");
+        index
+            .insert(&info_dir, |id| Some(entry(id)), entry("b"))
+            .unwrap();
+        fs::remove_file(info_dir.join("a.trashinfo")).unwrap();
+        index.remove(&info_dir, |_| None, &["a"]).unwrap();
+        let entries = index
+            .load(&info_dir, |_| panic!("should not parse"))
+            .unwrap();
+        assert_eq!(entries, vec![entry("b")]);
+
+        // Simulate a crash: trashinfo written, index never updated
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        write_info(&info_dir, "c");
+        let mut entries = index.load(&info_dir, |id| Some(entry(id))).unwrap();
+        entries.sort_by(|x, y| x.id.cmp(&y.id));
+        assert_eq!(entries, vec![entry("b"), entry("c")]);
+    }
+
+    #[test]
+    fn test_update_reconciles_stale_index() {
+        let tmp = TempDir::new().unwrap();
+        let info_dir = tmp.path().join("info");
+        fs::create_dir(&info_dir).unwrap();
+        write_info(&info_dir, "a");
+        let index = TrashIndex::new(tmp.path());
+        index.load(&info_dir, |id| Some(entry(id))).unwrap();
+
+        // A crash left "b" out of the index; the next change must not hide it
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        write_info(&info_dir, "b");
+        write_info(&info_dir, "c");
+        index
+            .insert(&info_dir, |id| Some(entry(id)), entry("c"))
+            .unwrap();
+
+        let mut entries = index
+            .load(&info_dir, |_| panic!("should not parse"))
+            .unwrap();
+        entries.sort_by(|x, y| x.id.cmp(&y.id));
+        assert_eq!(entries, vec![entry("a"), entry("b"), entry("c")]);
+    }
+
+    #[test]
+    fn test_change_in_the_same_tick_is_noticed() {
+        let tmp = TempDir::new().unwrap();
+        let info_dir = tmp.path().join("info");
+        fs::create_dir(&info_dir).unwrap();
+        write_info(&info_dir, "a");
+        let index = TrashIndex::new(tmp.path());
+        index.load(&info_dir, |id| Some(entry(id))).unwrap();
+
+        // As on a filesystem with one-second timestamps
+        write_info(&info_dir, "b");
+        let mtime = fs::metadata(&info_dir).unwrap().modified().unwrap();
+        fs::File::options()
+            .write(true)
+            .open(tmp.path().join("index"))
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        let mut entries = index.load(&info_dir, |id| Some(entry(id))).unwrap();
+        entries.sort_by(|x, y| x.id.cmp(&y.id));
+        assert_eq!(entries, vec![entry("a"), entry("b")]);
+    }
+
+    #[test]
+    fn test_corrupt_index_is_rebuilt() {
+        let tmp = TempDir::new().unwrap();
+        let info_dir = tmp.path().join("info");
+        fs::create_dir(&info_dir).unwrap();
+        write_info(&info_dir, "a");
+        let index = TrashIndex::new(tmp.path());
+        index.load(&info_dir, |id| Some(entry(id))).unwrap();
+
+        fs::write(tmp.path().join("index"), "saferm-index 3\ngarbage\n").unwrap();
+        let entries = index.load(&info_dir, |id| Some(entry(id))).unwrap();
+        assert_eq!(entries, vec![entry("a")]);
+    }
+}
@@ -1,17 +1,47 @@
 use anyhow::{Context, Result};
 use chrono::Local;
 use rust_i18n::t;
+use std::collections::HashSet;
 use std::ffi::{OsStr, OsString};
 use std::fs;
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
-
-use super::{RestorableItem, TrashHandler};
+use std::sync::Mutex;
+
+use super::checksum::content_hash;
+use super::compress::{self, Compression, CompressionPolicy, file_mode, file_mtime};
+use super::crypto::{Cipher, KeySource};
+use super::dedup::ObjectStore;
+use super::encoding::{decode_uri_path, encode_uri_path};
+use super::freedesktop::HomeTrash;
+use super::index::{IndexEntry, TrashIndex};
+use super::retention::Retention;
+use super::shred::Shredder;
+use super::{Annotation, Integrity, RestorableItem, TrashHandler, TrashProblem};
+use crate::error::Error;
+use crate::observer::{Event, NoopObserver, Observer};
 use crate::prompt::Prompter;
 
 pub struct ManagedTrash {
     base_dir: PathBuf,
+    compression: CompressionPolicy,
+    encryption: Option<KeySource>,
+    dedup: bool,
+    /// Whether a content hash is recorded at trash time (`SAFERM_CONTENT_HASH`)
+    hashing: bool,
+    retention: Retention,
+    /// Backend label of listed entries
+    label: &'static str,
+    /// The FreeDesktop home trash, listed alongside (`SAFERM_XDG_TRASH`)
+    home: Option<HomeTrash>,
 }
 
+/// Data directory used when neither the platform's nor `$HOME` is known.
+pub(crate) const FALLBACK_DATA_DIR: &str = "/tmp/saferm";
+
+/// Backend label of items from the FreeDesktop home trash.
+pub const XDG_LABEL: &str = "xdg";
+
 impl Default for ManagedTrash {
     fn default() -> Self {
         Self::new()
@@ -20,35 +50,200 @@ impl Default for ManagedTrash {
 
 impl ManagedTrash {
     pub fn new() -> Self {
-        // Allow overriding the trash base dir via env var (useful for testing)
-        if let Ok(dir) = std::env::var("SAFERM_MANAGED_TRASH_DIR") {
-            return Self {
-                base_dir: PathBuf::from(dir),
-            };
+        Self::for_bin(None)
+    }
+
+    /// The trash bin `name` (see [`super::retention::validate_bin_name`]), or the
+    /// default bin. Named bins live under `bins/<name>` of the base dir and
+    /// have the same layout as the default bin.
+    pub fn for_bin(name: Option<&str>) -> Self {
+        let base_dir = Self::default_base_dir();
+        Self {
+            base_dir: match name {
+                Some(name) => base_dir.join("bins").join(name),
+                None => base_dir,
+            },
+            compression: CompressionPolicy::from_env(),
+            encryption: KeySource::from_env(),
+            dedup: ObjectStore::enabled_from_env(),
+            hashing: Self::hashing_from_env(),
+            retention: Retention::from_env(name),
+            label: "managed",
+            home: name.is_none().then(Self::home_trash_from_env).flatten(),
         }
+    }
 
-        let data_dir = dirs::data_dir().or_else(|| {
-            std::env::var("HOME")
-                .ok()
-                .map(|h| PathBuf::from(h).join(".local/share"))
-        });
-        let base_dir = match data_dir {
-            Some(dir) => dir,
-            None => {
-                eprintln!(
-                    "saferm: warning: could not determine data directory, using /tmp/saferm/trash"
-                );
-                PathBuf::from("/tmp/saferm")
+    /// A trash at an explicit directory, such as a project's `.saferm-trash`,
+    /// using the configured compression, encryption and deduplication. It has
+    /// no retention limits of its own; `label` names it in listings.
+    pub fn at(base_dir: PathBuf, label: &'static str) -> Self {
+        Self {
+            base_dir,
+            compression: CompressionPolicy::from_env(),
+            encryption: KeySource::from_env(),
+            dedup: ObjectStore::enabled_from_env(),
+            hashing: Self::hashing_from_env(),
+            retention: Retention::default(),
+            label,
+            home: None,
+        }
+    }
+
+    /// Hashing reads every trashed byte once more, so `SAFERM_CONTENT_HASH=0`
+    /// turns it off where that costs too much; those items are then reported as
+    /// unverified.
+    fn hashing_from_env() -> bool {
+        !std::env::var("SAFERM_CONTENT_HASH")
+            .is_ok_and(|v| matches!(v.as_str(), "0" | "false" | "no"))
+    }
+
+    /// The home trash when `SAFERM_XDG_TRASH` asks for it, so items other
+    /// tools (`trash-cli`, `gio trash`) put there can be restored and purged
+    /// without a desktop session.
+    fn home_trash_from_env() -> Option<HomeTrash> {
+        let enabled = std::env::var("SAFERM_XDG_TRASH")
+            .is_ok_and(|v| matches!(v.as_str(), "1" | "true" | "yes"));
+        if enabled { HomeTrash::new() } else { None }
+    }
+
+    /// Whether the home trash is listed alongside this trash.
+    pub fn reads_home_trash(&self) -> bool {
+        self.home.is_some()
+    }
+
+    /// Stop listing the home trash, when another backend already covers it.
+    pub(crate) fn ignore_home_trash(&mut self) {
+        self.home = None;
+    }
+
+    /// Whether anything was ever trashed here.
+    pub fn exists(&self) -> bool {
+        self.info_dir().is_dir()
+    }
+
+    /// Backend label of the entries this trash lists.
+    pub fn label(&self) -> &'static str {
+        self.label
+    }
+
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
+    }
+
+    /// Whether `item_id` is an entry of this trash.
+    pub fn contains(&self, item_id: &OsStr) -> bool {
+        if self.home_entry(item_id).is_some() {
+            return true;
+        }
+        self.validate_id(item_id)
+            .is_ok_and(|trash_name| self.info_path(&trash_name).is_file())
+    }
+
+    /// The entry name `item_id` stands for, checked so that joining it to
+    /// `files/` or `info/` cannot leave them: a ULID, or the name of an
+    /// existing `.trashinfo` for entries trashed before ids were ULIDs.
+    fn validate_id(&self, item_id: &OsStr) -> crate::Result<String> {
+        item_id
+            .to_str()
+            .filter(|name| is_ulid(name) || self.has_info_named(name))
+            .map(str::to_string)
+            .ok_or_else(|| Error::IdNotFound {
+                id: item_id.to_os_string(),
+            })
+    }
+
+    /// Whether `info/` holds `<name>.trashinfo`, compared against the listed
+    /// names rather than looked up by path.
+    fn has_info_named(&self, name: &str) -> bool {
+        let wanted = format!("{}.trashinfo", name);
+        fs::read_dir(self.info_dir()).is_ok_and(|entries| {
+            entries
+                .flatten()
+                .any(|entry| entry.file_name() == wanted.as_str())
+        })
+    }
+
+    /// The home trash item `item_id` names, if the home trash is read.
+    fn home_entry(&self, item_id: &OsStr) -> Option<(&HomeTrash, super::freedesktop::Entry)> {
+        let home = self.home.as_ref().filter(|home| home.owns(item_id))?;
+        home.entry(Path::new(item_id)).map(|entry| (home, entry))
+    }
+
+    /// Items of the home trash whose content is there.
+    fn home_items(&self, filter: Option<&str>, tag: Option<&str>) -> Result<Vec<RestorableItem>> {
+        let Some(home) = &self.home else {
+            return Ok(vec![]);
+        };
+        let sizes = home.directory_sizes();
+        let mut items = vec![];
+        for entry in home.entries()? {
+            let content = home.content_path(&entry.name);
+            let Ok(meta) = fs::symlink_metadata(&content) else {
+                continue;
+            };
+            let annotation = HomeTrash::annotation(&entry);
+            let matches = filter.is_none_or(|pat| {
+                entry.name.to_string_lossy().contains(pat)
+                    || entry.original_path.to_string_lossy().contains(pat)
+            }) && annotation.matches(tag);
+            if !matches {
+                continue;
             }
+            let size = match sizes.get(&entry.name) {
+                Some(&size) if meta.is_dir() => Some(size),
+                _ => tree_size(&content).ok(),
+            };
+            items.push(RestorableItem {
+                id: entry.info_path.into_os_string(),
+                display_name: entry
+                    .original_path
+                    .file_name()
+                    .unwrap_or(&entry.name)
+                    .to_os_string(),
+                original_path: entry.original_path,
+                deleted_at: entry.deleted_at,
+                size,
+                pinned: false,
+                annotation,
+                backend: XDG_LABEL,
+            });
+        }
+        Ok(items)
+    }
+
+    /// Where the default bin lives, under [`FALLBACK_DATA_DIR`] when no data
+    /// directory can be determined.
+    pub(crate) fn default_base_dir() -> PathBuf {
+        // Allow overriding the trash base dir via env var (useful for testing)
+        if let Ok(dir) = std::env::var("SAFERM_MANAGED_TRASH_DIR") {
+            return PathBuf::from(dir);
         }
-        .join("saferm")
-        .join("trash");
-        Self { base_dir }
+
+        dirs::data_dir()
+            .or_else(|| {
+                std::env::var("HOME")
+                    .ok()
+                    .map(|h| PathBuf::from(h).join(".local/share"))
+            })
+            .unwrap_or_else(|| PathBuf::from(FALLBACK_DATA_DIR))
+            .join("saferm")
+            .join("trash")
     }
 
-    #[cfg(test)]
+    /// A trash at `base_dir` that ignores the environment: no compression,
+    /// encryption, deduplication or retention limits.
+    #[cfg(any(test, feature = "testing"))]
     pub fn with_base_dir(base_dir: PathBuf) -> Self {
-        Self { base_dir }
+        Self {
+            base_dir,
+            compression: CompressionPolicy::default(),
+            encryption: None,
+            dedup: false,
+            hashing: true,
+            retention: Retention::default(),
+            label: "managed",
+            home: None,
+        }
     }
 
     fn files_dir(&self) -> PathBuf {
@@ -67,58 +262,662 @@ impl ManagedTrash {
         Ok(())
     }
 
-    fn unique_name(&self, original_name: &str) -> String {
+    /// Storage name for a new entry: a ULID, so ids sort by deletion time and
+    /// never collide with the names of trashed files.
+    fn new_id(&self) -> String {
+        // Monotonic within the process, so entries trashed in the same
+        // millisecond still sort in order
+        static GENERATOR: Mutex<ulid::Generator> = Mutex::new(ulid::Generator::new());
+        loop {
+            let id = GENERATOR
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .generate()
+                .unwrap_or_else(|_| ulid::Ulid::new())
+                .to_string();
+            if !self.files_dir().join(&id).exists() && !self.info_path(&id).exists() {
+                return id;
+            }
+        }
+    }
+
+    fn index(&self) -> TrashIndex {
+        TrashIndex::new(&self.base_dir)
+    }
+
+    /// Index entry for `trash_name`, or None if it is not restorable
+    /// (content missing or metadata unreadable).
+    fn read_index_entry(&self, trash_name: &str) -> Option<IndexEntry> {
+        if !self.files_dir().join(trash_name).exists() {
+            return None;
+        }
+        let info = self.read_trashinfo(trash_name).ok()?;
+        Some(self.index_entry(trash_name, info))
+    }
+
+    fn index_entry(&self, trash_name: &str, info: TrashInfo) -> IndexEntry {
+        // Entries from before sizes were recorded: files are cheap to stat
+        let size = info.size.or_else(|| {
+            fs::symlink_metadata(self.files_dir().join(trash_name))
+                .ok()
+                .filter(|m| m.is_file())
+                .map(|m| m.len())
+        });
+        IndexEntry {
+            id: trash_name.to_string(),
+            original_path: info.original_path,
+            deleted_at: info.deleted_at,
+            size,
+            pinned: info.pinned,
+            annotation: info.annotation,
+        }
+    }
+
+    /// Apply `change` to the index after the `.trashinfo` files were updated.
+    fn update_index(&self, change: impl FnOnce(&mut Vec<IndexEntry>), observer: &dyn Observer) {
+        self.sync_index(
+            self.index()
+                .update(&self.info_dir(), |id| self.read_index_entry(id), change),
+            observer,
+        );
+    }
+
+    /// Keep the index in step with a metadata change. The `.trashinfo` files are
+    /// authoritative, so a failed update only drops the index for a rebuild.
+    fn sync_index(&self, result: Result<()>, observer: &dyn Observer) {
+        if let Err(e) = result {
+            observer.notify(&Event::Warning {
+                message: &format!("failed to update trash index: {:#}", e),
+            });
+            let _ = self.index().invalidate();
+        }
+    }
+
+    /// Scratch space for compressing and verifying entries, on the trash's filesystem.
+    fn staging_dir(&self) -> PathBuf {
+        self.base_dir.join("staging")
+    }
+
+    /// The key needed to read `info`'s content, or None if it is stored unencrypted.
+    fn cipher_for(&self, trash_name: &str, info: &TrashInfo) -> Result<Option<Cipher>> {
+        if !info.encrypted {
+            return Ok(None);
+        }
+        match &self.encryption {
+            Some(source) => source.load().map(Some),
+            None => anyhow::bail!(t!("error_encryption_key_missing", name = trash_name)),
+        }
+    }
+
+    /// Compress (and with a `cipher`, encrypt) `src` into `dest` via the staging
+    /// dir, so a crash never leaves a partial archive in `files/`. `src` itself
+    /// is left untouched.
+    fn store_compressed(
+        &self,
+        src: &Path,
+        dest: &Path,
+        trash_name: &str,
+        cipher: Option<&Cipher>,
+    ) -> Result<Compression> {
+        let staging = self.staging_dir();
+        fs::create_dir_all(&staging)
+            .with_context(|| format!("failed to create staging dir: {:?}", staging))?;
+        let partial = staging.join(trash_name);
+        let result = compress::compress(src, &partial, cipher).and_then(|kind| {
+            fs::rename(&partial, dest)
+                .with_context(|| format!("failed to move archive: {:?}", dest))?;
+            Ok(kind)
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        result
+    }
+
+    fn objects(&self) -> ObjectStore {
+        ObjectStore::new(&self.base_dir)
+    }
+
+    /// Replace the content stored at `files/<trash_name>` with a manifest of
+    /// blobs in the object store. On failure the content stays as it was.
+    fn deduplicate(&self, trash_name: &str, observer: &dyn Observer) -> Result<()> {
+        let stored = self.files_dir().join(trash_name);
+        let staging = self.staging_dir();
+        fs::create_dir_all(&staging)
+            .with_context(|| format!("failed to create staging dir: {:?}", staging))?;
+        let manifest_path = staging.join(format!("{}.manifest", trash_name));
+        let tree = staging.join(format!("{}.tree", trash_name));
+
+        let store = self.objects();
+        let _lock = store.lock()?;
+        let manifest = store.ingest(&stored)?;
+        fs::write(&manifest_path, manifest)
+            .with_context(|| format!("failed to write manifest: {:?}", manifest_path))?;
+        fs::rename(&stored, &tree).with_context(|| format!("failed to move: {:?}", stored))?;
+        if let Err(e) = fs::rename(&manifest_path, &stored) {
+            let _ = fs::rename(&tree, &stored);
+            let _ = fs::remove_file(&manifest_path);
+            return Err(e).with_context(|| format!("failed to store manifest: {:?}", stored));
+        }
+        if let Err(e) = remove_tree(&tree) {
+            observer.notify(&Event::Warning {
+                message: &format!("failed to remove staged copy '{}': {}", tree.display(), e),
+            });
+        }
+        Ok(())
+    }
+
+    /// Permanently delete every entry except pinned ones (unless `include_pinned`),
+    /// shredding all stored content and metadata first when a `shredder` is given.
+    fn empty(
+        &self,
+        prompter: &dyn Prompter,
+        shredder: Option<&Shredder>,
+        include_pinned: bool,
+        observer: &dyn Observer,
+    ) -> Result<()> {
         let files_dir = self.files_dir();
-        if !files_dir.join(original_name).exists() {
-            return original_name.to_string();
-        }
-
-        // Handle name collisions by appending a counter
-        let stem = Path::new(original_name)
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or(original_name);
-        let ext = Path::new(original_name)
-            .extension()
-            .and_then(|s| s.to_str());
-
-        for i in 1u64.. {
-            let candidate = match ext {
-                Some(e) => format!("{}.{}.{}", stem, i, e),
-                None => format!("{}.{}", stem, i),
-            };
-            if !files_dir.join(&candidate).exists() {
-                return candidate;
+        if !files_dir.exists() {
+            observer.notify(&Event::NothingToCleanUp { pinned: 0 });
+            return Ok(());
+        }
+
+        let entries: Vec<_> = fs::read_dir(&files_dir)
+            .with_context(|| format!("failed to read trash dir: {:?}", files_dir))?
+            .collect::<std::io::Result<_>>()?;
+
+        if entries.is_empty() {
+            observer.notify(&Event::NothingToCleanUp { pinned: 0 });
+            return Ok(());
+        }
+
+        let pinned: HashSet<String> = if include_pinned {
+            HashSet::new()
+        } else {
+            self.pinned_ids()?
+        };
+        let is_pinned = |name: &OsStr| name.to_str().is_some_and(|n| pinned.contains(n));
+        if entries.iter().all(|entry| is_pinned(&entry.file_name())) {
+            observer.notify(&Event::NothingToCleanUp {
+                pinned: pinned.len(),
+            });
+            return Ok(());
+        }
+
+        let question = match shredder {
+            Some(_) => t!("confirm_cleanup_shred"),
+            None => t!("confirm_cleanup_managed"),
+        };
+        if !prompter.confirm(&question)? {
+            observer.notify(&Event::CleanupCancelled);
+            return Ok(());
+        }
+
+        let remove = |path: &Path| discard(path, shredder);
+
+        // Remove all files
+        for entry in entries.iter().filter(|e| !is_pinned(&e.file_name())) {
+            remove(&entry.path())?;
+        }
+        // Leftovers of interrupted compression may hold content too
+        let staging = self.staging_dir();
+        if staging.exists() {
+            remove(&staging)?;
+        }
+        // Pinned manifests still need their blobs
+        match shredder {
+            Some(_) if pinned.is_empty() => self.objects().purge(remove)?,
+            _ => self.collect_garbage(shredder, observer),
+        }
+
+        // Remove all info files
+        let info_dir = self.info_dir();
+        if info_dir.exists() {
+            for entry in fs::read_dir(&info_dir)? {
+                let path = entry?.path();
+                if !path.file_stem().is_some_and(is_pinned) {
+                    remove(&path)?;
+                }
+            }
+        }
+        self.update_index(
+            |entries| entries.retain(|e| pinned.contains(&e.id)),
+            observer,
+        );
+
+        observer.notify(&Event::CleanedUp {
+            pinned_kept: pinned.len(),
+        });
+        Ok(())
+    }
+
+    /// Ids of the pinned entries.
+    fn pinned_ids(&self) -> Result<HashSet<String>> {
+        Ok(self
+            .index()
+            .load(&self.info_dir(), |id| self.read_index_entry(id))?
+            .into_iter()
+            .filter(|entry| entry.pinned)
+            .map(|entry| entry.id)
+            .collect())
+    }
+
+    /// Drop object store blobs that no manifest in `files/` refers to anymore,
+    /// shredding them when a `shredder` is given.
+    fn collect_garbage(&self, shredder: Option<&Shredder>, observer: &dyn Observer) {
+        let result = self.objects().gc(
+            || {
+                let mut manifests = vec![];
+                if let Ok(entries) = fs::read_dir(self.files_dir()) {
+                    for entry in entries {
+                        if let Some(manifest) = read_manifest(&entry?.path())? {
+                            manifests.push(manifest);
+                        }
+                    }
+                }
+                Ok(manifests)
+            },
+            |path| match shredder {
+                Some(shredder) => shredder.remove(path),
+                None => Ok(fs::remove_file(path)?),
+            },
+        );
+        if let Err(e) = result {
+            observer.notify(&Event::Warning {
+                message: &format!("failed to clean up deduplicated content: {:#}", e),
+            });
+        }
+    }
+
+    /// Unpack a compressed or deduplicated entry to the new path `dest`,
+    /// including the mode and mtime of the item itself. Nothing is left behind
+    /// on failure.
+    fn unpack(&self, trash_name: &str, info: &TrashInfo, dest: &Path) -> Result<()> {
+        let src = self.files_dir().join(trash_name);
+        let result = match info.compression {
+            _ if info.dedup => fs::read_to_string(&src)
+                .with_context(|| format!("failed to read manifest: {:?}", src))
+                .and_then(|manifest| self.objects().rebuild(&manifest, dest)),
+            Some(kind) => {
+                let cipher = self.cipher_for(trash_name, info)?;
+                compress::decompress(&src, kind, dest, cipher.as_ref())
+                    .and_then(|()| compress::apply_metadata(dest, info.mode, info.mtime))
+            }
+            None => anyhow::bail!("'{}' is stored as is", trash_name),
+        };
+        if result.is_err() {
+            let _ = remove_tree(dest);
+        }
+        result
+    }
+
+    /// Unpack next to `destination`, verify the result, then move it into place.
+    fn restore_packed(
+        &self,
+        trash_name: &str,
+        info: &TrashInfo,
+        destination: &Path,
+        observer: &dyn Observer,
+    ) -> Result<()> {
+        let file_name = destination
+            .file_name()
+            .unwrap_or(OsStr::new(trash_name))
+            .to_string_lossy();
+        let staging = destination.with_file_name(format!(".{}.saferm-{}", file_name, trash_name));
+        self.unpack(trash_name, info, &staging)?;
+
+        if let Some(expected) = &info.content_hash {
+            match content_hash(&staging) {
+                Ok(actual) if &actual != expected => {
+                    let _ = remove_tree(&staging);
+                    return Err(Error::Corrupted {
+                        id: trash_name.into(),
+                    }
+                    .into());
+                }
+                Ok(_) => {}
+                Err(e) => observer.notify(&Event::Warning {
+                    message: &format!("could not verify '{}': {:#}", trash_name, e),
+                }),
+            }
+        }
+
+        fs::rename(&staging, destination).map_err(|source| {
+            let _ = remove_tree(&staging);
+            Error::Io {
+                path: destination.to_path_buf(),
+                source,
             }
+            .into()
+        })
+    }
+
+    fn quarantine_dir(&self) -> PathBuf {
+        self.base_dir.join("quarantine")
+    }
+
+    /// Where `--fsck` points the synthesized metadata of orphaned content.
+    fn recovered_dir(&self) -> PathBuf {
+        dirs::home_dir()
+            .unwrap_or_else(|| self.base_dir.clone())
+            .join("saferm-recovered")
+    }
+
+    /// Move `path` out of the trash into `quarantine/<kind>/`, never overwriting.
+    fn quarantine(&self, path: &Path, kind: &str) -> Result<()> {
+        let dir = self.quarantine_dir().join(kind);
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create quarantine dir: {:?}", dir))?;
+
+        let name = path.file_name().unwrap_or(OsStr::new("unknown"));
+        let mut dest = dir.join(name);
+        let mut counter = 0u64;
+        while dest.symlink_metadata().is_ok() {
+            counter += 1;
+            dest = dir.join(format!("{}.{}", name.to_string_lossy(), counter));
         }
-        unreachable!()
+        fs::rename(path, &dest).with_context(|| format!("failed to quarantine: {:?}", path))
     }
 
-    fn write_trashinfo(&self, trash_name: &str, original_path: &Path) -> Result<()> {
-        let info_path = self.info_dir().join(format!("{}.trashinfo", trash_name));
-        let now = Local::now().format("%Y-%m-%dT%H:%M:%S").to_string();
-        let content = format!(
-            "[Trash Info]\nPath={}\nDeletionDate={}\n",
-            original_path.display(),
-            now,
+    /// Move `source` into the trash as an entry for `original_path` deleted at
+    /// `deleted_at`; `path` is the target as given, for messages and events.
+    fn store(
+        &self,
+        path: &Path,
+        source: &Path,
+        original_path: &Path,
+        deleted_at: i64,
+        annotation: &Annotation,
+        observer: &dyn Observer,
+    ) -> Result<OsString> {
+        let original_path = original_path.to_path_buf();
+
+        let trash_name = self.new_id();
+        let dest = self.files_dir().join(&trash_name);
+        let hash_or_warn = |p: &Path| match self.hashing.then(|| content_hash(p))? {
+            Ok(hash) => Some(hash),
+            Err(e) => {
+                observer.notify(&Event::Warning {
+                    message: &format!("failed to hash '{}': {:#}", path.display(), e),
+                });
+                None
+            }
+        };
+
+        // Encrypted, large or matching items are packed; everything else keeps the
+        // cheap rename (and is deduplicated afterwards if enabled).
+        let cipher = self.encryption.as_ref().map(KeySource::load).transpose()?;
+        let size = tree_size(source).ok();
+        let name = original_path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        let mut compressed = None;
+        // Set when the content was copied to another filesystem, leaving the original behind
+        let mut copied = false;
+        let meta = fs::symlink_metadata(source)?;
+        // Symlinks, FIFOs, sockets and devices have no content to pack
+        let packable = meta.is_file() || meta.is_dir();
+        if packable
+            && (cipher.is_some()
+                || (!self.dedup && self.compression.applies(&name, size.unwrap_or(0))))
+        {
+            // Hash the original first; it is only removed once the archive is in place.
+            // Encrypted entries keep no plaintext hash in the readable trashinfo;
+            // age authenticates their content instead.
+            let hash = match cipher {
+                Some(_) => None,
+                None => hash_or_warn(source),
+            };
+            match self.store_compressed(source, &dest, &trash_name, cipher.as_ref()) {
+                Ok(kind) => compressed = Some((kind, hash, meta)),
+                // Never fall back to storing secrets in plaintext
+                Err(e) if cipher.is_some() => {
+                    return Err(e.context(t!(
+                        "error_trash_failed",
+                        name = path.display().to_string(),
+                        reason = "encryption failed"
+                    )));
+                }
+                Err(e) => observer.notify(&Event::Warning {
+                    message: &format!(
+                        "failed to compress '{}', storing as is: {:#}",
+                        path.display(),
+                        e
+                    ),
+                }),
+            }
+        }
+
+        let info = match &compressed {
+            Some((kind, hash, meta)) => TrashInfo {
+                original_path: original_path.clone(),
+                deleted_at: Some(deleted_at),
+                content_hash: hash.clone(),
+                size,
+                compression: Some(*kind),
+                encrypted: cipher.is_some(),
+                dedup: false,
+                mode: file_mode(meta),
+                mtime: file_mtime(meta),
+                pinned: false,
+                annotation: annotation.clone(),
+            },
+            None => {
+                // Hash before the move, so nothing lands in files/ without its trashinfo
+                // while the content is read; a rename stores exactly what was hashed.
+                let content_hash = hash_or_warn(source);
+                let mut report = |copied, total| {
+                    observer.notify(&Event::BytesCopied {
+                        path,
+                        copied,
+                        total,
+                    })
+                };
+                fs::rename(source, &dest)
+                    .or_else(|e| match e.kind() {
+                        ErrorKind::CrossesDevices => {
+                            copied = true;
+                            copy_across(source, &dest, &mut report)
+                        }
+                        _ => Err(e),
+                    })
+                    .with_context(|| {
+                        t!(
+                            "error_trash_failed",
+                            name = path.display().to_string(),
+                            reason = "rename failed"
+                        )
+                    })?;
+                let mut info = TrashInfo {
+                    original_path: original_path.clone(),
+                    deleted_at: Some(deleted_at),
+                    content_hash,
+                    size: tree_size(&dest).ok(),
+                    compression: None,
+                    encrypted: false,
+                    dedup: false,
+                    mode: None,
+                    mtime: None,
+                    pinned: false,
+                    annotation: annotation.clone(),
+                };
+                if self.dedup {
+                    match self.deduplicate(&trash_name, observer) {
+                        Ok(()) => info.dedup = true,
+                        Err(e) => observer.notify(&Event::Warning {
+                            message: &format!(
+                                "failed to deduplicate '{}', storing as is: {:#}",
+                                path.display(),
+                                e
+                            ),
+                        }),
+                    }
+                }
+                info
+            }
+        };
+        self.write_trashinfo(&trash_name, &info)?;
+        let entry = self.index_entry(&trash_name, info);
+        self.sync_index(
+            self.index()
+                .insert(&self.info_dir(), |id| self.read_index_entry(id), entry),
+            observer,
         );
-        fs::write(&info_path, content)
+
+        // The entry is complete and restorable before the original goes
+        if compressed.is_some() || copied {
+            remove_tree(source).with_context(|| {
+                t!(
+                    "error_trash_failed",
+                    name = path.display().to_string(),
+                    reason = "failed to remove original after copying it to the trash"
+                )
+            })?;
+        }
+        Ok(OsString::from(trash_name))
+    }
+
+    /// Take over `content` from another trash as an entry deleted from
+    /// `original_path` at `deleted_at`.
+    pub fn adopt(
+        &self,
+        content: &Path,
+        original_path: &Path,
+        deleted_at: i64,
+        annotation: &Annotation,
+    ) -> Result<OsString> {
+        self.ensure_dirs()?;
+        self.store(
+            content,
+            content,
+            original_path,
+            deleted_at,
+            annotation,
+            &NoopObserver,
+        )
+    }
+
+    /// Entries saferm trashed here, without the home trash.
+    fn own_items(&self, filter: Option<&str>, tag: Option<&str>) -> Result<Vec<RestorableItem>> {
+        let entries = self
+            .index()
+            .load(&self.info_dir(), |id| self.read_index_entry(id))?;
+
+        let items = entries
+            .into_iter()
+            .filter(|entry| {
+                // Apply filter
+                filter.is_none_or(|pat| {
+                    entry.id.contains(pat) || entry.original_path.to_string_lossy().contains(pat)
+                }) && entry.annotation.matches(tag)
+            })
+            .map(|entry| {
+                let display_name = entry
+                    .original_path
+                    .file_name()
+                    .unwrap_or(OsStr::new(&entry.id))
+                    .to_os_string();
+                RestorableItem {
+                    id: OsString::from(&entry.id),
+                    original_path: entry.original_path,
+                    display_name,
+                    deleted_at: entry.deleted_at,
+                    size: entry.size,
+                    pinned: entry.pinned,
+                    annotation: entry.annotation,
+                    backend: self.label,
+                }
+            })
+            .collect();
+
+        Ok(items)
+    }
+
+    fn info_path(&self, trash_name: &str) -> PathBuf {
+        self.info_dir().join(format!("{}.trashinfo", trash_name))
+    }
+
+    fn write_trashinfo(&self, trash_name: &str, info: &TrashInfo) -> Result<()> {
+        let info_path = self.info_path(trash_name);
+        fs::write(&info_path, info.render())
             .with_context(|| format!("failed to write trashinfo: {:?}", info_path))?;
         Ok(())
     }
+
+    fn read_trashinfo(&self, trash_name: &str) -> Result<TrashInfo> {
+        let info_path = self.info_path(trash_name);
+        let content = fs::read_to_string(&info_path)
+            .with_context(|| format!("failed to read trashinfo: {:?}", info_path))?;
+        parse_trashinfo(&content)
+    }
+
+    /// Compare the stored content hash of `trash_name` with its current content.
+    /// Compressed and deduplicated entries are unpacked into the staging dir to be hashed;
+    /// encrypted ones carry no hash and are intact if they decrypt.
+    fn check_integrity(&self, trash_name: &str) -> Result<Integrity> {
+        let info = self.read_trashinfo(trash_name)?;
+        if info.content_hash.is_none() && !info.encrypted {
+            return Ok(Integrity::Unverified);
+        }
+        let actual = if info.is_packed() {
+            // A missing key is an error, not a sign of corruption
+            self.cipher_for(trash_name, &info)?;
+            {
+                let staging = self.staging_dir();
+                fs::create_dir_all(&staging)
+                    .with_context(|| format!("failed to create staging dir: {:?}", staging))?;
+                let unpacked = staging.join(format!("{}.verify", trash_name));
+                if self.unpack(trash_name, &info, &unpacked).is_err() {
+                    return Ok(Integrity::Corrupted);
+                }
+                let hash = content_hash(&unpacked);
+                let _ = remove_tree(&unpacked);
+                hash?
+            }
+        } else {
+            content_hash(&self.files_dir().join(trash_name))?
+        };
+        let Some(expected) = &info.content_hash else {
+            return Ok(Integrity::Intact);
+        };
+        Ok(if &actual == expected {
+            Integrity::Intact
+        } else {
+            Integrity::Corrupted
+        })
+    }
 }
 
 impl TrashHandler for ManagedTrash {
-    fn trash(&self, path: &Path) -> Result<()> {
+    fn trash(&self, path: &Path) -> crate::Result<Option<OsString>> {
+        self.trash_annotated(path, &Annotation::default())
+    }
+
+    fn trash_annotated(
+        &self,
+        path: &Path,
+        annotation: &Annotation,
+    ) -> crate::Result<Option<OsString>> {
+        self.trash_observed(path, annotation, &NoopObserver)
+    }
+
+    fn trash_observed(
+        &self,
+        path: &Path,
+        annotation: &Annotation,
+        observer: &dyn Observer,
+    ) -> crate::Result<Option<OsString>> {
         // Symlinks: remove directly to avoid canonicalize() resolving the target
         if path.is_symlink() {
-            return std::fs::remove_file(path).with_context(|| {
+            std::fs::remove_file(path).with_context(|| {
                 t!(
                     "error_trash_failed",
                     name = path.display().to_string(),
                     reason = "failed to remove symlink"
                 )
-            });
+            })?;
+            return Ok(None);
         }
 
         self.ensure_dirs()?;
@@ -126,163 +925,600 @@ impl TrashHandler for ManagedTrash {
         let canonical = path
             .canonicalize()
             .with_context(|| format!("failed to resolve path: {:?}", path))?;
+        let id = self.store(
+            path,
+            &canonical,
+            &canonical,
+            Local::now().timestamp(),
+            annotation,
+            observer,
+        )?;
+        Ok(Some(id))
+    }
 
-        let original_name = path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown");
+    fn cleanup(&self, prompter: &dyn Prompter, include_pinned: bool) -> crate::Result<()> {
+        self.cleanup_observed(prompter, None, include_pinned, &NoopObserver)
+    }
 
-        let trash_name = self.unique_name(original_name);
-        let dest = self.files_dir().join(&trash_name);
+    fn cleanup_shred(
+        &self,
+        prompter: &dyn Prompter,
+        shredder: &Shredder,
+        include_pinned: bool,
+    ) -> crate::Result<()> {
+        self.cleanup_observed(prompter, Some(shredder), include_pinned, &NoopObserver)
+    }
 
-        fs::rename(&canonical, &dest).with_context(|| {
-            t!(
-                "error_trash_failed",
-                name = path.display().to_string(),
-                reason = "rename failed"
-            )
-        })?;
+    fn cleanup_observed(
+        &self,
+        prompter: &dyn Prompter,
+        shredder: Option<&Shredder>,
+        include_pinned: bool,
+        observer: &dyn Observer,
+    ) -> crate::Result<()> {
+        Ok(self.empty(prompter, shredder, include_pinned, observer)?)
+    }
 
-        self.write_trashinfo(&trash_name, &canonical)?;
-        Ok(())
+    fn backend_name(&self) -> &'static str {
+        "managed"
     }
 
-    fn cleanup(&self, prompter: &dyn Prompter) -> Result<()> {
-        let files_dir = self.files_dir();
-        if !files_dir.exists() {
-            println!("{}", t!("cleanup_nothing"));
-            return Ok(());
+    fn set_pinned(&self, item_id: &OsStr, pinned: bool) -> crate::Result<()> {
+        if self.home_entry(item_id).is_some() {
+            return Err(Error::Unsupported {
+                operation: if pinned { "--pin" } else { "--unpin" },
+                backend: XDG_LABEL,
+            });
         }
+        let trash_name = self.validate_id(item_id)?;
+        if !self.files_dir().join(&trash_name).exists() {
+            return Err(Error::IdNotFound {
+                id: item_id.to_os_string(),
+            });
+        }
+        let mut info = self.read_trashinfo(&trash_name)?;
+        if info.pinned != pinned {
+            info.pinned = pinned;
+            self.write_trashinfo(&trash_name, &info)?;
+        }
+        // The index is rebuilt from the metadata if this fails
+        self.update_index(
+            |entries| {
+                for entry in entries.iter_mut().filter(|e| e.id == trash_name) {
+                    entry.pinned = pinned;
+                }
+            },
+            &NoopObserver,
+        );
+        Ok(())
+    }
 
-        let entries: Vec<_> = fs::read_dir(&files_dir)
-            .with_context(|| format!("failed to read trash dir: {:?}", files_dir))?
+    fn evict(&self) -> crate::Result<Vec<RestorableItem>> {
+        self.evict_observed(&NoopObserver)
+    }
+
+    fn evict_observed(&self, observer: &dyn Observer) -> crate::Result<Vec<RestorableItem>> {
+        if self.retention.is_unlimited() {
+            return Ok(vec![]);
+        }
+        // Retention applies to saferm's own entries, not the home trash
+        let mut items = self.own_items(None, None)?;
+        let doomed: HashSet<OsString> = self
+            .retention
+            .select(&items, Local::now().timestamp())
+            .into_iter()
             .collect();
+        items.retain(|item| doomed.contains(&item.id));
+        for item in &items {
+            self.purge_observed(&item.id, None, observer)?;
+        }
+        Ok(items)
+    }
 
-        if entries.is_empty() {
-            println!("{}", t!("cleanup_nothing"));
+    fn purge(&self, item_id: &OsStr, shredder: Option<&Shredder>) -> crate::Result<()> {
+        self.purge_observed(item_id, shredder, &NoopObserver)
+    }
+
+    fn purge_observed(
+        &self,
+        item_id: &OsStr,
+        shredder: Option<&Shredder>,
+        observer: &dyn Observer,
+    ) -> crate::Result<()> {
+        if let Some((home, entry)) = self.home_entry(item_id) {
+            discard(&home.content_path(&entry.name), shredder)?;
+            if shredder.is_some() {
+                discard(&entry.info_path, shredder)?;
+            }
+            home.drop_entry(&entry)?;
             return Ok(());
         }
+        let trash_name = self.validate_id(item_id)?;
+        let src = self.files_dir().join(&trash_name);
+        if fs::symlink_metadata(&src).is_err() {
+            return Err(Error::IdNotFound {
+                id: item_id.to_os_string(),
+            });
+        }
+        let dedup = self
+            .read_trashinfo(&trash_name)
+            .is_ok_and(|info| info.dedup);
+
+        let remove = |path: &Path| discard(path, shredder);
+        remove(&src)?;
+        // The metadata names the original path, so it goes the same way
+        let info_path = self.info_path(&trash_name);
+        if info_path.exists() {
+            remove(&info_path)?;
+        }
+        self.sync_index(
+            self.index().remove(
+                &self.info_dir(),
+                |id| self.read_index_entry(id),
+                &[&trash_name],
+            ),
+            observer,
+        );
+        if dedup {
+            self.collect_garbage(shredder, observer);
+        }
+        Ok(())
+    }
+
+    fn list_restorable(
+        &self,
+        filter: Option<&str>,
+        tag: Option<&str>,
+    ) -> crate::Result<Vec<RestorableItem>> {
+        let mut items = self.own_items(filter, tag)?;
+        items.extend(self.home_items(filter, tag)?);
+        Ok(items)
+    }
+
+    fn restore_to(&self, item_id: &OsStr, destination: &Path) -> crate::Result<()> {
+        self.restore_observed(item_id, destination, &NoopObserver)
+    }
 
-        if !prompter.confirm(&t!("confirm_cleanup_managed"))? {
-            println!("{}", t!("cleanup_cancelled"));
+    fn restore_observed(
+        &self,
+        item_id: &OsStr,
+        destination: &Path,
+        observer: &dyn Observer,
+    ) -> crate::Result<()> {
+        if let Some((home, entry)) = self.home_entry(item_id) {
+            home.take(&entry, destination)?;
             return Ok(());
         }
+        let trash_name = self.validate_id(item_id)?;
+        let src = self.files_dir().join(&trash_name);
 
-        // Remove all files
-        for entry in fs::read_dir(&files_dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_dir() {
-                fs::remove_dir_all(&path)?;
-            } else {
-                fs::remove_file(&path)?;
+        if !src.exists() {
+            return Err(Error::IdNotFound {
+                id: item_id.to_os_string(),
+            });
+        }
+
+        if let Ok(info) = self.read_trashinfo(&trash_name)
+            && info.is_packed()
+        {
+            self.restore_packed(&trash_name, &info, destination, observer)?;
+            let _ = fs::remove_file(&src);
+            let _ = fs::remove_file(self.info_path(&trash_name));
+            self.sync_index(
+                self.index().remove(
+                    &self.info_dir(),
+                    |id| self.read_index_entry(id),
+                    &[&trash_name],
+                ),
+                observer,
+            );
+            if info.dedup {
+                self.collect_garbage(None, observer);
             }
+            return Ok(());
         }
 
-        // Remove all info files
-        let info_dir = self.info_dir();
-        if info_dir.exists() {
-            for entry in fs::read_dir(&info_dir)? {
-                let entry = entry?;
-                fs::remove_file(entry.path())?;
+        // Refuse to hand back content that changed while it sat in the trash.
+        // Entries without a readable hash are restored as before.
+        match self.check_integrity(&trash_name) {
+            Ok(Integrity::Corrupted) => {
+                return Err(Error::Corrupted {
+                    id: item_id.to_os_string(),
+                });
             }
+            Ok(_) => {}
+            Err(e) => observer.notify(&Event::Warning {
+                message: &format!("could not verify '{}': {:#}", trash_name, e),
+            }),
         }
 
-        println!("{}", t!("cleanup_success"));
+        move_across(&src, destination).map_err(|source| Error::Io {
+            path: destination.to_path_buf(),
+            source,
+        })?;
+
+        // Clean up the .trashinfo file
+        let _ = fs::remove_file(self.info_path(&trash_name));
+        self.sync_index(
+            self.index().remove(
+                &self.info_dir(),
+                |id| self.read_index_entry(id),
+                &[&trash_name],
+            ),
+            observer,
+        );
+
         Ok(())
     }
 
-    fn backend_name(&self) -> &'static str {
-        "managed"
+    fn verify_integrity(
+        &self,
+        filter: Option<&str>,
+    ) -> crate::Result<Vec<(RestorableItem, Integrity)>> {
+        let items = self.list_restorable(filter, None)?;
+        Ok(items
+            .into_iter()
+            .map(|item| {
+                // Other tools record no content hash
+                if item.backend == XDG_LABEL {
+                    return (item, Integrity::Unverified);
+                }
+                // One unreadable entry must not hide the state of the others
+                let status = self
+                    .check_integrity(&item.id.to_string_lossy())
+                    .unwrap_or_else(|e| Integrity::Unreadable {
+                        reason: format!("{:#}", e),
+                    });
+                (item, status)
+            })
+            .collect())
     }
 
-    fn list_restorable(&self, filter: Option<&str>) -> Result<Vec<RestorableItem>> {
+    fn fsck(&self) -> crate::Result<Vec<TrashProblem>> {
+        let mut problems = vec![];
+        let files_dir = self.files_dir();
         let info_dir = self.info_dir();
-        if !info_dir.exists() {
-            return Ok(vec![]);
-        }
 
-        let mut items = vec![];
-        for entry in fs::read_dir(&info_dir)? {
-            let entry = entry?;
-            let path = entry.path();
+        // Metadata side: every .trashinfo must parse and point at existing content.
+        let mut described: HashSet<OsString> = HashSet::new();
+        for path in scan_dir(&info_dir, &mut problems) {
             if path.extension().is_none_or(|e| e != "trashinfo") {
                 continue;
             }
-
-            let trash_name = match path.file_stem().and_then(|s| s.to_str()) {
-                Some(name) => name.to_string(),
-                None => continue,
+            let Some(trash_name) = path.file_stem() else {
+                continue;
             };
-
-            // Verify the corresponding file still exists in files/
-            if !self.files_dir().join(&trash_name).exists() {
+            described.insert(trash_name.to_os_string());
+
+            let content = match fs::read_to_string(&path) {
+                Ok(c) => c,
+                Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+                    problems.push(TrashProblem::Permission {
+                        path,
+                        reason: e.to_string(),
+                    });
+                    continue;
+                }
+                Err(e) => {
+                    problems.push(TrashProblem::BrokenInfo {
+                        path,
+                        reason: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            if let Err(e) = parse_trashinfo(&content) {
+                problems.push(TrashProblem::BrokenInfo {
+                    path,
+                    reason: e.to_string(),
+                });
                 continue;
             }
+            if files_dir.join(trash_name).symlink_metadata().is_err() {
+                problems.push(TrashProblem::DanglingInfo { path });
+            }
+        }
 
-            let content = fs::read_to_string(&path)?;
-            let (original_path, deleted_at) = match parse_trashinfo(&content) {
-                Ok(v) => v,
-                Err(_) => continue,
+        // Content side: everything in files/ needs metadata and must be readable.
+        for path in scan_dir(&files_dir, &mut problems) {
+            let Some(name) = path.file_name() else {
+                continue;
             };
+            if !described.contains(name) {
+                problems.push(TrashProblem::Orphan { path });
+                continue;
+            }
+            let readable = if path.is_dir() {
+                fs::read_dir(&path).map(|_| ())
+            } else {
+                fs::File::open(&path).map(|_| ())
+            };
+            if let Err(e) = readable
+                && e.kind() == ErrorKind::PermissionDenied
+            {
+                problems.push(TrashProblem::Permission {
+                    path,
+                    reason: e.to_string(),
+                });
+            }
+        }
 
-            // Apply filter
-            if let Some(pat) = filter {
-                let name_matches = trash_name.contains(pat);
-                let path_matches = original_path.to_string_lossy().contains(pat);
-                if !name_matches && !path_matches {
-                    continue;
+        Ok(problems)
+    }
+
+    fn repair(&self, problem: &TrashProblem) -> crate::Result<()> {
+        // Repairs add and remove metadata behind the index's back
+        self.sync_index(self.index().invalidate(), &NoopObserver);
+        match problem {
+            TrashProblem::Orphan { path } => {
+                // Synthesize metadata so the content becomes restorable again.
+                let trash_name = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .with_context(|| format!("unsupported file name: {:?}", path))?;
+                let deleted_at = fs::symlink_metadata(path)
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .map(|t| chrono::DateTime::<Local>::from(t).timestamp());
+                let info = TrashInfo {
+                    original_path: self.recovered_dir().join(trash_name),
+                    deleted_at,
+                    content_hash: content_hash(path).ok(),
+                    size: tree_size(path).ok(),
+                    compression: None,
+                    encrypted: false,
+                    dedup: false,
+                    mode: None,
+                    mtime: None,
+                    pinned: false,
+                    annotation: Annotation::default(),
+                };
+                Ok(self.write_trashinfo(trash_name, &info)?)
+            }
+            TrashProblem::DanglingInfo { path } => Ok(fs::remove_file(path)
+                .with_context(|| format!("failed to remove trashinfo: {:?}", path))?),
+            TrashProblem::BrokenInfo { path, .. } => {
+                if let Some(trash_name) = path.file_stem() {
+                    let content = self.files_dir().join(trash_name);
+                    if content.symlink_metadata().is_ok() {
+                        self.quarantine(&content, "files")?;
+                    }
                 }
+                Ok(self.quarantine(path, "info")?)
             }
+            TrashProblem::Permission { path, .. } => Err(Error::msg(t!(
+                "fsck_permission",
+                path = path.display().to_string()
+            ))),
+        }
+    }
+}
 
-            let display_name = original_path
-                .file_name()
-                .unwrap_or(OsStr::new(&trash_name))
-                .to_os_string();
+/// Total size in bytes of a file, symlink or directory tree (symlinks are not followed).
+fn tree_size(path: &Path) -> Result<u64> {
+    let meta = fs::symlink_metadata(path)?;
+    if !meta.is_dir() {
+        return Ok(meta.len());
+    }
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        total += tree_size(&entry?.path())?;
+    }
+    Ok(total)
+}
 
-            items.push(RestorableItem {
-                id: OsString::from(&trash_name),
-                original_path,
-                display_name,
-                deleted_at,
-            });
-        }
+/// Whether `name` is a ULID as [`ManagedTrash::new_id`] writes it.
+fn is_ulid(name: &str) -> bool {
+    name.len() == 26
+        && name.bytes().all(|b| {
+            b.is_ascii_digit()
+                || (b.is_ascii_uppercase() && !matches!(b, b'I' | b'L' | b'O' | b'U'))
+        })
+}
 
-        Ok(items)
+/// Remove a file, symlink or whole directory tree.
+pub(crate) fn remove_tree(path: &Path) -> std::io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
     }
+}
 
-    fn restore_to(&self, item_id: &OsStr, destination: &Path) -> Result<()> {
-        let trash_name = item_id.to_string_lossy();
-        let src = self.files_dir().join(trash_name.as_ref());
+/// Copy `source` to `dest` on another filesystem, leaving no partial copy
+/// behind on failure. `progress` gets the bytes copied so far and the total.
+fn copy_across(
+    source: &Path,
+    dest: &Path,
+    progress: &mut dyn FnMut(u64, u64),
+) -> std::io::Result<()> {
+    let total = tree_size(source).unwrap_or(0);
+    let mut copied = 0;
+    let result = copy_tree(source, dest, &mut |bytes| {
+        copied += bytes;
+        progress(copied, total);
+    });
+    if result.is_err() {
+        let _ = remove_tree(dest);
+    }
+    result
+}
 
-        if !src.exists() {
-            anyhow::bail!(t!("restore_not_found"));
+/// Move `source` to `dest`, falling back to a copy and delete when they are
+/// on different filesystems.
+pub(crate) fn move_across(source: &Path, dest: &Path) -> std::io::Result<()> {
+    match fs::rename(source, dest) {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            copy_across(source, dest, &mut |_, _| {})?;
+            remove_tree(source)
         }
+        result => result,
+    }
+}
 
-        fs::rename(&src, destination).with_context(|| {
-            t!(
-                "error_restore_failed",
-                name = trash_name,
-                reason = "rename failed"
-            )
-        })?;
+/// Copy a file, symlink or directory tree with its permissions and file
+/// modification times, reporting the bytes of each chunk written.
+fn copy_tree(source: &Path, dest: &Path, on_bytes: &mut dyn FnMut(u64)) -> std::io::Result<()> {
+    use std::io::{Read, Write};
 
-        // Clean up the .trashinfo file
-        let info_path = self.info_dir().join(format!("{}.trashinfo", trash_name));
-        let _ = fs::remove_file(info_path);
+    let meta = fs::symlink_metadata(source)?;
+    if meta.is_symlink() {
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(fs::read_link(source)?, dest)?;
+            // Counted by tree_size, so the total is reached
+            on_bytes(meta.len());
+        }
+        #[cfg(not(unix))]
+        return Err(std::io::Error::new(
+            ErrorKind::Unsupported,
+            format!("cannot copy symlink: {:?}", source),
+        ));
+    } else if meta.is_dir() {
+        fs::create_dir(dest)?;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            copy_tree(&entry.path(), &dest.join(entry.file_name()), on_bytes)?;
+        }
+        fs::set_permissions(dest, meta.permissions())?;
+    } else if !meta.is_file() {
+        // Opening a FIFO would block, and a device would be read to its end
+        return Err(std::io::Error::new(
+            ErrorKind::Unsupported,
+            format!("cannot copy special file: {:?}", source),
+        ));
+    } else {
+        let mut input = fs::File::open(source)?;
+        // Private until the source's permissions are applied below
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut output = options.open(dest)?;
+        let mut buf = vec![0; 1 << 20];
+        loop {
+            let n = input.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            output.write_all(&buf[..n])?;
+            on_bytes(n as u64);
+        }
+        output.set_permissions(meta.permissions())?;
+        output.set_modified(meta.modified()?)?;
+    }
+    Ok(())
+}
 
-        Ok(())
+/// Permanently delete a file or tree, shredding it when a `shredder` is given.
+fn discard(path: &Path, shredder: Option<&Shredder>) -> Result<()> {
+    match shredder {
+        Some(shredder) => shredder.remove(path),
+        None => remove_tree(path).with_context(|| format!("failed to remove: {:?}", path)),
+    }
+}
+
+/// Read `path` as a dedup manifest; None if it is not one.
+fn read_manifest(path: &Path) -> Result<Option<String>> {
+    use std::io::Read;
+    let mut header = [0u8; 18];
+    let is_manifest = fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .is_ok_and(|()| header.as_slice() == b"saferm-manifest 1\n");
+    if !is_manifest {
+        return Ok(None);
+    }
+    let manifest =
+        fs::read_to_string(path).with_context(|| format!("failed to read manifest: {:?}", path))?;
+    Ok(Some(manifest))
+}
+
+/// List a trash directory, recording a permission problem instead of failing.
+fn scan_dir(dir: &Path, problems: &mut Vec<TrashProblem>) -> Vec<PathBuf> {
+    match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(e) if e.kind() == ErrorKind::NotFound => vec![],
+        Err(e) => {
+            problems.push(TrashProblem::Permission {
+                path: dir.to_path_buf(),
+                reason: e.to_string(),
+            });
+            vec![]
+        }
     }
 }
 
-/// Parse a .trashinfo file and return (original_path, deleted_at_unix_seconds or None).
-fn parse_trashinfo(content: &str) -> Result<(PathBuf, Option<i64>)> {
+/// Parsed contents of a `.trashinfo` file.
+struct TrashInfo {
+    original_path: PathBuf,
+    /// Deletion timestamp as unix seconds (None if missing or unparseable)
+    deleted_at: Option<i64>,
+    /// `algorithm:hex` content hash recorded at trash time (None for older entries)
+    content_hash: Option<String>,
+    /// Total size in bytes recorded at trash time (None for older entries)
+    size: Option<u64>,
+    /// How the content is stored (None: as is)
+    compression: Option<Compression>,
+    /// Whether the stored archive is age-encrypted
+    encrypted: bool,
+    /// Whether `files/` holds a manifest of blobs in the object store
+    dedup: bool,
+    /// Permission bits of a compressed item itself
+    mode: Option<u32>,
+    /// Modification time (unix seconds) of a compressed item itself
+    mtime: Option<i64>,
+    /// Kept by cleanup unless pinned entries are explicitly included
+    pinned: bool,
+    annotation: Annotation,
+}
+
+/// Parse a .trashinfo file.
+fn parse_trashinfo(content: &str) -> Result<TrashInfo> {
     let mut path: Option<PathBuf> = None;
     let mut date: Option<i64> = None;
+    let mut hash: Option<String> = None;
+    let mut size: Option<u64> = None;
+    let mut compression: Option<Compression> = None;
+    let mut encrypted = false;
+    let mut dedup = false;
+    let mut mode: Option<u32> = None;
+    let mut mtime: Option<i64> = None;
+    let mut pinned = false;
+    let mut annotation = Annotation::default();
 
     for line in content.lines() {
+        if annotation.read_info_line(line) {
+            continue;
+        }
         if let Some(p) = line.strip_prefix("Path=") {
-            path = Some(PathBuf::from(p));
+            // Percent-encoded as in FreeDesktop trashinfo, so a newline in a
+            // name can't start a line of its own
+            path = Some(decode_uri_path(p));
+        } else if let Some(h) = line.strip_prefix("ContentHash=") {
+            hash = Some(h.to_string());
+        } else if let Some(n) = line.strip_prefix("Size=") {
+            size = n.parse().ok();
+        } else if let Some(c) = line.strip_prefix("Compression=") {
+            compression = Some(
+                Compression::parse(c)
+                    .with_context(|| format!("invalid trashinfo: unknown Compression {}", c))?,
+            );
+        } else if let Some(e) = line.strip_prefix("Encryption=") {
+            if e != "age" {
+                anyhow::bail!("invalid trashinfo: unknown Encryption {}", e);
+            }
+            encrypted = true;
+        } else if let Some(st) = line.strip_prefix("Storage=") {
+            if st != "dedup" {
+                anyhow::bail!("invalid trashinfo: unknown Storage {}", st);
+            }
+            dedup = true;
+        } else if let Some(m) = line.strip_prefix("Mode=") {
+            mode = u32::from_str_radix(m, 8).ok();
+        } else if let Some(t) = line.strip_prefix("Mtime=") {
+            mtime = t.parse().ok();
+        } else if let Some(p) = line.strip_prefix("Pinned=") {
+            pinned = p == "true";
         } else if let Some(d) = line.strip_prefix("DeletionDate=")
             && let Ok(dt) = chrono::NaiveDateTime::parse_from_str(d, "%Y-%m-%dT%H:%M:%S")
             && let chrono::LocalResult::Single(local_dt) = dt.and_local_timezone(Local)
@@ -292,11 +1528,73 @@ fn parse_trashinfo(content: &str) -> Result<(PathBuf, Option<i64>)> {
     }
 
     match path {
-        Some(p) => Ok((p, date)),
+        Some(p) => Ok(TrashInfo {
+            original_path: p,
+            deleted_at: date,
+            content_hash: hash,
+            size,
+            compression,
+            encrypted,
+            dedup,
+            mode,
+            mtime,
+            pinned,
+            annotation,
+        }),
         None => anyhow::bail!("invalid trashinfo: missing Path"),
     }
 }
 
+impl TrashInfo {
+    /// True if the content has to be unpacked rather than renamed back.
+    fn is_packed(&self) -> bool {
+        self.compression.is_some() || self.dedup
+    }
+
+    /// Serialize in the `.trashinfo` format read by [`parse_trashinfo`].
+    fn render(&self) -> String {
+        let mut content = format!(
+            "[Trash Info]\nPath={}\n",
+            encode_uri_path(&self.original_path)
+        );
+        if let Some(date) = self
+            .deleted_at
+            .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+        {
+            content.push_str(&format!(
+                "DeletionDate={}\n",
+                date.with_timezone(&Local).format("%Y-%m-%dT%H:%M:%S")
+            ));
+        }
+        if let Some(hash) = &self.content_hash {
+            content.push_str(&format!("ContentHash={}\n", hash));
+        }
+        if let Some(size) = self.size {
+            content.push_str(&format!("Size={}\n", size));
+        }
+        if let Some(compression) = self.compression {
+            content.push_str(&format!("Compression={}\n", compression.as_str()));
+        }
+        if self.encrypted {
+            content.push_str("Encryption=age\n");
+        }
+        if self.dedup {
+            content.push_str("Storage=dedup\n");
+        }
+        if let Some(mode) = self.mode {
+            content.push_str(&format!("Mode={:o}\n", mode));
+        }
+        if let Some(mtime) = self.mtime {
+            content.push_str(&format!("Mtime={}\n", mtime));
+        }
+        if self.pinned {
+            content.push_str("Pinned=true\n");
+        }
+        content.push_str(&self.annotation.to_info_lines());
+        content
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -309,6 +1607,76 @@ mod tests {
         (tmp, handler)
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_trash_fifo_does_not_read_it() {
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::fs::FileTypeExt;
+
+        let tmp = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let fifo = source_dir.path().join("p");
+        let c_path = std::ffi::CString::new(fifo.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+
+        // Packing is skipped too: compression would otherwise apply to every name
+        let handler = ManagedTrash {
+            compression: CompressionPolicy::matching(&["*"]),
+            ..ManagedTrash::with_base_dir(tmp.path().to_path_buf())
+        };
+        let (done, finished) = std::sync::mpsc::channel();
+        let id = std::thread::scope(|scope| {
+            scope.spawn(|| done.send(handler.trash(&fifo).unwrap().unwrap()).unwrap());
+            finished
+                .recv_timeout(std::time::Duration::from_secs(10))
+                .expect("trashing a FIFO must not block")
+        });
+
+        assert!(
+            tmp.path()
+                .join("info")
+                .join(format!("{}.trashinfo", id.to_str().unwrap()))
+                .exists()
+        );
+        let results = handler.verify_integrity(None).unwrap();
+        assert_eq!(results[0].1, Integrity::Intact);
+        handler.restore_to(&id, &fifo).unwrap();
+        assert!(fs::symlink_metadata(&fifo).unwrap().file_type().is_fifo());
+    }
+
+    #[test]
+    fn test_ids_outside_the_trash_are_refused() {
+        let (tmp, handler) = setup();
+        handler.ensure_dirs().unwrap();
+        let victim = tmp.path().join("victim");
+        fs::write(&victim, "keep").unwrap();
+        fs::write(tmp.path().join("victim.trashinfo"), "[Trash Info]\n").unwrap();
+
+        for id in ["../victim", "/etc/passwd", "..", ""] {
+            let id = OsStr::new(id);
+            let error = handler.purge(id, None).unwrap_err();
+            assert_eq!(error.kind(), crate::ErrorKind::IdNotFound);
+            assert!(handler.restore_to(id, &tmp.path().join("out")).is_err());
+            assert!(handler.set_pinned(id, true).is_err());
+            assert!(!handler.contains(id));
+        }
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "keep");
+        assert!(tmp.path().join("victim.trashinfo").exists());
+
+        // Entries named after their file before ids were ULIDs still restore
+        fs::write(tmp.path().join("files/old.txt"), "old").unwrap();
+        fs::write(
+            tmp.path().join("info/old.txt.trashinfo"),
+            "[Trash Info]\nPath=/tmp/old.txt\nDeletionDate=2024-01-01T00:00:00\n",
+        )
+        .unwrap();
+        let restored = tmp.path().join("old.txt");
+        handler
+            .restore_to(OsStr::new("old.txt"), &restored)
+            .unwrap();
+        assert_eq!(fs::read_to_string(&restored).unwrap(), "old");
+    }
+
     #[test]
     fn test_trash_file() {
         let (tmp, handler) = setup();
@@ -318,18 +1686,20 @@ mod tests {
         let file_path = source_dir.path().join("test.txt");
         fs::write(&file_path, "hello").unwrap();
 
-        handler.trash(&file_path).unwrap();
+        let id = handler.trash(&file_path).unwrap().unwrap();
+        let id = id.to_str().unwrap();
 
         // Original should be gone
         assert!(!file_path.exists());
 
-        // Should be in trash files dir
-        let trashed = tmp.path().join("files").join("test.txt");
+        // Should be in trash files dir under its ULID
+        assert_eq!(id.len(), 26);
+        let trashed = tmp.path().join("files").join(id);
         assert!(trashed.exists());
         assert_eq!(fs::read_to_string(&trashed).unwrap(), "hello");
 
         // Should have trashinfo
-        let info = tmp.path().join("info").join("test.txt.trashinfo");
+        let info = tmp.path().join("info").join(format!("{}.trashinfo", id));
         assert!(info.exists());
         let info_content = fs::read_to_string(&info).unwrap();
         assert!(info_content.contains("[Trash Info]"));
@@ -338,32 +1708,90 @@ mod tests {
     }
 
     #[test]
-    fn test_trash_name_collision() {
+    fn test_newline_in_name_stays_in_path() {
+        let (tmp, handler) = setup();
+        let source_dir = TempDir::new().unwrap();
+        let file = source_dir.path().join("a\nPinned=true");
+        fs::write(&file, "a").unwrap();
+        let id = handler.trash(&file).unwrap().unwrap();
+
+        let info = fs::read_to_string(
+            tmp.path()
+                .join("info")
+                .join(format!("{}.trashinfo", id.to_str().unwrap())),
+        )
+        .unwrap();
+        assert!(!info.contains("Pinned=true"));
+        handler.index().invalidate().unwrap();
+        let items = handler.list_restorable(None, None).unwrap();
+        assert_eq!(items[0].original_path, file);
+        assert!(!items[0].pinned);
+
+        handler.restore_to(&id, &file).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "a");
+    }
+
+    #[test]
+    fn test_trash_name_collision() {
+        let (tmp, handler) = setup();
+
+        // Create and trash first file
+        let source_dir = TempDir::new().unwrap();
+        let file1 = source_dir.path().join("dup.txt");
+        fs::write(&file1, "first").unwrap();
+        let id1 = handler.trash(&file1).unwrap().unwrap();
+
+        // Create and trash second file with same name
+        let file2 = source_dir.path().join("dup.txt");
+        fs::write(&file2, "second").unwrap();
+        let id2 = handler.trash(&file2).unwrap().unwrap();
+
+        // Both should exist in trash with different ids
+        assert_ne!(id1, id2);
+        let files_dir = tmp.path().join("files");
+        assert_eq!(fs::read_to_string(files_dir.join(&id1)).unwrap(), "first");
+        assert_eq!(fs::read_to_string(files_dir.join(&id2)).unwrap(), "second");
+    }
+
+    #[test]
+    fn test_ids_do_not_collide_with_file_names() {
+        let (tmp, handler) = setup();
+
+        // A real file named like the old `stem.N.ext` collision scheme
+        let source_dir = TempDir::new().unwrap();
+        for name in ["dup.txt", "dup.1.txt", "dup.txt"] {
+            let file = source_dir.path().join(name);
+            fs::write(&file, name).unwrap();
+            handler.trash(&file).unwrap();
+        }
+
+        let items = handler.list_restorable(None, None).unwrap();
+        assert_eq!(items.len(), 3);
+        assert!(items.iter().any(|i| i.original_path.ends_with("dup.1.txt")));
+        assert!(!tmp.path().join("files").join("dup.1.txt").exists());
+    }
+
+    #[test]
+    fn test_legacy_named_entries_are_restorable() {
         let (tmp, handler) = setup();
 
-        // Create and trash first file
-        let source_dir = TempDir::new().unwrap();
-        let file1 = source_dir.path().join("dup.txt");
-        fs::write(&file1, "first").unwrap();
-        handler.trash(&file1).unwrap();
+        // Entries written before ULID ids used the original file name
+        handler.ensure_dirs().unwrap();
+        fs::write(tmp.path().join("files").join("old.txt"), "old").unwrap();
+        fs::write(
+            tmp.path().join("info").join("old.txt.trashinfo"),
+            "[Trash Info]\nPath=/tmp/old.txt\nDeletionDate=2024-01-01T00:00:00\n",
+        )
+        .unwrap();
 
-        // Create and trash second file with same name
-        let file2 = source_dir.path().join("dup.txt");
-        fs::write(&file2, "second").unwrap();
-        handler.trash(&file2).unwrap();
+        let items = handler.list_restorable(None, None).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].id, "old.txt");
 
-        // Both should exist in trash with different names
-        let files_dir = tmp.path().join("files");
-        assert!(files_dir.join("dup.txt").exists());
-        assert!(files_dir.join("dup.1.txt").exists());
-        assert_eq!(
-            fs::read_to_string(files_dir.join("dup.txt")).unwrap(),
-            "first"
-        );
-        assert_eq!(
-            fs::read_to_string(files_dir.join("dup.1.txt")).unwrap(),
-            "second"
-        );
+        let dest_dir = TempDir::new().unwrap();
+        let dest = dest_dir.path().join("old.txt");
+        handler.restore_to(&items[0].id, &dest).unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "old");
     }
 
     #[test]
@@ -394,7 +1822,7 @@ mod tests {
         .unwrap();
 
         let prompter = AutoConfirmPrompter;
-        handler.cleanup(&prompter).unwrap();
+        handler.cleanup(&prompter, false).unwrap();
 
         // Files and info should be gone
         assert!(
@@ -411,13 +1839,216 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_cleanup_shred() {
+        let (tmp, mut handler) = setup();
+        handler.dedup = true;
+
+        let source_dir = TempDir::new().unwrap();
+        let dir = source_dir.path().join("creds");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("token"), "secret").unwrap();
+        handler.trash(&dir).unwrap();
+        assert!(tmp.path().join("objects").exists());
+
+        handler
+            .cleanup_shred(&AutoConfirmPrompter, &Shredder::new(2, false), false)
+            .unwrap();
+        assert!(handler.list_restorable(None, None).unwrap().is_empty());
+        assert!(!tmp.path().join("objects").exists());
+        assert!(
+            fs::read_dir(tmp.path().join("files"))
+                .unwrap()
+                .next()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_purge_single_entry() {
+        let (tmp, mut handler) = setup();
+        handler.dedup = true;
+
+        let source_dir = TempDir::new().unwrap();
+        let mut ids = vec![];
+        for name in ["a", "b"] {
+            let dir = source_dir.path().join(name);
+            fs::create_dir(&dir).unwrap();
+            fs::write(dir.join("shared.txt"), "same content").unwrap();
+            ids.push(handler.trash(&dir).unwrap().unwrap());
+        }
+
+        // Blobs still referenced by the other entry survive
+        handler
+            .purge(&ids[0], Some(&Shredder::new(1, false)))
+            .unwrap();
+        let remaining = handler.list_restorable(None, None).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, ids[1]);
+        assert!(
+            !tmp.path()
+                .join("info")
+                .join(format!("{}.trashinfo", ids[0].to_string_lossy()))
+                .exists()
+        );
+
+        let dest = source_dir.path().join("b");
+        handler.restore_to(&ids[1], &dest).unwrap();
+        assert_eq!(
+            fs::read_to_string(dest.join("shared.txt")).unwrap(),
+            "same content"
+        );
+
+        assert!(handler.purge(&ids[0], None).is_err());
+    }
+
+    #[test]
+    fn test_cleanup_keeps_pinned_entries() {
+        let (_tmp, handler) = setup();
+        let source_dir = TempDir::new().unwrap();
+        let mut ids = vec![];
+        for name in ["keep.txt", "drop.txt"] {
+            let file = source_dir.path().join(name);
+            fs::write(&file, name).unwrap();
+            ids.push(handler.trash(&file).unwrap().unwrap());
+        }
+        handler.set_pinned(&ids[0], true).unwrap();
+
+        handler.cleanup(&AutoConfirmPrompter, false).unwrap();
+        let remaining = handler.list_restorable(None, None).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, ids[0]);
+        assert!(remaining[0].pinned);
+        // Survives a rebuild of the index from the metadata
+        handler.index().invalidate().unwrap();
+        assert!(handler.list_restorable(None, None).unwrap()[0].pinned);
+
+        handler.cleanup(&AutoConfirmPrompter, false).unwrap();
+        assert_eq!(handler.list_restorable(None, None).unwrap().len(), 1);
+        handler.cleanup(&AutoConfirmPrompter, true).unwrap();
+        assert!(handler.list_restorable(None, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cleanup_reports_through_the_observer() {
+        let (_tmp, handler) = setup();
+        let source_dir = TempDir::new().unwrap();
+        let file = source_dir.path().join("keep.txt");
+        fs::write(&file, "keep").unwrap();
+        let id = handler.trash(&file).unwrap().unwrap();
+        handler.set_pinned(&id, true).unwrap();
+        let file = source_dir.path().join("drop.txt");
+        fs::write(&file, "drop").unwrap();
+        handler.trash(&file).unwrap();
+
+        let events = std::sync::Mutex::new(vec![]);
+        let observer = |event: &Event| events.lock().unwrap().push(format!("{:?}", event));
+        handler
+            .cleanup_observed(&AutoConfirmPrompter, None, false, &observer)
+            .unwrap();
+        handler
+            .cleanup_observed(&AutoConfirmPrompter, None, false, &observer)
+            .unwrap();
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "CleanedUp { pinned_kept: 1 }",
+                "NothingToCleanUp { pinned: 1 }"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unpin() {
+        let (_tmp, handler) = setup();
+        let source_dir = TempDir::new().unwrap();
+        let file = source_dir.path().join("a.txt");
+        fs::write(&file, "a").unwrap();
+        let id = handler.trash(&file).unwrap().unwrap();
+
+        handler.set_pinned(&id, true).unwrap();
+        handler.set_pinned(&id, false).unwrap();
+        assert!(!handler.list_restorable(None, None).unwrap()[0].pinned);
+        handler.cleanup(&AutoConfirmPrompter, false).unwrap();
+        assert!(handler.list_restorable(None, None).unwrap().is_empty());
+
+        assert!(handler.set_pinned(OsStr::new("missing"), true).is_err());
+    }
+
+    #[test]
+    fn test_annotated_entries_filter_by_tag() {
+        let (_tmp, handler) = setup();
+        let source_dir = TempDir::new().unwrap();
+        let old = source_dir.path().join("old_schema");
+        fs::create_dir(&old).unwrap();
+        fs::write(old.join("v1.sql"), "create table t;").unwrap();
+        let other = source_dir.path().join("other.txt");
+        fs::write(&other, "x").unwrap();
+
+        let annotation = Annotation {
+            reason: Some("replaced by v2 schema".to_string()),
+            tags: vec!["migration".to_string(), "db".to_string()],
+        };
+        let id = handler.trash_annotated(&old, &annotation).unwrap().unwrap();
+        handler.trash(&other).unwrap();
+
+        let tagged = handler.list_restorable(None, Some("migration")).unwrap();
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(tagged[0].id, id);
+        assert_eq!(tagged[0].annotation, annotation);
+        assert_eq!(handler.list_restorable(None, None).unwrap().len(), 2);
+        assert!(
+            handler
+                .list_restorable(None, Some("nope"))
+                .unwrap()
+                .is_empty()
+        );
+
+        // Read back from the trashinfo when the index is rebuilt
+        handler.index().invalidate().unwrap();
+        let tagged = handler.list_restorable(Some("old"), Some("db")).unwrap();
+        assert_eq!(tagged[0].annotation, annotation);
+    }
+
+    #[test]
+    fn test_evict_respects_pins_and_limits() {
+        let (_tmp, mut handler) = setup();
+        let source_dir = TempDir::new().unwrap();
+        let mut ids = vec![];
+        for name in ["old.txt", "pinned.txt", "new.txt"] {
+            let file = source_dir.path().join(name);
+            fs::write(&file, "0123456789").unwrap();
+            ids.push(handler.trash(&file).unwrap().unwrap());
+        }
+        handler.set_pinned(&ids[1], true).unwrap();
+
+        // Without limits nothing goes
+        assert!(handler.evict().unwrap().is_empty());
+
+        handler.retention = Retention {
+            max_age: None,
+            quota: Some(15),
+        };
+        let evicted = handler.evict().unwrap();
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].id, ids[0]);
+        let remaining: Vec<_> = handler
+            .list_restorable(None, None)
+            .unwrap()
+            .into_iter()
+            .map(|item| item.id)
+            .collect();
+        assert_eq!(remaining.len(), 2);
+        assert!(!remaining.contains(&ids[0]));
+    }
+
     #[test]
     fn test_cleanup_empty() {
         let (_tmp, handler) = setup();
 
         // Cleanup on empty trash should not error
         let prompter = AutoConfirmPrompter;
-        handler.cleanup(&prompter).unwrap();
+        handler.cleanup(&prompter, false).unwrap();
     }
 
     #[test]
@@ -434,11 +2065,11 @@ mod tests {
         handler.trash(&file2).unwrap();
 
         // List all
-        let items = handler.list_restorable(None).unwrap();
+        let items = handler.list_restorable(None, None).unwrap();
         assert_eq!(items.len(), 2);
 
         // Filter by pattern
-        let filtered = handler.list_restorable(Some("alpha")).unwrap();
+        let filtered = handler.list_restorable(Some("alpha"), None).unwrap();
         assert_eq!(filtered.len(), 1);
         assert!(
             filtered[0]
@@ -448,14 +2079,14 @@ mod tests {
         );
 
         // Filter with no match
-        let empty = handler.list_restorable(Some("nonexistent")).unwrap();
+        let empty = handler.list_restorable(Some("nonexistent"), None).unwrap();
         assert!(empty.is_empty());
     }
 
     #[test]
     fn test_list_restorable_empty_trash() {
         let (_tmp, handler) = setup();
-        let items = handler.list_restorable(None).unwrap();
+        let items = handler.list_restorable(None, None).unwrap();
         assert!(items.is_empty());
     }
 
@@ -471,7 +2102,7 @@ mod tests {
         assert!(!file_path.exists());
 
         // List and restore
-        let items = handler.list_restorable(None).unwrap();
+        let items = handler.list_restorable(None, None).unwrap();
         assert_eq!(items.len(), 1);
 
         let dest = source_dir.path().join("restored.txt");
@@ -481,7 +2112,7 @@ mod tests {
         assert_eq!(fs::read_to_string(&dest).unwrap(), "important data");
 
         // Trash should now be empty
-        let after = handler.list_restorable(None).unwrap();
+        let after = handler.list_restorable(None, None).unwrap();
         assert!(after.is_empty());
     }
 
@@ -497,7 +2128,7 @@ mod tests {
         assert!(!file_path.exists());
 
         // Restore to original path
-        let items = handler.list_restorable(None).unwrap();
+        let items = handler.list_restorable(None, None).unwrap();
         handler
             .restore_to(&items[0].id, &items[0].original_path)
             .unwrap();
@@ -519,7 +2150,7 @@ mod tests {
         // Restore to a path with a non-existent parent directory
         // Note: parent dir creation is handled in ops.rs, not in the backend.
         // Backend only does the rename. Let's test the basic restore.
-        let items = handler.list_restorable(None).unwrap();
+        let items = handler.list_restorable(None, None).unwrap();
         let new_dest = source_dir.path().join("test_restored.txt");
         handler.restore_to(&items[0].id, &new_dest).unwrap();
         assert!(new_dest.exists());
@@ -534,7 +2165,7 @@ mod tests {
             std::ffi::OsStr::new("nonexistent"),
             Path::new("/tmp/dest.txt"),
         );
-        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), crate::ErrorKind::IdNotFound);
     }
 
     #[test]
@@ -550,7 +2181,7 @@ mod tests {
         assert!(!dir_path.exists());
 
         // Restore it
-        let items = handler.list_restorable(None).unwrap();
+        let items = handler.list_restorable(None, None).unwrap();
         assert_eq!(items.len(), 1);
         handler.restore_to(&items[0].id, &dir_path).unwrap();
 
@@ -561,4 +2192,530 @@ mod tests {
             "inside"
         );
     }
+
+    #[test]
+    fn test_restore_refuses_corrupted_content() {
+        let (tmp, handler) = setup();
+
+        let source_dir = TempDir::new().unwrap();
+        let file_path = source_dir.path().join("rot.txt");
+        fs::write(&file_path, "pristine").unwrap();
+        let id = handler.trash(&file_path).unwrap().unwrap();
+        let id = id.to_str().unwrap();
+
+        let info =
+            fs::read_to_string(tmp.path().join("info").join(format!("{}.trashinfo", id))).unwrap();
+        assert!(info.contains("ContentHash=sha256:"));
+
+        // Simulate bit rot / tampering inside files/
+        fs::write(tmp.path().join("files").join(id), "rotten").unwrap();
+
+        let error = handler.restore_to(OsStr::new(id), &file_path).unwrap_err();
+        assert_eq!(error.kind(), crate::ErrorKind::Corrupted);
+        assert!(!file_path.exists());
+        assert!(tmp.path().join("files").join(id).exists());
+    }
+
+    #[test]
+    fn test_restore_packed_errors_are_typed() {
+        let tmp = TempDir::new().unwrap();
+        let handler = ManagedTrash {
+            base_dir: tmp.path().to_path_buf(),
+            compression: CompressionPolicy::matching(&["*.log"]),
+            encryption: None,
+            dedup: false,
+            hashing: true,
+            retention: Retention::default(),
+            label: "managed",
+            home: None,
+        };
+        let source_dir = TempDir::new().unwrap();
+        let log = source_dir.path().join("app.log");
+        fs::write(&log, "line\n").unwrap();
+        let id = handler.trash(&log).unwrap().unwrap();
+
+        // A directory in the way fails the final rename
+        let occupied = source_dir.path().join("occupied");
+        fs::create_dir_all(occupied.join("child")).unwrap();
+        let error = handler.restore_to(&id, &occupied).unwrap_err();
+        assert!(error.io_error().is_some());
+        assert_eq!(error.path(), Some(occupied.as_path()));
+
+        // A recorded hash that doesn't match the unpacked content
+        let info_path = tmp
+            .path()
+            .join("info")
+            .join(format!("{}.trashinfo", id.to_str().unwrap()));
+        let info = fs::read_to_string(&info_path).unwrap();
+        let tampered: String = info
+            .lines()
+            .map(|line| match line.starts_with("ContentHash=") {
+                true => format!("ContentHash=sha256:{}\n", "0".repeat(64)),
+                false => format!("{}\n", line),
+            })
+            .collect();
+        fs::write(&info_path, tampered).unwrap();
+        let error = handler.restore_to(&id, &log).unwrap_err();
+        assert_eq!(error.kind(), crate::ErrorKind::Corrupted);
+        assert!(!log.exists());
+    }
+
+    #[test]
+    fn test_verify_integrity() {
+        let (tmp, handler) = setup();
+
+        let source_dir = TempDir::new().unwrap();
+        let dir_path = source_dir.path().join("tree");
+        fs::create_dir(&dir_path).unwrap();
+        fs::write(dir_path.join("inner.txt"), "inside").unwrap();
+        let id = handler.trash(&dir_path).unwrap().unwrap();
+
+        let results = handler.verify_integrity(None).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].1, Integrity::Intact);
+
+        fs::write(tmp.path().join("files").join(id).join("extra.txt"), "new").unwrap();
+        let results = handler.verify_integrity(None).unwrap();
+        assert_eq!(results[0].1, Integrity::Corrupted);
+    }
+
+    #[test]
+    fn test_verify_integrity_continues_past_unreadable_entry() {
+        let tmp = TempDir::new().unwrap();
+        let key = tmp.path().join("key.txt");
+        let identity = age::x25519::Identity::generate();
+        fs::write(
+            &key,
+            age::secrecy::ExposeSecret::expose_secret(&identity.to_string()),
+        )
+        .unwrap();
+        let encrypting = ManagedTrash {
+            encryption: Some(KeySource::KeyFile(key)),
+            ..ManagedTrash::with_base_dir(tmp.path().join("trash"))
+        };
+        let handler = ManagedTrash::with_base_dir(tmp.path().join("trash"));
+
+        let source_dir = TempDir::new().unwrap();
+        let secret = source_dir.path().join("secret.txt");
+        let kept = source_dir.path().join("kept.txt");
+        fs::write(&secret, "secret").unwrap();
+        fs::write(&kept, "kept").unwrap();
+        let secret_id = encrypting.trash(&secret).unwrap().unwrap();
+        handler.trash(&kept).unwrap();
+
+        // Without the key the encrypted entry cannot be checked
+        let results = handler.verify_integrity(None).unwrap();
+        assert_eq!(results.len(), 2);
+        for (item, status) in &results {
+            if item.id == secret_id {
+                assert!(matches!(status, Integrity::Unreadable { .. }));
+            } else {
+                assert_eq!(status, &Integrity::Intact);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hashing_can_be_turned_off() {
+        let (_tmp, mut handler) = setup();
+        handler.hashing = false;
+
+        let source_dir = TempDir::new().unwrap();
+        let file = source_dir.path().join("big.bin");
+        fs::write(&file, "data").unwrap();
+        handler.trash(&file).unwrap();
+
+        let results = handler.verify_integrity(None).unwrap();
+        assert_eq!(results[0].1, Integrity::Unverified);
+    }
+
+    #[test]
+    fn test_verify_integrity_legacy_entry() {
+        let (tmp, handler) = setup();
+
+        // Entries written before content hashes existed are reported, not rejected.
+        handler.ensure_dirs().unwrap();
+        fs::write(tmp.path().join("files").join("old.txt"), "old").unwrap();
+        fs::write(
+            tmp.path().join("info").join("old.txt.trashinfo"),
+            "[Trash Info]\nPath=/tmp/old.txt\nDeletionDate=2024-01-01T00:00:00\n",
+        )
+        .unwrap();
+
+        let results = handler.verify_integrity(None).unwrap();
+        assert_eq!(results[0].1, Integrity::Unverified);
+    }
+
+    #[test]
+    fn test_compressed_entries_roundtrip() {
+        let tmp = TempDir::new().unwrap();
+        let handler = ManagedTrash {
+            base_dir: tmp.path().to_path_buf(),
+            compression: CompressionPolicy::matching(&["*.log", "build"]),
+            encryption: None,
+            dedup: false,
+            hashing: true,
+            retention: Retention::default(),
+            label: "managed",
+            home: None,
+        };
+
+        let source_dir = TempDir::new().unwrap();
+        let log = source_dir.path().join("app.log");
+        fs::write(&log, "line\n".repeat(1000)).unwrap();
+        let dir = source_dir.path().join("build");
+        fs::create_dir_all(dir.join("out")).unwrap();
+        fs::write(dir.join("out/bin"), "binary").unwrap();
+        let log_id = handler.trash(&log).unwrap().unwrap();
+        let dir_id = handler.trash(&dir).unwrap().unwrap();
+        assert!(!log.exists() && !dir.exists());
+
+        // Stored as single archives, listed with their original sizes
+        let files = tmp.path().join("files");
+        assert!(fs::metadata(files.join(&log_id)).unwrap().len() < 5000);
+        assert!(fs::metadata(files.join(&dir_id)).unwrap().is_file());
+        let info = fs::read_to_string(
+            tmp.path()
+                .join("info")
+                .join(format!("{}.trashinfo", dir_id.to_str().unwrap())),
+        )
+        .unwrap();
+        assert!(info.contains("Compression=zstd-tar"));
+        let items = handler.list_restorable(Some("app.log"), None).unwrap();
+        assert_eq!(items[0].size, Some(5000));
+
+        let results = handler.verify_integrity(None).unwrap();
+        assert!(results.iter().all(|(_, s)| *s == Integrity::Intact));
+
+        handler.restore_to(&log_id, &log).unwrap();
+        handler.restore_to(&dir_id, &dir).unwrap();
+        assert_eq!(fs::read_to_string(&log).unwrap(), "line\n".repeat(1000));
+        assert_eq!(fs::read_to_string(dir.join("out/bin")).unwrap(), "binary");
+        assert!(fs::read_dir(&files).unwrap().next().is_none());
+        assert_eq!(fs::read_dir(source_dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_encrypted_entries_roundtrip() {
+        let tmp = TempDir::new().unwrap();
+        let key = tmp.path().join("key.txt");
+        let identity = age::x25519::Identity::generate();
+        fs::write(
+            &key,
+            age::secrecy::ExposeSecret::expose_secret(&identity.to_string()),
+        )
+        .unwrap();
+        let handler = ManagedTrash {
+            base_dir: tmp.path().join("trash"),
+            compression: CompressionPolicy::default(),
+            encryption: Some(KeySource::KeyFile(key)),
+            dedup: false,
+            hashing: true,
+            retention: Retention::default(),
+            label: "managed",
+            home: None,
+        };
+
+        let source_dir = TempDir::new().unwrap();
+        let env = source_dir.path().join(".env");
+        fs::write(&env, "API_TOKEN=hunter2").unwrap();
+        let id = handler.trash(&env).unwrap().unwrap();
+
+        // Content is unreadable on disk, metadata stays listable
+        let blob = fs::read(tmp.path().join("trash/files").join(&id)).unwrap();
+        assert!(!blob.windows(7).any(|w| w == b"hunter2"));
+        let items = handler.list_restorable(Some(".env"), None).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            handler.verify_integrity(None).unwrap()[0].1,
+            Integrity::Intact
+        );
+
+        // Without the key, restore fails and keeps the entry
+        let keyless = ManagedTrash::with_base_dir(tmp.path().join("trash"));
+        assert!(keyless.restore_to(&id, &env).is_err());
+        assert!(!env.exists());
+
+        handler.restore_to(&id, &env).unwrap();
+        assert_eq!(fs::read_to_string(&env).unwrap(), "API_TOKEN=hunter2");
+        assert!(handler.list_restorable(None, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_missing_key_file_refuses_to_trash() {
+        let (tmp, mut handler) = setup();
+        handler.encryption = Some(KeySource::KeyFile(tmp.path().join("no-such-key")));
+
+        let source_dir = TempDir::new().unwrap();
+        let file_path = source_dir.path().join("secret.txt");
+        fs::write(&file_path, "secret").unwrap();
+        assert!(handler.trash(&file_path).is_err());
+        assert!(file_path.exists());
+    }
+
+    #[test]
+    fn test_deduplicated_entries_share_blobs() {
+        let (tmp, mut handler) = setup();
+        handler.dedup = true;
+        let objects = || {
+            fs::read_dir(tmp.path().join("objects"))
+                .map(|d| {
+                    d.map(|e| fs::read_dir(e.unwrap().path()).unwrap().count())
+                        .sum()
+                })
+                .unwrap_or(0)
+        };
+
+        let source_dir = TempDir::new().unwrap();
+        let dir = source_dir.path().join("node_modules");
+        let mut ids = vec![];
+        for _ in 0..2 {
+            fs::create_dir_all(dir.join("pkg")).unwrap();
+            fs::write(dir.join("pkg/index.js"), "module.exports = 42;").unwrap();
+            fs::write(dir.join("pkg/README"), "same everywhere").unwrap();
+            ids.push(handler.trash(&dir).unwrap().unwrap());
+        }
+        assert_eq!(objects(), 2);
+        assert!(tmp.path().join("files").join(&ids[0]).is_file());
+        assert!(
+            handler
+                .verify_integrity(None)
+                .unwrap()
+                .iter()
+                .all(|(_, s)| *s == Integrity::Intact)
+        );
+
+        // Blobs still referenced by the other entry survive a restore
+        handler.restore_to(&ids[0], &dir).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("pkg/index.js")).unwrap(),
+            "module.exports = 42;"
+        );
+        assert_eq!(objects(), 2);
+
+        // Editing the restored copy doesn't touch the store
+        fs::write(dir.join("pkg/index.js"), "edited").unwrap();
+        let other = source_dir.path().join("other");
+        handler.restore_to(&ids[1], &other).unwrap();
+        assert_eq!(
+            fs::read_to_string(other.join("pkg/index.js")).unwrap(),
+            "module.exports = 42;"
+        );
+        assert_eq!(objects(), 0);
+    }
+
+    #[test]
+    fn test_small_entries_are_renamed() {
+        let (tmp, handler) = setup();
+        let source_dir = TempDir::new().unwrap();
+        let file_path = source_dir.path().join("small.log");
+        fs::write(&file_path, "x").unwrap();
+        let id = handler.trash(&file_path).unwrap().unwrap();
+
+        let info = fs::read_to_string(
+            tmp.path()
+                .join("info")
+                .join(format!("{}.trashinfo", id.to_str().unwrap())),
+        )
+        .unwrap();
+        assert!(!info.contains("Compression="));
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("files").join(&id)).unwrap(),
+            "x"
+        );
+    }
+
+    #[test]
+    fn test_reads_home_trash_items() {
+        let (tmp, mut handler) = setup();
+        let home_dir = TempDir::new().unwrap();
+        let home = home_dir.path().join("Trash");
+        handler.home = Some(HomeTrash::at(home.clone()));
+        fs::create_dir_all(home.join("info")).unwrap();
+        fs::create_dir_all(home.join("files/build")).unwrap();
+        fs::write(home.join("files/build/out.o"), "object").unwrap();
+        fs::write(home.join("files/old notes.txt"), "notes").unwrap();
+        let dest = TempDir::new().unwrap();
+        let notes = dest.path().join("old notes.txt");
+        fs::write(
+            home.join("info/old notes.txt.trashinfo"),
+            format!(
+                "[Trash Info]\nPath={}\nDeletionDate=2019-03-04T05:06:07\n",
+                encode_uri_path(&notes)
+            ),
+        )
+        .unwrap();
+        fs::write(
+            home.join("info/build.trashinfo"),
+            "[Trash Info]\nPath=/srv/build\nDeletionDate=2019-03-04T05:06:07\n",
+        )
+        .unwrap();
+        // A trashinfo without content is not listed
+        fs::write(
+            home.join("info/gone.trashinfo"),
+            "[Trash Info]\nPath=/srv/gone\n",
+        )
+        .unwrap();
+
+        let source = TempDir::new().unwrap();
+        let own = source.path().join("own.txt");
+        fs::write(&own, "own").unwrap();
+        handler.trash(&own).unwrap();
+
+        let items = handler.list_restorable(None, None).unwrap();
+        assert_eq!(items.len(), 3);
+        let xdg: Vec<_> = items.iter().filter(|i| i.backend == XDG_LABEL).collect();
+        assert_eq!(xdg.len(), 2);
+        let build = xdg.iter().find(|i| i.display_name == "build").unwrap();
+        assert_eq!(build.size, Some(6));
+        assert!(handler.contains(&build.id));
+
+        // Pinning is saferm's own; retention leaves other tools' items alone
+        assert!(handler.set_pinned(&build.id, true).is_err());
+        handler.retention = Retention {
+            max_age: Some(86400),
+            ..Retention::default()
+        };
+        assert!(handler.evict().unwrap().is_empty());
+
+        let note_item = handler.list_restorable(Some("notes"), None).unwrap();
+        assert_eq!(note_item.len(), 1);
+        handler.restore_to(&note_item[0].id, &notes).unwrap();
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "notes");
+        assert!(!home.join("info/old notes.txt.trashinfo").exists());
+
+        handler.purge(&build.id, None).unwrap();
+        assert!(!home.join("files/build").exists());
+        assert!(!home.join("info/build.trashinfo").exists());
+        let left = handler.list_restorable(None, None).unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].backend, "managed");
+        assert!(tmp.path().join("info").is_dir());
+    }
+
+    #[test]
+    fn test_fsck_clean_trash() {
+        let (_tmp, handler) = setup();
+        let source_dir = TempDir::new().unwrap();
+        let file_path = source_dir.path().join("fine.txt");
+        fs::write(&file_path, "ok").unwrap();
+        handler.trash(&file_path).unwrap();
+
+        assert!(handler.fsck().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_fsck_detects_and_repairs_problems() {
+        let (tmp, handler) = setup();
+        handler.ensure_dirs().unwrap();
+        let files = tmp.path().join("files");
+        let info = tmp.path().join("info");
+
+        fs::write(files.join("orphan.txt"), "lost").unwrap();
+        fs::write(
+            info.join("gone.txt.trashinfo"),
+            "[Trash Info]\nPath=/tmp/gone.txt\n",
+        )
+        .unwrap();
+        fs::write(files.join("broken.txt"), "data").unwrap();
+        fs::write(info.join("broken.txt.trashinfo"), "garbage").unwrap();
+
+        let problems = handler.fsck().unwrap();
+        assert_eq!(problems.len(), 3);
+        assert!(problems.contains(&TrashProblem::Orphan {
+            path: files.join("orphan.txt")
+        }));
+        assert!(problems.contains(&TrashProblem::DanglingInfo {
+            path: info.join("gone.txt.trashinfo")
+        }));
+        assert!(problems.iter().any(|p| matches!(
+            p,
+            TrashProblem::BrokenInfo { path, .. } if path == &info.join("broken.txt.trashinfo")
+        )));
+
+        for problem in &problems {
+            handler.repair(problem).unwrap();
+        }
+        assert!(handler.fsck().unwrap().is_empty());
+
+        // The orphan is restorable again under the recovered path
+        let items = handler.list_restorable(None, None).unwrap();
+        assert_eq!(items.len(), 1);
+        assert!(
+            items[0]
+                .original_path
+                .ends_with("saferm-recovered/orphan.txt")
+        );
+
+        // The broken entry was quarantined, not deleted
+        assert!(tmp.path().join("quarantine/files/broken.txt").exists());
+        assert!(
+            tmp.path()
+                .join("quarantine/info/broken.txt.trashinfo")
+                .exists()
+        );
+        assert!(!info.join("gone.txt.trashinfo").exists());
+    }
+
+    #[test]
+    fn test_copy_across_copies_tree_and_reports_bytes() {
+        let tmp = TempDir::new().unwrap();
+        let source = tmp.path().join("src");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("a.txt"), "hello").unwrap();
+        fs::write(source.join("sub/b.txt"), "world!").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("a.txt", source.join("link")).unwrap();
+        let dest = tmp.path().join("dest");
+
+        let mut reports = Vec::new();
+        copy_across(&source, &dest, &mut |copied, total| {
+            reports.push((copied, total))
+        })
+        .unwrap();
+
+        assert!(source.exists());
+        assert_eq!(fs::read_to_string(dest.join("a.txt")).unwrap(), "hello");
+        assert_eq!(
+            fs::read_to_string(dest.join("sub/b.txt")).unwrap(),
+            "world!"
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            fs::read_link(dest.join("link")).unwrap(),
+            PathBuf::from("a.txt")
+        );
+        let total = if cfg!(unix) { 16 } else { 11 };
+        assert!(reports.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(reports.last(), Some(&(total, total)));
+    }
+
+    #[test]
+    fn test_move_across_moves_tree() {
+        let tmp = TempDir::new().unwrap();
+        let source = tmp.path().join("src");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("a.txt"), "hello").unwrap();
+        let dest = tmp.path().join("dest");
+
+        move_across(&source, &dest).unwrap();
+
+        assert!(!source.exists());
+        assert_eq!(fs::read_to_string(dest.join("a.txt")).unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_copy_across_failure_leaves_no_partial_copy() {
+        let tmp = TempDir::new().unwrap();
+        let source = tmp.path().join("src");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("a.txt"), "hello").unwrap();
+        let dest = tmp.path().join("dest");
+        // The copy fails on the existing file and leaves no partial tree
+        fs::create_dir(&dest).unwrap();
+
+        assert!(copy_across(&source, &dest, &mut |_, _| {}).is_err());
+        assert!(source.join("a.txt").exists());
+        assert!(!dest.exists());
+    }
 }
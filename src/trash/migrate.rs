@@ -0,0 +1,255 @@
+use anyhow::{Context, Result};
+use std::ffi::OsStr;
+use std::path::Path;
+
+use super::freedesktop::HomeTrash;
+use super::managed::{ManagedTrash, remove_tree};
+use super::os_trash::OsTrash;
+use super::{RestorableItem, TrashHandler};
+use crate::error::Error;
+
+/// A backend entries can be migrated between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Managed,
+    Os,
+}
+
+impl Side {
+    pub fn name(self) -> &'static str {
+        match self {
+            Side::Managed => "managed",
+            Side::Os => "os",
+        }
+    }
+}
+
+/// Moves entries between the managed trash and the FreeDesktop home trash,
+/// keeping their original paths, deletion dates, reasons and tags.
+///
+/// Each entry moves on its own: the new metadata is written first, then the
+/// content is moved, then the old metadata is dropped. Running the migration
+/// again after an interruption picks up the entries that are left.
+pub struct Migrator {
+    managed: ManagedTrash,
+    home: HomeTrash,
+}
+
+impl Migrator {
+    /// A migrator for the managed trash bin `bin` (or the default bin) and
+    /// the user's OS trash.
+    pub fn new(bin: Option<&str>) -> crate::Result<Self> {
+        let home = HomeTrash::new()
+            .ok_or_else(|| Error::msg("could not determine the home trash directory"))?;
+        Ok(Self {
+            managed: ManagedTrash::for_bin(bin),
+            home,
+        })
+    }
+
+    #[cfg(test)]
+    fn with(managed: ManagedTrash, home: HomeTrash) -> Self {
+        Self { managed, home }
+    }
+
+    /// The entries of `from`, optionally filtered by a pattern.
+    pub fn pending(&self, from: Side, filter: Option<&str>) -> crate::Result<Vec<RestorableItem>> {
+        match from {
+            Side::Managed => self.managed.list_restorable(filter, None),
+            Side::Os => Ok(self.os_items(filter)?),
+        }
+    }
+
+    /// Move one entry of the other backend to `to`.
+    pub fn migrate(&self, item: &RestorableItem, to: Side) -> crate::Result<()> {
+        match to {
+            Side::Os => Ok(self.to_os(item)?),
+            Side::Managed => Ok(self.to_managed(item)?),
+        }
+    }
+
+    /// Items of the home trash, with the OS backend's ids (their info files).
+    /// Items whose content is already gone are listed too, so an interrupted
+    /// migration can drop their metadata.
+    fn os_items(&self, filter: Option<&str>) -> Result<Vec<RestorableItem>> {
+        let items = self
+            .home
+            .entries()?
+            .into_iter()
+            .filter(|entry| {
+                filter.is_none_or(|pat| entry.original_path.to_string_lossy().contains(pat))
+            })
+            .map(|entry| {
+                let id = entry.info_path.into_os_string();
+                let size = std::fs::symlink_metadata(self.home.content_path(&entry.name))
+                    .ok()
+                    .filter(|meta| meta.is_file())
+                    .map(|meta| meta.len());
+                RestorableItem {
+                    annotation: OsTrash::read_note(&id),
+                    id,
+                    display_name: entry
+                        .original_path
+                        .file_name()
+                        .unwrap_or(&entry.name)
+                        .to_os_string(),
+                    original_path: entry.original_path,
+                    deleted_at: entry.deleted_at,
+                    size,
+                    pinned: false,
+                    backend: "os",
+                }
+            })
+            .collect();
+        Ok(items)
+    }
+
+    fn to_os(&self, item: &RestorableItem) -> Result<()> {
+        // Managed ids are unique ULIDs, so a retry reuses the same name
+        let name = item.id.to_string_lossy();
+        let deleted_at = item
+            .deleted_at
+            .unwrap_or_else(|| chrono::Local::now().timestamp());
+        // Whatever is there is left from an interrupted attempt
+        let content = self.home.content_path(OsStr::new(&*name));
+        if content.symlink_metadata().is_ok() {
+            remove_tree(&content).with_context(|| format!("failed to remove: {:?}", content))?;
+        }
+        self.home.forget(OsStr::new(&*name))?;
+        let entry = self.home.reserve(&name, &item.original_path, deleted_at)?;
+        if let Err(e) = self.managed.restore_to(&item.id, &content) {
+            let _ = self.home.forget(&entry.name);
+            return Err(e.into());
+        }
+        let id = entry.info_path.into_os_string();
+        OsTrash::annotate(&id, &item.annotation)
+            .with_context(|| format!("failed to record reason and tags for {:?}", id))
+    }
+
+    fn to_managed(&self, item: &RestorableItem) -> Result<()> {
+        let name = Path::new(&item.id)
+            .file_stem()
+            .ok_or_else(|| anyhow::anyhow!("not a trash item: {:?}", item.id))?;
+        let content = self.home.content_path(name);
+        if content.symlink_metadata().is_ok() {
+            let deleted_at = item
+                .deleted_at
+                .unwrap_or_else(|| chrono::Local::now().timestamp());
+            self.managed
+                .adopt(&content, &item.original_path, deleted_at, &item.annotation)?;
+        }
+        self.home.forget(name)?;
+        OsTrash::remove_note(&item.id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    use crate::trash::Annotation;
+
+    #[test]
+    fn test_roundtrip_keeps_path_and_date() {
+        let managed_dir = TempDir::new().unwrap();
+        let home_dir = TempDir::new().unwrap();
+        let source = TempDir::new().unwrap();
+        let managed = ManagedTrash::at(managed_dir.path().to_path_buf(), "managed");
+        let migrator = Migrator::with(
+            ManagedTrash::at(managed_dir.path().to_path_buf(), "managed"),
+            HomeTrash::at(home_dir.path().join("Trash")),
+        );
+
+        let file = source.path().join("report.txt");
+        fs::write(&file, "q3").unwrap();
+        let annotation = Annotation {
+            reason: None,
+            tags: vec!["old".to_string()],
+        };
+        managed.trash_annotated(&file, &annotation).unwrap();
+        let before = migrator.pending(Side::Managed, None).unwrap();
+        assert_eq!(before.len(), 1);
+
+        migrator.migrate(&before[0], Side::Os).unwrap();
+        assert!(migrator.pending(Side::Managed, None).unwrap().is_empty());
+        let in_os = migrator.pending(Side::Os, Some("report")).unwrap();
+        assert_eq!(in_os.len(), 1);
+        assert_eq!(in_os[0].original_path, before[0].original_path);
+        assert_eq!(in_os[0].deleted_at, before[0].deleted_at);
+        assert_eq!(
+            fs::read_to_string(home_dir.path().join("Trash/files").join(&before[0].id)).unwrap(),
+            "q3"
+        );
+
+        migrator.migrate(&in_os[0], Side::Managed).unwrap();
+        assert!(migrator.pending(Side::Os, None).unwrap().is_empty());
+        let back = migrator.pending(Side::Managed, None).unwrap();
+        assert_eq!(back.len(), 1);
+        assert_eq!(back[0].original_path, before[0].original_path);
+        assert_eq!(back[0].deleted_at, before[0].deleted_at);
+        assert_eq!(back[0].annotation, annotation);
+    }
+
+    #[test]
+    fn test_resumes_after_interruption() {
+        let managed_dir = TempDir::new().unwrap();
+        let home_dir = TempDir::new().unwrap();
+        let home = HomeTrash::at(home_dir.path().join("Trash"));
+        let migrator = Migrator::with(
+            ManagedTrash::at(managed_dir.path().to_path_buf(), "managed"),
+            HomeTrash::at(home_dir.path().join("Trash")),
+        );
+
+        // Interrupted after the content moved but before the info was dropped
+        let entry = home
+            .reserve("gone.txt", Path::new("/tmp/gone.txt"), 0)
+            .unwrap();
+        // Interrupted before anything moved
+        home.reserve("kept.txt", Path::new("/tmp/kept.txt"), 0)
+            .unwrap();
+        fs::write(home.content_path("kept.txt".as_ref()), "kept").unwrap();
+
+        for item in migrator.pending(Side::Os, None).unwrap() {
+            migrator.migrate(&item, Side::Managed).unwrap();
+        }
+        assert!(home.entries().unwrap().is_empty());
+        assert!(!entry.info_path.exists());
+        let managed = migrator.pending(Side::Managed, None).unwrap();
+        assert_eq!(managed.len(), 1);
+        assert_eq!(managed[0].original_path, Path::new("/tmp/kept.txt"));
+    }
+
+    #[test]
+    fn test_to_os_resumes_after_interruption() {
+        let managed_dir = TempDir::new().unwrap();
+        let home_dir = TempDir::new().unwrap();
+        let source = TempDir::new().unwrap();
+        let home = HomeTrash::at(home_dir.path().join("Trash"));
+        let migrator = Migrator::with(
+            ManagedTrash::at(managed_dir.path().to_path_buf(), "managed"),
+            HomeTrash::at(home_dir.path().join("Trash")),
+        );
+
+        let file = source.path().join("report.txt");
+        fs::write(&file, "q3").unwrap();
+        migrator.managed.trash(&file).unwrap();
+        let item = migrator.pending(Side::Managed, None).unwrap().remove(0);
+
+        // Interrupted mid-copy: the info is reserved and a partial copy exists
+        let name = item.id.to_string_lossy();
+        home.reserve(&name, Path::new("/stale"), 0).unwrap();
+        fs::write(home.content_path(OsStr::new(&*name)), "q").unwrap();
+
+        migrator.migrate(&item, Side::Os).unwrap();
+        let in_os = migrator.pending(Side::Os, None).unwrap();
+        assert_eq!(in_os.len(), 1);
+        assert_eq!(in_os[0].original_path, item.original_path);
+        assert_eq!(
+            fs::read_to_string(home.content_path(OsStr::new(&*name))).unwrap(),
+            "q3"
+        );
+    }
+}
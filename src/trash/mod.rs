@@ -1,13 +1,32 @@
+pub mod audited;
+pub mod checksum;
+mod compress;
+mod crypto;
+mod dedup;
+pub(crate) mod encoding;
+pub mod exec;
+mod freedesktop;
+mod index;
 pub mod managed;
+#[cfg(all(unix, not(target_os = "macos")))]
+pub mod migrate;
 pub mod os_trash;
+pub mod project;
+pub mod retention;
+pub mod routing;
+pub mod shred;
 
-use anyhow::Result;
+use crate::error::{Error, Result};
+use crate::observer::Observer;
 use std::ffi::{OsStr, OsString};
 use std::path::{Path, PathBuf};
 
+use routing::Store;
+
 /// An item in the trash that can be restored.
+#[derive(Debug, Clone)]
 pub struct RestorableItem {
-    /// Backend-opaque stable key (e.g., trash filename for managed, OsString id for os_limited)
+    /// Backend-opaque stable key (e.g., ULID storage name for managed, OsString id for os_limited)
     pub id: OsString,
     /// The original path before the file was trashed
     pub original_path: PathBuf,
@@ -15,42 +34,407 @@ pub struct RestorableItem {
     pub display_name: OsString,
     /// Deletion timestamp as unix seconds (None if unknown)
     pub deleted_at: Option<i64>,
+    /// Size in bytes (total of all files for directories; None if unknown)
+    pub size: Option<u64>,
+    /// Kept by cleanup unless explicitly included (see [`TrashHandler::set_pinned`])
+    pub pinned: bool,
+    /// Why and under which tags the item was trashed
+    pub annotation: Annotation,
+    /// Name of the backend holding the item, such as `os` or `managed`
+    pub backend: &'static str,
+}
+
+/// A free-text reason and tags recorded with a trashed item.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Annotation {
+    pub reason: Option<String>,
+    pub tags: Vec<String>,
 }
 
-pub trait TrashHandler {
-    fn trash(&self, path: &Path) -> Result<()>;
-    fn cleanup(&self, prompter: &dyn crate::prompt::Prompter) -> Result<()>;
+impl Annotation {
+    pub fn is_empty(&self) -> bool {
+        self.reason.is_none() && self.tags.is_empty()
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// True if no tag is required or the item carries it.
+    pub fn matches(&self, tag: Option<&str>) -> bool {
+        tag.is_none_or(|tag| self.has_tag(tag))
+    }
+
+    /// `Reason=` and `Tags=` lines for a trashinfo-style metadata file.
+    pub(crate) fn to_info_lines(&self) -> String {
+        let mut lines = String::new();
+        if let Some(reason) = &self.reason {
+            lines.push_str(&format!("Reason={}\n", encoding::escape_field(reason)));
+        }
+        if !self.tags.is_empty() {
+            lines.push_str(&format!("Tags={}\n", self.tags.join(",")));
+        }
+        lines
+    }
+
+    /// Take a line written by [`Annotation::to_info_lines`]; false if it is some other key.
+    pub(crate) fn read_info_line(&mut self, line: &str) -> bool {
+        if let Some(reason) = line.strip_prefix("Reason=") {
+            self.reason = Some(encoding::unescape_field(reason));
+        } else if let Some(tags) = line.strip_prefix("Tags=") {
+            self.tags = tags
+                .split(',')
+                .filter(|t| !t.is_empty())
+                .map(str::to_string)
+                .collect();
+        } else {
+            return false;
+        }
+        true
+    }
+}
+
+/// Check a tag given on the command line: tags are stored comma-separated,
+/// so they cannot contain commas, whitespace or control characters.
+pub fn validate_tag(tag: &str) -> Result<()> {
+    if tag.is_empty()
+        || tag
+            .chars()
+            .any(|c| c == ',' || c.is_whitespace() || c.is_control())
+    {
+        return Err(Error::InvalidTag {
+            tag: tag.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Sort order for [`TrashHandler::list_page`]; each key has its natural direction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    /// Newest first
+    #[default]
+    Date,
+    /// Original path, A to Z
+    Path,
+    /// Largest first (unknown sizes last)
+    Size,
+}
+
+/// A sorted, filtered and paginated listing request.
+#[derive(Debug, Clone, Default)]
+pub struct ListQuery<'a> {
+    pub filter: Option<&'a str>,
+    /// Only items carrying this tag
+    pub tag: Option<&'a str>,
+    pub sort: SortKey,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+/// One page of a listing plus totals over every matching item.
+pub struct ListPage {
+    pub items: Vec<RestorableItem>,
+    /// Number of matching items before pagination
+    pub total: usize,
+    /// Sum of the known sizes of all matching items
+    pub total_size: u64,
+}
+
+impl ListQuery<'_> {
+    /// Sort and paginate already-filtered items.
+    pub fn paginate(&self, mut items: Vec<RestorableItem>) -> ListPage {
+        match self.sort {
+            SortKey::Date => {
+                items.sort_by(|a, b| (b.deleted_at, &b.id).cmp(&(a.deleted_at, &a.id)))
+            }
+            SortKey::Path => items.sort_by(|a, b| a.original_path.cmp(&b.original_path)),
+            SortKey::Size => items.sort_by_key(|item| std::cmp::Reverse(item.size)),
+        }
+
+        let total = items.len();
+        let total_size = items.iter().filter_map(|item| item.size).sum();
+        let items = items
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect();
+        ListPage {
+            items,
+            total,
+            total_size,
+        }
+    }
+}
+
+/// Outcome of checking a trashed item against the content hash recorded at trash time.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Integrity {
+    Intact,
+    Corrupted,
+    /// No hash was recorded (older entries, or a backend that doesn't store hashes).
+    Unverified,
+    /// The item could not be read to check it (permissions, a missing key).
+    Unreadable {
+        reason: String,
+    },
+}
+
+/// An inconsistency between trashed content and its metadata, found by [`TrashHandler::fsck`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrashProblem {
+    /// Content in the trash that has no metadata.
+    Orphan { path: PathBuf },
+    /// Metadata whose content is gone.
+    DanglingInfo { path: PathBuf },
+    /// Metadata that cannot be parsed.
+    BrokenInfo { path: PathBuf, reason: String },
+    /// An entry saferm is not allowed to read.
+    Permission { path: PathBuf, reason: String },
+}
+
+impl TrashProblem {
+    pub fn path(&self) -> &Path {
+        match self {
+            TrashProblem::Orphan { path }
+            | TrashProblem::DanglingInfo { path }
+            | TrashProblem::BrokenInfo { path, .. }
+            | TrashProblem::Permission { path, .. } => path,
+        }
+    }
+
+    /// Permission problems need a human; everything else can be fixed by [`TrashHandler::repair`].
+    pub fn is_repairable(&self) -> bool {
+        !matches!(self, TrashProblem::Permission { .. })
+    }
+}
+
+/// The backend a target is sent to, and why if it isn't the default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub backend: &'static str,
+    pub reason: Option<String>,
+}
+
+impl Route {
+    pub fn new(backend: &'static str, reason: Option<String>) -> Self {
+        Self { backend, reason }
+    }
+}
+
+pub trait TrashHandler: Send + Sync {
+    /// Move `path` to the trash and return the id of the new entry, if the backend can tell.
+    /// Symlinks are removed directly and have no id.
+    fn trash(&self, path: &Path) -> Result<Option<OsString>>;
+
+    /// Move `path` to the trash like [`TrashHandler::trash`], recording a reason and tags.
+    fn trash_annotated(&self, path: &Path, annotation: &Annotation) -> Result<Option<OsString>> {
+        if !annotation.is_empty() {
+            return Err(Error::Unsupported {
+                operation: "--reason/--tag",
+                backend: self.backend_name(),
+            });
+        }
+        self.trash(path)
+    }
+
+    /// Move `path` to the trash like [`TrashHandler::trash_annotated`],
+    /// reporting progress, such as bytes copied to another filesystem, to `observer`.
+    fn trash_observed(
+        &self,
+        path: &Path,
+        annotation: &Annotation,
+        _observer: &dyn Observer,
+    ) -> Result<Option<OsString>> {
+        self.trash_annotated(path, annotation)
+    }
+
+    /// Empty the trash. Pinned entries are kept unless `include_pinned` is set.
+    fn cleanup(&self, prompter: &dyn crate::prompt::Prompter, include_pinned: bool) -> Result<()>;
+
+    /// Empty the trash like [`TrashHandler::cleanup`], overwriting file contents before unlinking them.
+    fn cleanup_shred(
+        &self,
+        _prompter: &dyn crate::prompt::Prompter,
+        _shredder: &shred::Shredder,
+        _include_pinned: bool,
+    ) -> Result<()> {
+        Err(Error::Unsupported {
+            operation: "--shred",
+            backend: self.backend_name(),
+        })
+    }
+
+    /// Empty the trash like [`TrashHandler::cleanup`], or like
+    /// [`TrashHandler::cleanup_shred`] when a `shredder` is given, reporting
+    /// the outcome and warnings to `observer`.
+    fn cleanup_observed(
+        &self,
+        prompter: &dyn crate::prompt::Prompter,
+        shredder: Option<&shred::Shredder>,
+        include_pinned: bool,
+        _observer: &dyn Observer,
+    ) -> Result<()> {
+        match shredder {
+            Some(shredder) => self.cleanup_shred(prompter, shredder, include_pinned),
+            None => self.cleanup(prompter, include_pinned),
+        }
+    }
+
     fn backend_name(&self) -> &'static str;
 
-    /// List items in the trash that can be restored, optionally filtered by a substring pattern.
-    fn list_restorable(&self, filter: Option<&str>) -> Result<Vec<RestorableItem>>;
+    /// Where [`TrashHandler::trash`] would send `path`.
+    fn route(&self, _path: &Path) -> Route {
+        Route::new(self.backend_name(), None)
+    }
+
+    /// List items in the trash that can be restored, optionally filtered by a
+    /// substring pattern and a tag.
+    fn list_restorable(
+        &self,
+        filter: Option<&str>,
+        tag: Option<&str>,
+    ) -> Result<Vec<RestorableItem>>;
+
+    /// List one page of restorable items, sorted, with totals over all matches.
+    fn list_page(&self, query: &ListQuery) -> Result<ListPage> {
+        Ok(query.paginate(self.list_restorable(query.filter, query.tag)?))
+    }
 
     /// Restore a trashed item (identified by `item_id`) to the given `destination` path.
     fn restore_to(&self, item_id: &OsStr, destination: &Path) -> Result<()>;
+
+    /// Restore like [`TrashHandler::restore_to`], reporting warnings to `observer`.
+    fn restore_observed(
+        &self,
+        item_id: &OsStr,
+        destination: &Path,
+        _observer: &dyn Observer,
+    ) -> Result<()> {
+        self.restore_to(item_id, destination)
+    }
+
+    /// Permanently delete one trashed item, content and metadata, shredding
+    /// its content first when a `shredder` is given.
+    fn purge(&self, _item_id: &OsStr, _shredder: Option<&shred::Shredder>) -> Result<()> {
+        Err(Error::Unsupported {
+            operation: "--purge",
+            backend: self.backend_name(),
+        })
+    }
+
+    /// Purge like [`TrashHandler::purge`], reporting warnings to `observer`.
+    fn purge_observed(
+        &self,
+        item_id: &OsStr,
+        shredder: Option<&shred::Shredder>,
+        _observer: &dyn Observer,
+    ) -> Result<()> {
+        self.purge(item_id, shredder)
+    }
+
+    /// Permanently delete entries past the backend's age or size limits and
+    /// return them. Pinned entries are never evicted.
+    fn evict(&self) -> Result<Vec<RestorableItem>> {
+        Ok(vec![])
+    }
+
+    /// Evict like [`TrashHandler::evict`], reporting warnings to `observer`.
+    fn evict_observed(&self, _observer: &dyn Observer) -> Result<Vec<RestorableItem>> {
+        self.evict()
+    }
+
+    /// Pin or unpin a trashed item; pinned items survive cleanup.
+    fn set_pinned(&self, _item_id: &OsStr, pinned: bool) -> Result<()> {
+        Err(Error::Unsupported {
+            operation: if pinned { "--pin" } else { "--unpin" },
+            backend: self.backend_name(),
+        })
+    }
+
+    /// Recompute content hashes of restorable items and compare them with the stored ones.
+    fn verify_integrity(&self, filter: Option<&str>) -> Result<Vec<(RestorableItem, Integrity)>> {
+        Ok(self
+            .list_restorable(filter, None)?
+            .into_iter()
+            .map(|item| (item, Integrity::Unverified))
+            .collect())
+    }
+
+    /// Scan the trash for inconsistencies between content and metadata.
+    fn fsck(&self) -> Result<Vec<TrashProblem>> {
+        Err(Error::Unsupported {
+            operation: "--fsck",
+            backend: self.backend_name(),
+        })
+    }
+
+    /// Fix a problem reported by [`TrashHandler::fsck`].
+    fn repair(&self, _problem: &TrashProblem) -> Result<()> {
+        Err(Error::Unsupported {
+            operation: "--fsck",
+            backend: self.backend_name(),
+        })
+    }
 }
 
-pub fn create_handler() -> Box<dyn TrashHandler> {
+/// Create the configured backend, wrapped in the audit log if one is set up.
+/// Named bins (`bin`) only exist in the managed backend, so they always use it;
+/// otherwise each target is routed on its own (see [`routing::RoutedTrash`]).
+pub fn create_handler(bin: Option<&str>) -> Box<dyn TrashHandler> {
+    let handler: Box<dyn TrashHandler> = match bin {
+        Some(name) => Box::new(managed::ManagedTrash::for_bin(Some(name))),
+        None => {
+            let routed = routing::RoutedTrash::new(
+                select_backend(),
+                project::Discovery::from_env(),
+                routing::rules_from_env(),
+            );
+            // A backend chosen explicitly is the only one used
+            if std::env::var_os("SAFERM_TRASH_BACKEND").is_some() {
+                Box::new(routed)
+            } else {
+                Box::new(routed.with_available_backends())
+            }
+        }
+    };
+    match crate::audit::AuditLog::from_env() {
+        Ok(Some(log)) => Box::new(audited::AuditedTrash::new(handler, log)),
+        Ok(None) => handler,
+        Err(e) => {
+            eprintln!("saferm: warning: audit log disabled: {:#}", e);
+            handler
+        }
+    }
+}
+
+fn select_backend() -> Store {
     if let Ok(backend) = std::env::var("SAFERM_TRASH_BACKEND") {
         return match backend.as_str() {
-            "os" => Box::new(os_trash::OsTrash),
-            "managed" => Box::new(managed::ManagedTrash::new()),
-            other => {
-                eprintln!(
-                    "saferm: unknown SAFERM_TRASH_BACKEND '{}', using default",
-                    other
-                );
-                default_handler()
-            }
+            "os" => Store::Other(Box::new(os_trash::OsTrash)),
+            "managed" => Store::Managed(managed::ManagedTrash::new()),
+            other => match other.strip_prefix("exec:") {
+                Some(helper) if !helper.is_empty() => {
+                    Store::Other(Box::new(exec::ExecTrash::new(PathBuf::from(helper))))
+                }
+                _ => {
+                    eprintln!(
+                        "saferm: unknown SAFERM_TRASH_BACKEND '{}', using default",
+                        other
+                    );
+                    default_handler()
+                }
+            },
         };
     }
     default_handler()
 }
 
-fn default_handler() -> Box<dyn TrashHandler> {
+fn default_handler() -> Store {
     if should_use_os_trash() {
-        Box::new(os_trash::OsTrash)
+        Store::Other(Box::new(os_trash::OsTrash))
     } else {
-        Box::new(managed::ManagedTrash::new())
+        Store::Managed(managed::ManagedTrash::new())
     }
 }
 
@@ -69,3 +453,87 @@ fn should_use_os_trash() -> bool {
     // Default to managed trash on unknown platforms
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, path: &str, deleted_at: i64, size: Option<u64>) -> RestorableItem {
+        RestorableItem {
+            id: OsString::from(id),
+            original_path: PathBuf::from(path),
+            display_name: OsString::from(path),
+            deleted_at: Some(deleted_at),
+            size,
+            pinned: false,
+            annotation: Annotation::default(),
+            backend: "managed",
+        }
+    }
+
+    fn items() -> Vec<RestorableItem> {
+        vec![
+            item("a", "/b.txt", 100, Some(10)),
+            item("b", "/a.txt", 300, None),
+            item("c", "/c.txt", 200, Some(30)),
+        ]
+    }
+
+    fn ids(page: &ListPage) -> Vec<&str> {
+        page.items.iter().map(|i| i.id.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_list_query_sorts_and_paginates() {
+        let by_date = ListQuery::default().paginate(items());
+        assert_eq!(ids(&by_date), ["b", "c", "a"]);
+        assert_eq!(by_date.total, 3);
+        assert_eq!(by_date.total_size, 40);
+
+        let by_path = ListQuery {
+            sort: SortKey::Path,
+            ..Default::default()
+        };
+        assert_eq!(ids(&by_path.paginate(items())), ["b", "a", "c"]);
+
+        let by_size = ListQuery {
+            sort: SortKey::Size,
+            offset: 1,
+            limit: Some(1),
+            ..Default::default()
+        };
+        let page = by_size.paginate(items());
+        assert_eq!(ids(&page), ["a"]);
+        assert_eq!(page.total, 3);
+    }
+
+    #[test]
+    fn test_annotation_info_lines_roundtrip() {
+        let annotation = Annotation {
+            reason: Some("replaced by v2\nschema (100%)".to_string()),
+            tags: vec!["migration".to_string(), "db".to_string()],
+        };
+        let mut parsed = Annotation::default();
+        for line in annotation.to_info_lines().lines() {
+            assert!(parsed.read_info_line(line));
+        }
+        assert_eq!(parsed, annotation);
+        assert!(!parsed.read_info_line("Path=/tmp/x"));
+        assert!(parsed.matches(Some("db")));
+        assert!(!parsed.matches(Some("d")));
+        assert!(Annotation::default().to_info_lines().is_empty());
+    }
+
+    #[test]
+    fn test_validate_tag() {
+        assert!(validate_tag("migration").is_ok());
+        assert!(validate_tag("v2-schema").is_ok());
+        for bad in ["", "a,b", "two words", "tab\t"] {
+            assert!(
+                matches!(validate_tag(bad), Err(Error::InvalidTag { tag }) if tag == bad),
+                "{:?}",
+                bad
+            );
+        }
+    }
+}
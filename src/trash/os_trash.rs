@@ -2,17 +2,34 @@ use anyhow::{Context, Result};
 use rust_i18n::t;
 #[cfg(target_os = "macos")]
 use std::collections::HashSet;
-use std::ffi::OsStr;
-#[cfg(target_os = "macos")]
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use super::{RestorableItem, TrashHandler};
+#[cfg(all(unix, not(target_os = "macos")))]
+use super::freedesktop::HomeTrash;
+#[cfg(all(unix, not(target_os = "macos")))]
+use super::shred::Shredder;
+use super::{Annotation, RestorableItem, TrashHandler};
+use crate::error::Error;
 use crate::prompt::Prompter;
 
 pub struct OsTrash;
 
+impl OsTrash {
+    /// Whether this system has an OS trash to list, even if saferm doesn't
+    /// trash into it by default (a FreeDesktop trash used from an SSH session).
+    pub fn is_available() -> bool {
+        if cfg!(target_os = "macos") {
+            return true;
+        }
+        if cfg!(all(unix, not(target_os = "macos"))) {
+            return dirs::data_dir().is_some_and(|dir| dir.join("Trash").join("info").is_dir());
+        }
+        false
+    }
+}
+
 // macOS-specific helper methods for restore metadata tracking
 #[cfg(target_os = "macos")]
 impl OsTrash {
@@ -49,21 +66,26 @@ impl OsTrash {
             .collect()
     }
 
-    /// Write restore metadata after a successful trash operation
-    fn write_restore_meta(trash_name: &OsStr, original_path: &Path) -> Result<()> {
+    /// Write restore metadata after a successful trash operation and return its id
+    fn write_restore_meta(
+        trash_name: &OsStr,
+        original_path: &Path,
+        annotation: &Annotation,
+    ) -> Result<OsString> {
         Self::ensure_info_dir()?;
         let id = uuid_v4();
         let info_path = Self::info_dir().join(format!("{}.trashinfo", id));
         let trash_path = Self::trash_dir().join(trash_name);
         let now = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S").to_string();
         let content = format!(
-            "[Trash Info]\nPath={}\nTrashPath={}\nDeletionDate={}\n",
+            "[Trash Info]\nPath={}\nTrashPath={}\nDeletionDate={}\n{}",
             original_path.display(),
             trash_path.display(),
             now,
+            annotation.to_info_lines(),
         );
         fs::write(&info_path, content)?;
-        Ok(())
+        Ok(OsString::from(id))
     }
 
     /// Parse a macOS restore metadata file
@@ -92,6 +114,75 @@ impl OsTrash {
     }
 }
 
+// Linux: reasons and tags live next to the freedesktop trash, which has no room for them
+#[cfg(all(unix, not(target_os = "macos")))]
+impl OsTrash {
+    fn notes_dir() -> PathBuf {
+        let data_dir = dirs::data_dir().unwrap_or_else(|| {
+            dirs::home_dir()
+                .map(|h| h.join(".local/share"))
+                .unwrap_or_else(|| PathBuf::from("/tmp/saferm"))
+        });
+        data_dir.join("saferm").join("os-trash-notes")
+    }
+
+    /// Note file of a trash item; item ids are paths, so they are hashed into a name.
+    fn note_path(item_id: &OsStr) -> PathBuf {
+        use sha2::{Digest, Sha256};
+        let digest = Sha256::digest(item_id.as_encoded_bytes());
+        Self::notes_dir().join(super::checksum::to_hex(&digest))
+    }
+
+    pub(crate) fn read_note(item_id: &OsStr) -> Annotation {
+        let mut annotation = Annotation::default();
+        if let Ok(content) = fs::read_to_string(Self::note_path(item_id)) {
+            for line in content.lines() {
+                annotation.read_info_line(line);
+            }
+        }
+        annotation
+    }
+
+    /// Attach `annotation` to the newest trash item that came from
+    /// `original_path`, or drop a note left under its id by an earlier item
+    /// when there is nothing to record.
+    ///
+    /// The item is looked up by name in the home trash; only an item that went
+    /// to the trash of another volume needs the full listing.
+    fn write_note(original_path: &Path, annotation: &Annotation) -> Result<()> {
+        let item_id = match HomeTrash::new().and_then(|home| home.newest_from(original_path)) {
+            Some(entry) => entry.info_path.into_os_string(),
+            None => {
+                trash::os_limited::list()?
+                    .into_iter()
+                    .filter(|item| item.original_path() == original_path)
+                    .max_by_key(|item| item.time_deleted)
+                    .ok_or_else(|| anyhow::anyhow!("trashed item not found"))?
+                    .id
+            }
+        };
+        if annotation.is_empty() {
+            Self::remove_note(&item_id);
+            return Ok(());
+        }
+        Self::annotate(&item_id, annotation)
+    }
+
+    /// Attach `annotation` to the trash item `item_id`.
+    pub(crate) fn annotate(item_id: &OsStr, annotation: &Annotation) -> Result<()> {
+        if annotation.is_empty() {
+            return Ok(());
+        }
+        fs::create_dir_all(Self::notes_dir())?;
+        fs::write(Self::note_path(item_id), annotation.to_info_lines())?;
+        Ok(())
+    }
+
+    pub(crate) fn remove_note(item_id: &OsStr) {
+        let _ = fs::remove_file(Self::note_path(item_id));
+    }
+}
+
 /// Simple UUID v4 generation without external crate
 #[cfg(target_os = "macos")]
 fn uuid_v4() -> String {
@@ -111,17 +202,26 @@ fn uuid_v4() -> String {
 }
 
 impl TrashHandler for OsTrash {
-    fn trash(&self, path: &Path) -> Result<()> {
+    fn trash(&self, path: &Path) -> crate::Result<Option<OsString>> {
+        self.trash_annotated(path, &Annotation::default())
+    }
+
+    fn trash_annotated(
+        &self,
+        path: &Path,
+        annotation: &Annotation,
+    ) -> crate::Result<Option<OsString>> {
         // Symlinks: remove directly since they are just pointers,
         // and the trash crate may fail for symlinks in certain directories.
         if path.is_symlink() {
-            return std::fs::remove_file(path).with_context(|| {
+            std::fs::remove_file(path).with_context(|| {
                 t!(
                     "error_trash_failed",
                     name = path.display().to_string(),
                     reason = "failed to remove symlink"
                 )
-            });
+            })?;
+            return Ok(None);
         }
 
         #[cfg(target_os = "macos")]
@@ -144,25 +244,42 @@ impl TrashHandler for OsTrash {
 
             // Only write metadata if we can confidently identify the new entry
             if new_entries.len() == 1 {
-                let _ = Self::write_restore_meta(new_entries[0], &original_path);
+                return Ok(
+                    Self::write_restore_meta(new_entries[0], &original_path, annotation).ok(),
+                );
             }
 
-            Ok(())
+            Ok(None)
         }
 
         #[cfg(not(target_os = "macos"))]
         {
+            let original_path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+            // The trash crate doesn't report the id of the new entry
             trash::delete(path).with_context(|| {
                 t!(
                     "error_trash_failed",
                     name = path.display().to_string(),
                     reason = "OS trash operation failed"
                 )
-            })
+            })?;
+
+            // Notes are only pruned by cleanup, so one may be waiting under the new id
+            if (!annotation.is_empty() || Self::notes_dir().exists())
+                && let Err(e) = Self::write_note(&original_path, annotation)
+            {
+                eprintln!(
+                    "saferm: warning: failed to record reason and tags for '{}': {:#}",
+                    path.display(),
+                    e
+                );
+            }
+            Ok(None)
         }
     }
 
-    fn cleanup(&self, _prompter: &dyn Prompter) -> Result<()> {
+    fn cleanup(&self, _prompter: &dyn Prompter, _include_pinned: bool) -> crate::Result<()> {
         #[cfg(target_os = "macos")]
         {
             eprintln!("{}", t!("cleanup_macos_hint"));
@@ -179,6 +296,8 @@ impl TrashHandler for OsTrash {
             })?;
 
             if items.is_empty() {
+                // Left by items emptied or restored outside saferm
+                let _ = fs::remove_dir_all(Self::notes_dir());
                 println!("{}", t!("cleanup_nothing"));
                 return Ok(());
             }
@@ -190,6 +309,7 @@ impl TrashHandler for OsTrash {
 
             trash::os_limited::purge_all(items)
                 .with_context(|| t!("error_cleanup_failed", reason = "purge failed"))?;
+            let _ = fs::remove_dir_all(Self::notes_dir());
             println!("{}", t!("cleanup_success"));
             Ok(())
         }
@@ -199,7 +319,40 @@ impl TrashHandler for OsTrash {
         "os"
     }
 
-    fn list_restorable(&self, filter: Option<&str>) -> Result<Vec<RestorableItem>> {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn purge(&self, item_id: &OsStr, shredder: Option<&Shredder>) -> crate::Result<()> {
+        // The trash crate deletes the files itself, so there is nothing to overwrite first
+        if shredder.is_some() {
+            return Err(Error::Unsupported {
+                operation: "--purge --shred",
+                backend: self.backend_name(),
+            });
+        }
+
+        let items = trash::os_limited::list().with_context(|| {
+            t!(
+                "error_cleanup_failed",
+                reason = "failed to list trash items"
+            )
+        })?;
+        let to_purge: Vec<_> = items.into_iter().filter(|i| i.id == item_id).collect();
+        if to_purge.is_empty() {
+            return Err(Error::IdNotFound {
+                id: item_id.to_os_string(),
+            });
+        }
+
+        trash::os_limited::purge_all(to_purge)
+            .with_context(|| t!("error_cleanup_failed", reason = "purge failed"))?;
+        Self::remove_note(item_id);
+        Ok(())
+    }
+
+    fn list_restorable(
+        &self,
+        filter: Option<&str>,
+        tag: Option<&str>,
+    ) -> crate::Result<Vec<RestorableItem>> {
         #[cfg(target_os = "macos")]
         {
             let info_dir = Self::info_dir();
@@ -239,6 +392,14 @@ impl TrashHandler for OsTrash {
                     continue;
                 }
 
+                let mut annotation = Annotation::default();
+                for line in content.lines() {
+                    annotation.read_info_line(line);
+                }
+                if !annotation.matches(tag) {
+                    continue;
+                }
+
                 // Apply filter
                 if let Some(pat) = filter {
                     let name = original_path
@@ -256,11 +417,20 @@ impl TrashHandler for OsTrash {
                     .unwrap_or(OsStr::new("unknown"))
                     .to_os_string();
 
+                let size = fs::symlink_metadata(&trash_path)
+                    .ok()
+                    .filter(|m| m.is_file())
+                    .map(|m| m.len());
+
                 items.push(RestorableItem {
                     id,
                     original_path,
                     display_name,
                     deleted_at,
+                    size,
+                    pinned: false,
+                    annotation,
+                    backend: "os",
                 });
             }
 
@@ -284,6 +454,11 @@ impl TrashHandler for OsTrash {
 
             let mut items = vec![];
             for item in os_items {
+                let annotation = Self::read_note(&item.id);
+                if !annotation.matches(tag) {
+                    continue;
+                }
+
                 let name_str = item.name.to_string_lossy();
 
                 // Apply filter
@@ -294,11 +469,21 @@ impl TrashHandler for OsTrash {
                     }
                 }
 
+                // Directories only report their entry count here, not bytes
+                let size = match trash::os_limited::metadata(&item).map(|m| m.size) {
+                    Ok(trash::TrashItemSize::Bytes(bytes)) => Some(bytes),
+                    _ => None,
+                };
+
                 items.push(RestorableItem {
                     id: item.id.clone(),
                     original_path: item.original_path(),
                     display_name: item.name.clone(),
                     deleted_at: Some(item.time_deleted),
+                    size,
+                    pinned: false,
+                    annotation,
+                    backend: "os",
                 });
             }
 
@@ -306,28 +491,33 @@ impl TrashHandler for OsTrash {
         }
     }
 
-    fn restore_to(&self, item_id: &OsStr, destination: &Path) -> Result<()> {
+    fn restore_to(&self, item_id: &OsStr, destination: &Path) -> crate::Result<()> {
         #[cfg(target_os = "macos")]
         {
             let info_dir = Self::info_dir();
             let info_path = info_dir.join(format!("{}.trashinfo", item_id.to_string_lossy()));
 
-            let content =
-                fs::read_to_string(&info_path).with_context(|| t!("restore_not_found"))?;
+            let not_found = || Error::IdNotFound {
+                id: item_id.to_os_string(),
+            };
+            let content = fs::read_to_string(&info_path).map_err(|source| match source.kind() {
+                std::io::ErrorKind::NotFound => not_found(),
+                _ => Error::Io {
+                    path: info_path.clone(),
+                    source,
+                },
+            })?;
 
-            let (_original_path, trash_path, _deleted_at) = Self::parse_restore_meta(&content)
-                .ok_or_else(|| anyhow::anyhow!(t!("restore_not_found")))?;
+            let (_original_path, trash_path, _deleted_at) =
+                Self::parse_restore_meta(&content).ok_or_else(not_found)?;
 
             if !trash_path.exists() {
-                anyhow::bail!(t!("restore_not_found"));
+                return Err(not_found());
             }
 
-            fs::rename(&trash_path, destination).with_context(|| {
-                t!(
-                    "error_restore_failed",
-                    name = trash_path.display().to_string(),
-                    reason = "rename failed"
-                )
+            fs::rename(&trash_path, destination).map_err(|source| Error::Io {
+                path: destination.to_path_buf(),
+                source,
             })?;
 
             // Clean up metadata
@@ -350,11 +540,12 @@ impl TrashHandler for OsTrash {
             let to_restore: Vec<_> = items.into_iter().filter(|i| i.id == item_id).collect();
 
             if to_restore.is_empty() {
-                anyhow::bail!(t!("restore_not_found"));
+                return Err(Error::IdNotFound {
+                    id: item_id.to_os_string(),
+                });
             }
 
             let original_path = to_restore[0].original_path();
-            let item_name = to_restore[0].name.to_string_lossy().to_string();
 
             // If dest differs and the original path is occupied (rename/overwrite case),
             // temporarily move the occupying file so restore_all won't collide.
@@ -380,7 +571,10 @@ impl TrashHandler for OsTrash {
                             base_name
                         ));
                     }
-                    fs::rename(&original_path, &tmp)?;
+                    fs::rename(&original_path, &tmp).map_err(|source| Error::Io {
+                        path: original_path.clone(),
+                        source,
+                    })?;
                     Some(tmp)
                 } else {
                     None
@@ -388,6 +582,7 @@ impl TrashHandler for OsTrash {
 
             match trash::os_limited::restore_all(to_restore) {
                 Ok(()) => {
+                    Self::remove_note(item_id);
                     // If destination differs from original, move after native restore
                     if destination != original_path
                         && let Err(e) = fs::rename(&original_path, destination)
@@ -402,7 +597,10 @@ impl TrashHandler for OsTrash {
                                 re
                             );
                         }
-                        return Err(e.into());
+                        return Err(Error::Io {
+                            path: destination.to_path_buf(),
+                            source: e,
+                        });
                     }
                     // Put back the evicted file
                     if let Some(tmp) = temp_evict
@@ -428,10 +626,10 @@ impl TrashHandler for OsTrash {
                         );
                     }
                     match e {
-                        trash::Error::RestoreCollision { .. } => {
-                            anyhow::bail!(t!("restore_conflict", name = item_name))
+                        trash::Error::RestoreCollision { path, .. } => {
+                            Err(Error::Conflict { path })
                         }
-                        other => Err(anyhow::anyhow!(other)),
+                        other => Err(anyhow::anyhow!(other).into()),
                     }
                 }
             }
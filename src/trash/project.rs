@@ -0,0 +1,209 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the project-local trash directory.
+pub const PROJECT_TRASH_DIR: &str = ".saferm-trash";
+
+/// How project-local trash directories are found, from `SAFERM_PROJECT_TRASH`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Discovery {
+    /// Use an existing `.saferm-trash/` (the default)
+    Existing,
+    /// Also create `.saferm-trash/` at the root of a git work tree
+    Auto,
+    /// Never use project trash directories
+    Off,
+}
+
+impl Discovery {
+    pub fn from_env() -> Self {
+        match std::env::var("SAFERM_PROJECT_TRASH").as_deref() {
+            Ok("auto") => Discovery::Auto,
+            Ok("off" | "0") => Discovery::Off,
+            Ok("" | "on" | "1") | Err(_) => Discovery::Existing,
+            Ok(other) => {
+                eprintln!(
+                    "saferm: warning: unknown SAFERM_PROJECT_TRASH '{}', using existing project trash only",
+                    other
+                );
+                Discovery::Existing
+            }
+        }
+    }
+}
+
+/// Find the project trash for `start`: the nearest `.saferm-trash/` in `start`
+/// or one of its ancestors. The search stops at the first git root, where the
+/// directory is created in [`Discovery::Auto`] mode, at the home directory and
+/// at the edge of the filesystem `start` is on.
+///
+/// A `.saferm-trash/` that is not a private directory of the current user is
+/// refused, so nobody can plant one to collect other users' files.
+pub fn find_project_trash(start: &Path, discovery: Discovery) -> Option<PathBuf> {
+    search(start, discovery, dirs::home_dir().as_deref())
+}
+
+fn search(start: &Path, discovery: Discovery, home: Option<&Path>) -> Option<PathBuf> {
+    if discovery == Discovery::Off {
+        return None;
+    }
+    let device = device_of(start);
+    for dir in start.ancestors() {
+        if device_of(dir) != device {
+            return None;
+        }
+        let trash_dir = dir.join(PROJECT_TRASH_DIR);
+        if trash_dir.symlink_metadata().is_ok() {
+            if is_private_dir(&trash_dir) {
+                return Some(trash_dir);
+            }
+            eprintln!(
+                "saferm: warning: ignoring project trash {:?}: it must be a directory only you own and can write",
+                trash_dir
+            );
+            return None;
+        }
+        if dir.join(".git").exists() {
+            if discovery == Discovery::Auto {
+                return create_project_trash(&trash_dir).ok().map(|()| trash_dir);
+            }
+            return None;
+        }
+        if Some(dir) == home {
+            return None;
+        }
+    }
+    None
+}
+
+fn create_project_trash(trash_dir: &Path) -> Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder
+        .create(trash_dir)
+        .with_context(|| format!("failed to create project trash: {:?}", trash_dir))?;
+    // Keep trashed files out of the repository
+    fs::write(trash_dir.join(".gitignore"), "*\n")
+        .with_context(|| format!("failed to create project trash: {:?}", trash_dir))
+}
+
+/// Whether `dir` is a real directory, not a symlink, that the current user
+/// owns and nobody else can write to.
+#[cfg(unix)]
+pub(crate) fn is_private_dir(dir: &Path) -> bool {
+    // SAFETY: getuid has no preconditions and cannot fail.
+    let uid = unsafe { libc::getuid() };
+    fs::symlink_metadata(dir).is_ok_and(|meta| is_private_to(&meta, uid))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn is_private_dir(dir: &Path) -> bool {
+    fs::symlink_metadata(dir).is_ok_and(|meta| meta.is_dir())
+}
+
+#[cfg(unix)]
+fn is_private_to(meta: &fs::Metadata, uid: u32) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    meta.is_dir() && meta.uid() == uid && meta.mode() & 0o022 == 0
+}
+
+#[cfg(unix)]
+fn device_of(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).ok().map(|meta| meta.dev())
+}
+
+#[cfg(not(unix))]
+fn device_of(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_project_trash() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        let nested = repo.join("crates/core/src");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir(repo.join(".git")).unwrap();
+
+        // A git root alone is not enough unless discovery is automatic
+        assert_eq!(find_project_trash(&nested, Discovery::Existing), None);
+        let created = find_project_trash(&nested, Discovery::Auto).unwrap();
+        assert_eq!(created, repo.join(PROJECT_TRASH_DIR));
+        assert_eq!(
+            fs::read_to_string(created.join(".gitignore")).unwrap(),
+            "*\n"
+        );
+        assert_eq!(
+            find_project_trash(&nested, Discovery::Existing),
+            Some(created.clone())
+        );
+        assert_eq!(find_project_trash(&nested, Discovery::Off), None);
+
+        // The search doesn't leave the git work tree
+        fs::create_dir(tmp.path().join(PROJECT_TRASH_DIR)).unwrap();
+        fs::remove_dir_all(&created).unwrap();
+        assert_eq!(find_project_trash(&nested, Discovery::Existing), None);
+    }
+
+    #[test]
+    fn test_search_stops_at_home() {
+        let tmp = TempDir::new().unwrap();
+        let home = tmp.path().join("home");
+        let nested = home.join("notes/2024");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir(tmp.path().join(PROJECT_TRASH_DIR)).unwrap();
+
+        assert_eq!(search(&nested, Discovery::Existing, Some(&home)), None);
+        fs::create_dir(home.join(PROJECT_TRASH_DIR)).unwrap();
+        assert_eq!(
+            search(&nested, Discovery::Existing, Some(&home)),
+            Some(home.join(PROJECT_TRASH_DIR))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_writable_project_trash_is_refused() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("project");
+        let trash_dir = project.join(PROJECT_TRASH_DIR);
+        fs::create_dir_all(&trash_dir).unwrap();
+        fs::create_dir(project.join(".git")).unwrap();
+
+        for mode in [0o777, 0o775, 0o757] {
+            fs::set_permissions(&trash_dir, fs::Permissions::from_mode(mode)).unwrap();
+            assert_eq!(find_project_trash(&project, Discovery::Existing), None);
+        }
+        fs::set_permissions(&trash_dir, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(
+            find_project_trash(&project, Discovery::Existing),
+            Some(trash_dir.clone())
+        );
+
+        // A symlink to a private directory is refused too
+        fs::remove_dir(&trash_dir).unwrap();
+        std::os::unix::fs::symlink(tmp.path(), &trash_dir).unwrap();
+        assert_eq!(find_project_trash(&project, Discovery::Existing), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_project_trash_of_another_user_is_refused() {
+        let tmp = TempDir::new().unwrap();
+        let meta = fs::symlink_metadata(tmp.path()).unwrap();
+        let owner = std::os::unix::fs::MetadataExt::uid(&meta);
+
+        assert!(is_private_to(&meta, owner));
+        assert!(!is_private_to(&meta, owner.wrapping_add(1)));
+    }
+}
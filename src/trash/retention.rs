@@ -0,0 +1,197 @@
+use anyhow::Result;
+use rust_i18n::t;
+use std::collections::HashSet;
+use std::ffi::OsString;
+
+use super::RestorableItem;
+use super::compress::parse_size;
+
+/// Age and size limits of one managed trash bin.
+///
+/// The default bin reads `SAFERM_RETENTION` and `SAFERM_QUOTA`; a named bin
+/// reads `SAFERM_BIN_<NAME>_RETENTION` and `SAFERM_BIN_<NAME>_QUOTA`, with the
+/// name upper-cased and `-` turned into `_`. Bins don't inherit each other's
+/// limits. Pinned entries are never evicted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Retention {
+    /// Entries trashed longer ago than this many seconds are evicted
+    pub max_age: Option<i64>,
+    /// The oldest entries are evicted while the bin holds more bytes than this
+    pub quota: Option<u64>,
+}
+
+impl Retention {
+    pub fn from_env(bin: Option<&str>) -> Self {
+        let prefix = match bin {
+            Some(name) => format!("SAFERM_BIN_{}_", name.to_uppercase().replace('-', "_")),
+            None => "SAFERM_".to_string(),
+        };
+        let read = |key: &str, parse: &dyn Fn(&str) -> Option<u64>| {
+            let var = format!("{}{}", prefix, key);
+            let value = std::env::var(&var).ok().filter(|v| !v.is_empty())?;
+            let parsed = parse(&value);
+            if parsed.is_none() {
+                eprintln!("saferm: warning: invalid {} '{}', ignoring", var, value);
+            }
+            parsed
+        };
+        Self {
+            max_age: read("RETENTION", &parse_duration).and_then(|s| i64::try_from(s).ok()),
+            quota: read("QUOTA", &parse_size),
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_age.is_none() && self.quota.is_none()
+    }
+
+    /// Ids of the entries to evict at unix time `now`: every unpinned entry past
+    /// the retention age, then the oldest unpinned ones until the bin fits its
+    /// quota. The newest entry is kept even if it alone exceeds the quota, so
+    /// what was just trashed can always be restored.
+    pub fn select(&self, items: &[RestorableItem], now: i64) -> Vec<OsString> {
+        let mut by_age: Vec<&RestorableItem> = items.iter().collect();
+        // Oldest first; ids are ULIDs and break ties within the same second
+        by_age.sort_by(|a, b| (a.deleted_at, &a.id).cmp(&(b.deleted_at, &b.id)));
+        let newest = by_age.last().map(|item| item.id.clone());
+
+        let mut evicted: HashSet<&OsString> = HashSet::new();
+        if let Some(max_age) = self.max_age {
+            for item in by_age.iter().filter(|item| !item.pinned) {
+                if item.deleted_at.is_some_and(|at| now - at > max_age) {
+                    evicted.insert(&item.id);
+                }
+            }
+        }
+
+        if let Some(quota) = self.quota {
+            let mut total: u64 = by_age
+                .iter()
+                .filter(|item| !evicted.contains(&item.id))
+                .filter_map(|item| item.size)
+                .sum();
+            for item in &by_age {
+                if total <= quota {
+                    break;
+                }
+                if item.pinned || evicted.contains(&item.id) || Some(&item.id) == newest.as_ref() {
+                    continue;
+                }
+                evicted.insert(&item.id);
+                total -= item.size.unwrap_or(0);
+            }
+        }
+
+        by_age
+            .iter()
+            .filter(|item| evicted.contains(&item.id))
+            .map(|item| item.id.clone())
+            .collect()
+    }
+}
+
+/// Parse a duration such as `30d`, `12h`, `2w` or `90m` into seconds; a bare
+/// number counts days.
+pub(crate) fn parse_duration(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (digits, unit) = match s.char_indices().last()? {
+        (i, 'm') => (&s[..i], 60),
+        (i, 'h') => (&s[..i], 60 * 60),
+        (i, 'd') => (&s[..i], 24 * 60 * 60),
+        (i, 'w') => (&s[..i], 7 * 24 * 60 * 60),
+        _ => (s, 24 * 60 * 60),
+    };
+    digits.trim().parse::<u64>().ok()?.checked_mul(unit)
+}
+
+/// Check a bin name given on the command line; names become directory names.
+pub fn validate_bin_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        anyhow::bail!(t!("error_invalid_bin", name = name));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trash::Annotation;
+    use std::path::PathBuf;
+
+    fn item(id: &str, deleted_at: i64, size: u64, pinned: bool) -> RestorableItem {
+        RestorableItem {
+            id: OsString::from(id),
+            original_path: PathBuf::from(format!("/tmp/{}", id)),
+            display_name: OsString::from(id),
+            deleted_at: Some(deleted_at),
+            size: Some(size),
+            pinned,
+            annotation: Annotation::default(),
+            backend: "managed",
+        }
+    }
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30d"), Some(30 * 86400));
+        assert_eq!(parse_duration("12h"), Some(12 * 3600));
+        assert_eq!(parse_duration("2w"), Some(14 * 86400));
+        assert_eq!(parse_duration("90m"), Some(90 * 60));
+        assert_eq!(parse_duration("7"), Some(7 * 86400));
+        assert_eq!(parse_duration("soon"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn test_select_by_age_skips_pinned() {
+        let items = vec![
+            item("a", 0, 1, false),
+            item("b", 0, 1, true),
+            item("c", 9 * DAY, 1, false),
+        ];
+        let retention = Retention {
+            max_age: Some(7 * DAY),
+            quota: None,
+        };
+        assert_eq!(
+            retention.select(&items, 10 * DAY),
+            vec![OsString::from("a")]
+        );
+    }
+
+    #[test]
+    fn test_select_by_quota_oldest_first() {
+        let items = vec![
+            item("c", 3, 50, false),
+            item("a", 1, 50, false),
+            item("b", 2, 50, true),
+            item("d", 4, 500, false),
+        ];
+        let retention = Retention {
+            max_age: None,
+            quota: Some(100),
+        };
+        // The pinned and the newest entry stay even though the bin is still over quota
+        assert_eq!(
+            retention.select(&items, 10),
+            vec![OsString::from("a"), OsString::from("c")]
+        );
+        assert!(Retention::default().select(&items, 10).is_empty());
+    }
+
+    #[test]
+    fn test_validate_bin_name() {
+        assert!(validate_bin_name("scratch").is_ok());
+        assert!(validate_bin_name("build_artifacts-2").is_ok());
+        for bad in ["", "..", "a/b", "with space", ".hidden"] {
+            assert!(validate_bin_name(bad).is_err(), "{:?}", bad);
+        }
+    }
+}
@@ -0,0 +1,940 @@
+use anyhow::{Context, Result};
+use rust_i18n::t;
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+#[cfg(target_os = "linux")]
+use std::sync::OnceLock;
+
+use super::managed::ManagedTrash;
+use super::os_trash::OsTrash;
+use super::project::{self, Discovery};
+use super::shred::Shredder;
+use super::{Annotation, Integrity, RestorableItem, Route, TrashHandler, TrashProblem};
+use crate::error::Error;
+use crate::observer::{NoopObserver, Observer};
+use crate::prompt::Prompter;
+
+/// Backend name of targets that are deleted without going to a trash.
+pub const DIRECT: &str = "direct";
+
+/// Where a routing rule sends the targets it matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    /// The backend from `SAFERM_TRASH_BACKEND` or the platform default
+    Default,
+    Os,
+    /// The managed trash in the user's data directory
+    Managed,
+    /// A managed trash at the root of the target's filesystem
+    Volume,
+    /// Delete permanently, after the usual confirmation
+    Direct,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Matcher {
+    /// Targets under this directory
+    Path(PathBuf),
+    /// Targets on a filesystem of this type, such as `tmpfs`
+    FsType(String),
+    /// Targets owned by another user
+    Foreign,
+}
+
+/// One `MATCH=DESTINATION` entry of `SAFERM_ROUTES`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    text: String,
+    matcher: Matcher,
+    destination: Destination,
+}
+
+impl Rule {
+    fn parse(text: &str) -> Option<Self> {
+        let (matcher, destination) = text.split_once('=')?;
+        let matcher = match matcher.trim() {
+            "owner:other" => Matcher::Foreign,
+            m if m.starts_with("fs:") && m.len() > 3 => Matcher::FsType(m[3..].to_string()),
+            m if Path::new(m).is_absolute() => {
+                let dir = PathBuf::from(m);
+                Matcher::Path(dir.canonicalize().unwrap_or(dir))
+            }
+            _ => return None,
+        };
+        let destination = match destination.trim() {
+            "default" => Destination::Default,
+            "os" => Destination::Os,
+            "managed" => Destination::Managed,
+            "volume" => Destination::Volume,
+            "direct" => Destination::Direct,
+            _ => return None,
+        };
+        Some(Self {
+            text: text.trim().to_string(),
+            matcher,
+            destination,
+        })
+    }
+
+    fn matches(&self, target: &Target) -> bool {
+        match &self.matcher {
+            Matcher::Path(dir) => target.path.starts_with(dir),
+            Matcher::FsType(fs_type) => target
+                .mount
+                .as_ref()
+                .is_some_and(|mount| &mount.fs_type == fs_type),
+            Matcher::Foreign => target.foreign,
+        }
+    }
+}
+
+/// Parse routing rules: comma-separated `MATCH=DESTINATION` entries, where
+/// MATCH is an absolute directory, `fs:TYPE` or `owner:other`, and DESTINATION
+/// is `default`, `os`, `managed`, `volume` or `direct`. The first matching rule
+/// wins; invalid entries are skipped with a warning.
+pub fn parse_rules(spec: &str) -> Vec<Rule> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let rule = Rule::parse(entry);
+            if rule.is_none() {
+                eprintln!(
+                    "saferm: warning: invalid SAFERM_ROUTES rule '{}', ignoring",
+                    entry
+                );
+            }
+            rule
+        })
+        .collect()
+}
+
+pub fn rules_from_env() -> Vec<Rule> {
+    std::env::var("SAFERM_ROUTES")
+        .map(|spec| parse_rules(&spec))
+        .unwrap_or_default()
+}
+
+/// The mount holding a path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    pub point: PathBuf,
+    pub fs_type: String,
+}
+
+/// Find the mount `path` (already canonical) is on.
+#[cfg(target_os = "linux")]
+pub fn mount_of(path: &Path) -> Option<Mount> {
+    mount_among(&read_mounts(), path)
+}
+
+/// The entries of `/proc/self/mounts`, in mount order.
+#[cfg(target_os = "linux")]
+fn read_mounts() -> Vec<Mount> {
+    let Ok(mounts) = fs::read_to_string("/proc/self/mounts") else {
+        return vec![];
+    };
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            Some(Mount {
+                point: unescape_mount_field(fields.get(1)?),
+                fs_type: fields.get(2)?.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn mount_among(mounts: &[Mount], path: &Path) -> Option<Mount> {
+    mounts
+        .iter()
+        .filter(|mount| path.starts_with(&mount.point))
+        // The last of equally long mount points is the one on top
+        .max_by_key(|mount| mount.point.as_os_str().len())
+        .cloned()
+}
+
+/// Undo the octal escapes (`\040` for a space) of `/proc/self/mounts`.
+#[cfg(target_os = "linux")]
+fn unescape_mount_field(field: &str) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes
+            .get(i + 1..i + 4)
+            .filter(|_| bytes[i] == b'\\')
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match octal {
+            Some(byte) => {
+                out.push(byte);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(OsString::from_vec(out))
+}
+
+#[cfg(target_os = "macos")]
+pub fn mount_of(path: &Path) -> Option<Mount> {
+    use std::os::unix::ffi::OsStrExt;
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statfs writes into the zeroed struct we pass and reads a valid C string.
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    // SAFETY: f_mntonname and f_fstypename are NUL-terminated strings filled in by statfs.
+    let (point, fs_type) = unsafe {
+        (
+            std::ffi::CStr::from_ptr(stat.f_mntonname.as_ptr()),
+            std::ffi::CStr::from_ptr(stat.f_fstypename.as_ptr()),
+        )
+    };
+    Some(Mount {
+        point: PathBuf::from(OsStr::from_bytes(point.to_bytes())),
+        fs_type: fs_type.to_string_lossy().into_owned(),
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn mount_of(_path: &Path) -> Option<Mount> {
+    None
+}
+
+/// Mount lookups for one router; on Linux `/proc/self/mounts` is read once.
+#[derive(Default)]
+struct MountTable {
+    #[cfg(target_os = "linux")]
+    mounts: OnceLock<Vec<Mount>>,
+}
+
+impl MountTable {
+    #[cfg(target_os = "linux")]
+    fn mount_of(&self, path: &Path) -> Option<Mount> {
+        mount_among(self.mounts.get_or_init(read_mounts), path)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn mount_of(&self, path: &Path) -> Option<Mount> {
+        mount_of(path)
+    }
+}
+
+#[cfg(unix)]
+fn current_uid() -> u32 {
+    // SAFETY: getuid has no preconditions and cannot fail.
+    unsafe { libc::getuid() }
+}
+
+#[cfg(unix)]
+fn is_foreign(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    fs::symlink_metadata(path).is_ok_and(|meta| meta.uid() != current_uid())
+}
+
+#[cfg(not(unix))]
+fn is_foreign(_path: &Path) -> bool {
+    false
+}
+
+/// The per-user managed trash at the root of `mount`.
+#[cfg(unix)]
+fn volume_trash_dir(mount: &Mount) -> PathBuf {
+    mount.point.join(format!(".saferm-trash-{}", current_uid()))
+}
+
+#[cfg(not(unix))]
+fn volume_trash_dir(mount: &Mount) -> PathBuf {
+    mount.point.join(".saferm-trash")
+}
+
+/// Create a volume trash only its owner can enter, or check that the existing
+/// one is a directory nobody else controls.
+fn create_volume_trash(dir: &Path) -> Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    match builder.create(dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => {
+            Err(e).with_context(|| format!("failed to create volume trash: {:?}", dir))
+        }
+        Err(_) if !project::is_private_dir(dir) => anyhow::bail!(
+            "refusing volume trash {:?}: it must be a directory only you own and can write",
+            dir
+        ),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+fn same_filesystem(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_filesystem(_a: &Path, _b: &Path) -> bool {
+    true
+}
+
+/// What routing looks at for one target.
+struct Target {
+    /// Absolute path, with the parent directory resolved; the target itself
+    /// may be a symlink
+    path: PathBuf,
+    parent: PathBuf,
+    mount: Option<Mount>,
+    foreign: bool,
+}
+
+impl Target {
+    fn resolve(path: &Path, mounts: &MountTable) -> Option<Self> {
+        let absolute = std::path::absolute(path).ok()?;
+        let parent = absolute.parent()?.canonicalize().ok()?;
+        let path = parent.join(absolute.file_name()?);
+        Some(Self {
+            mount: mounts.mount_of(&parent),
+            foreign: is_foreign(&path),
+            path,
+            parent,
+        })
+    }
+
+    /// Whether moving the target into `trash_dir` would move a trash into itself.
+    fn overlaps(&self, trash_dir: &Path) -> bool {
+        trash_dir.starts_with(&self.path) || self.path.starts_with(trash_dir)
+    }
+}
+
+/// A backend the router lists, restores and cleans up.
+pub(crate) enum Store {
+    /// A managed trash, which can tell whether it holds an entry
+    Managed(ManagedTrash),
+    Other(Box<dyn TrashHandler>),
+}
+
+impl Store {
+    fn handler(&self) -> &dyn TrashHandler {
+        match self {
+            Store::Managed(managed) => managed,
+            Store::Other(handler) => handler.as_ref(),
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Store::Managed(managed) => managed.label(),
+            Store::Other(handler) => handler.backend_name(),
+        }
+    }
+}
+
+/// Add the store `make` builds unless one with its label is there already.
+fn add_store(stores: &mut Vec<Store>, make: impl FnOnce() -> Store) {
+    let store = make();
+    if !stores.iter().any(|other| other.label() == store.label()) {
+        stores.push(store);
+    }
+}
+
+/// On Linux the OS trash is the home trash; a managed trash reading it as
+/// well (`SAFERM_XDG_TRASH`) would show every item twice.
+fn list_home_trash_once(stores: &mut [Store]) {
+    if cfg!(target_os = "macos") || !stores.iter().any(|store| store.label() == "os") {
+        return;
+    }
+    for store in stores {
+        if let Store::Managed(managed) = store {
+            managed.ignore_home_trash();
+        }
+    }
+}
+
+/// Where one target goes.
+#[derive(Clone)]
+enum Choice {
+    /// One of the router's stores, by index
+    Store(usize),
+    /// A project or volume trash, with its label
+    Dir(PathBuf, &'static str),
+    Direct,
+}
+
+/// Picks a backend for each target rather than one for the whole process.
+///
+/// A target inside a project with a `.saferm-trash/` goes there (see
+/// [`project`]). Otherwise the first `SAFERM_ROUTES` rule matching its path,
+/// filesystem type or owner decides, and targets no rule matches go to the
+/// default backend. Project and volume trash directories are only used on the
+/// target's own filesystem, so trashing stays a rename.
+///
+/// Listing and restoring cover the default backend, the backends rules route
+/// to, and the project and volume trash of the current directory. Cleanup only
+/// empties the default backend.
+///
+/// Each target is routed once: the route [`TrashHandler::route`] reports, and
+/// a prompt shows, is the one trashing it takes.
+pub struct RoutedTrash {
+    /// The default backend first
+    stores: Vec<Store>,
+    discovery: Discovery,
+    rules: Vec<Rule>,
+    mounts: MountTable,
+    /// Routes reported but not yet taken, by target path as given
+    decisions: Mutex<HashMap<PathBuf, (Choice, Route)>>,
+}
+
+impl RoutedTrash {
+    pub(crate) fn new(default: Store, discovery: Discovery, rules: Vec<Rule>) -> Self {
+        let mut stores = vec![default];
+        for rule in &rules {
+            match rule.destination {
+                Destination::Os => add_store(&mut stores, || Store::Other(Box::new(OsTrash))),
+                Destination::Managed => {
+                    add_store(&mut stores, || Store::Managed(ManagedTrash::new()))
+                }
+                _ => {}
+            }
+        }
+
+        let mounts = MountTable::default();
+        let cwd = std::env::current_dir()
+            .ok()
+            .and_then(|cwd| cwd.canonicalize().ok());
+        if let Some(cwd) = &cwd {
+            if let Some(dir) = project::find_project_trash(cwd, discovery) {
+                stores.push(Store::Managed(ManagedTrash::at(dir, "project")));
+            }
+            let volume = rules
+                .iter()
+                .any(|rule| rule.destination == Destination::Volume)
+                .then(|| mounts.mount_of(cwd))
+                .flatten()
+                .map(|mount| volume_trash_dir(&mount))
+                .filter(|dir| project::is_private_dir(dir));
+            if let Some(dir) = volume {
+                stores.push(Store::Managed(ManagedTrash::at(dir, "volume")));
+            }
+        }
+
+        list_home_trash_once(&mut stores);
+        Self {
+            stores,
+            discovery,
+            rules,
+            mounts,
+            decisions: Mutex::default(),
+        }
+    }
+
+    /// Also list and restore from the OS and managed trash when they exist,
+    /// whichever of them is the default. Items end up in both when the
+    /// detected default differs between sessions (SSH versus desktop).
+    pub(crate) fn with_available_backends(mut self) -> Self {
+        if OsTrash::is_available() {
+            add_store(&mut self.stores, || Store::Other(Box::new(OsTrash)));
+        }
+        let managed = ManagedTrash::new();
+        if managed.exists() {
+            add_store(&mut self.stores, || Store::Managed(managed));
+        }
+        list_home_trash_once(&mut self.stores);
+        self
+    }
+
+    fn default_handler(&self) -> &dyn TrashHandler {
+        self.stores[0].handler()
+    }
+
+    /// Index of the store labelled `name`, or of the default backend.
+    fn store_named(&self, name: &str) -> usize {
+        self.stores
+            .iter()
+            .position(|store| store.label() == name)
+            .unwrap_or(0)
+    }
+
+    /// The route of `path`: the one already reported for it, or a new one
+    /// kept until the target is trashed.
+    fn decision(&self, path: &Path) -> (Choice, Route) {
+        let decisions = || self.decisions.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(decision) = decisions().get(path) {
+            return decision.clone();
+        }
+        // Not under the lock, so parallel workers route concurrently
+        let decision = self.decide(path);
+        decisions()
+            .entry(path.to_path_buf())
+            .or_insert(decision)
+            .clone()
+    }
+
+    fn take_decision(&self, path: &Path) -> Choice {
+        let taken = self
+            .decisions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(path);
+        taken.unwrap_or_else(|| self.decide(path)).0
+    }
+
+    fn decide(&self, path: &Path) -> (Choice, Route) {
+        let default = || (Choice::Store(0), self.default_handler().route(path));
+        let Some(target) = Target::resolve(path, &self.mounts) else {
+            return default();
+        };
+
+        if let Some(dir) = project::find_project_trash(&target.parent, self.discovery)
+            && !target.overlaps(&dir)
+            && same_filesystem(&target.parent, &dir)
+        {
+            let reason = t!("route_project", dir = dir.display()).to_string();
+            return (
+                Choice::Dir(dir, "project"),
+                Route::new("project", Some(reason)),
+            );
+        }
+
+        let Some(rule) = self.rules.iter().find(|rule| rule.matches(&target)) else {
+            return default();
+        };
+        let reason = Some(t!("route_rule", rule = rule.text).to_string());
+        match rule.destination {
+            Destination::Default => (
+                Choice::Store(0),
+                Route::new(self.default_handler().backend_name(), reason),
+            ),
+            Destination::Os | Destination::Managed => {
+                let name = if rule.destination == Destination::Os {
+                    "os"
+                } else {
+                    "managed"
+                };
+                let index = self.store_named(name);
+                (
+                    Choice::Store(index),
+                    Route::new(self.stores[index].handler().backend_name(), reason),
+                )
+            }
+            Destination::Volume => match target.mount.as_ref().map(volume_trash_dir) {
+                Some(dir) if !target.overlaps(&dir) => {
+                    (Choice::Dir(dir, "volume"), Route::new("volume", reason))
+                }
+                _ => default(),
+            },
+            Destination::Direct => (Choice::Direct, Route::new(DIRECT, reason)),
+        }
+    }
+
+    /// The backend that holds `item_id`: the managed trash that has it, or
+    /// else the other backend that lists it.
+    fn owner(&self, item_id: &OsStr) -> &dyn TrashHandler {
+        if let Some(store) = self.stores.iter().find(|store| match store {
+            Store::Managed(managed) => managed.contains(item_id),
+            Store::Other(_) => false,
+        }) {
+            return store.handler();
+        }
+        let others: Vec<&dyn TrashHandler> = self
+            .stores
+            .iter()
+            .filter_map(|store| match store {
+                Store::Other(handler) => Some(handler.as_ref()),
+                Store::Managed(_) => None,
+            })
+            .collect();
+        match others.as_slice() {
+            [] => self.default_handler(),
+            [only] => *only,
+            _ => others
+                .iter()
+                .copied()
+                .find(|handler| {
+                    handler
+                        .list_restorable(None, None)
+                        .is_ok_and(|items| items.iter().any(|item| item.id == item_id))
+                })
+                .unwrap_or_else(|| self.default_handler()),
+        }
+    }
+
+    fn all(&self) -> impl Iterator<Item = &dyn TrashHandler> {
+        self.stores.iter().map(Store::handler)
+    }
+}
+
+/// Permanently delete a target routed to [`DIRECT`].
+fn delete_directly(path: &Path) -> Result<()> {
+    let meta = fs::symlink_metadata(path).with_context(|| format!("failed to read: {:?}", path))?;
+    if meta.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+    .with_context(|| format!("failed to delete: {:?}", path))
+}
+
+impl TrashHandler for RoutedTrash {
+    fn trash(&self, path: &Path) -> crate::Result<Option<OsString>> {
+        self.trash_annotated(path, &Annotation::default())
+    }
+
+    fn trash_annotated(
+        &self,
+        path: &Path,
+        annotation: &Annotation,
+    ) -> crate::Result<Option<OsString>> {
+        self.trash_observed(path, annotation, &NoopObserver)
+    }
+
+    fn trash_observed(
+        &self,
+        path: &Path,
+        annotation: &Annotation,
+        observer: &dyn Observer,
+    ) -> crate::Result<Option<OsString>> {
+        match self.take_decision(path) {
+            Choice::Store(index) => self.stores[index]
+                .handler()
+                .trash_observed(path, annotation, observer),
+            Choice::Dir(dir, label) => {
+                if label == "volume" {
+                    create_volume_trash(&dir)?;
+                }
+                ManagedTrash::at(dir, label).trash_observed(path, annotation, observer)
+            }
+            Choice::Direct => {
+                if !annotation.is_empty() {
+                    return Err(Error::Unsupported {
+                        operation: "--reason/--tag",
+                        backend: DIRECT,
+                    });
+                }
+                delete_directly(path)?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn route(&self, path: &Path) -> Route {
+        self.decision(path).1
+    }
+
+    // Cleanup empties the default backend only: the other stores are listed
+    // for restoring, and one prompt must not empty trash the user never chose
+    fn cleanup(&self, prompter: &dyn Prompter, include_pinned: bool) -> crate::Result<()> {
+        self.default_handler().cleanup(prompter, include_pinned)
+    }
+
+    fn cleanup_shred(
+        &self,
+        prompter: &dyn Prompter,
+        shredder: &Shredder,
+        include_pinned: bool,
+    ) -> crate::Result<()> {
+        self.default_handler()
+            .cleanup_shred(prompter, shredder, include_pinned)
+    }
+
+    fn cleanup_observed(
+        &self,
+        prompter: &dyn Prompter,
+        shredder: Option<&Shredder>,
+        include_pinned: bool,
+        observer: &dyn Observer,
+    ) -> crate::Result<()> {
+        self.default_handler()
+            .cleanup_observed(prompter, shredder, include_pinned, observer)
+    }
+
+    fn backend_name(&self) -> &'static str {
+        self.default_handler().backend_name()
+    }
+
+    fn list_restorable(
+        &self,
+        filter: Option<&str>,
+        tag: Option<&str>,
+    ) -> crate::Result<Vec<RestorableItem>> {
+        let mut items = vec![];
+        for handler in self.all() {
+            items.extend(handler.list_restorable(filter, tag)?);
+        }
+        if self.stores.len() > 1 {
+            // Newest first across backends
+            items.sort_by(|a, b| (b.deleted_at, &b.id).cmp(&(a.deleted_at, &a.id)));
+        }
+        Ok(items)
+    }
+
+    fn restore_to(&self, item_id: &OsStr, destination: &Path) -> crate::Result<()> {
+        self.owner(item_id).restore_to(item_id, destination)
+    }
+
+    fn restore_observed(
+        &self,
+        item_id: &OsStr,
+        destination: &Path,
+        observer: &dyn Observer,
+    ) -> crate::Result<()> {
+        self.owner(item_id)
+            .restore_observed(item_id, destination, observer)
+    }
+
+    fn purge(&self, item_id: &OsStr, shredder: Option<&Shredder>) -> crate::Result<()> {
+        self.owner(item_id).purge(item_id, shredder)
+    }
+
+    fn purge_observed(
+        &self,
+        item_id: &OsStr,
+        shredder: Option<&Shredder>,
+        observer: &dyn Observer,
+    ) -> crate::Result<()> {
+        self.owner(item_id)
+            .purge_observed(item_id, shredder, observer)
+    }
+
+    fn evict(&self) -> crate::Result<Vec<RestorableItem>> {
+        self.evict_observed(&NoopObserver)
+    }
+
+    fn evict_observed(&self, observer: &dyn Observer) -> crate::Result<Vec<RestorableItem>> {
+        let mut evicted = vec![];
+        for handler in self.all() {
+            evicted.extend(handler.evict_observed(observer)?);
+        }
+        Ok(evicted)
+    }
+
+    fn set_pinned(&self, item_id: &OsStr, pinned: bool) -> crate::Result<()> {
+        self.owner(item_id).set_pinned(item_id, pinned)
+    }
+
+    fn verify_integrity(
+        &self,
+        filter: Option<&str>,
+    ) -> crate::Result<Vec<(RestorableItem, Integrity)>> {
+        let mut results = vec![];
+        for handler in self.all() {
+            results.extend(handler.verify_integrity(filter)?);
+        }
+        Ok(results)
+    }
+
+    fn fsck(&self) -> crate::Result<Vec<TrashProblem>> {
+        // Other backends can't be checked; only the default one reports that
+        let mut problems = self.default_handler().fsck()?;
+        for store in &self.stores[1..] {
+            if let Store::Managed(managed) = store {
+                problems.extend(managed.fsck()?);
+            }
+        }
+        Ok(problems)
+    }
+
+    fn repair(&self, problem: &TrashProblem) -> crate::Result<()> {
+        let owner = self.stores[1..].iter().find_map(|store| match store {
+            Store::Managed(managed) if problem.path().starts_with(managed.base_dir()) => {
+                Some(managed)
+            }
+            _ => None,
+        });
+        match owner {
+            Some(managed) => managed.repair(problem),
+            None => self.default_handler().repair(problem),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trash::project::PROJECT_TRASH_DIR;
+    use tempfile::TempDir;
+
+    fn router(default: &Path, rules: &str) -> RoutedTrash {
+        RoutedTrash {
+            stores: vec![Store::Managed(ManagedTrash::at(
+                default.to_path_buf(),
+                "managed",
+            ))],
+            discovery: Discovery::Existing,
+            rules: parse_rules(rules),
+            mounts: MountTable::default(),
+            decisions: Mutex::default(),
+        }
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let rules = parse_rules("fs:tmpfs=direct, /data=volume,owner:other=os,bogus,/x=nowhere");
+        assert_eq!(rules.len(), 3);
+        assert_eq!(rules[0].matcher, Matcher::FsType("tmpfs".to_string()));
+        assert_eq!(rules[0].destination, Destination::Direct);
+        assert_eq!(rules[1].text, "/data=volume");
+        assert_eq!(rules[2].matcher, Matcher::Foreign);
+        assert!(parse_rules("").is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_mount_of() {
+        assert_eq!(mount_of(Path::new("/")).unwrap().point, PathBuf::from("/"));
+        assert_eq!(
+            unescape_mount_field("/mnt/usb\\040disk"),
+            PathBuf::from("/mnt/usb disk")
+        );
+    }
+
+    #[test]
+    fn test_routes_project_and_rules() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().canonicalize().unwrap();
+        let project = root.join("project");
+        let scratch = root.join("scratch");
+        fs::create_dir_all(project.join(PROJECT_TRASH_DIR)).unwrap();
+        fs::create_dir(&scratch).unwrap();
+        let global = TempDir::new().unwrap();
+        let router = router(global.path(), &format!("{}=direct", scratch.display()));
+
+        let inside = project.join("build.log");
+        fs::write(&inside, "log").unwrap();
+        let temp = scratch.join("tmp.bin");
+        fs::write(&temp, "bytes").unwrap();
+        let other = root.join("notes.txt");
+        fs::write(&other, "notes").unwrap();
+
+        assert_eq!(router.route(&inside).backend, "project");
+        assert_eq!(router.route(&temp).backend, DIRECT);
+        assert!(router.route(&temp).reason.unwrap().contains("scratch"));
+        assert_eq!(router.route(&other), Route::new("managed", None));
+        // The project trash itself is never moved into itself
+        assert_eq!(
+            router.route(&project.join(PROJECT_TRASH_DIR)).backend,
+            "managed"
+        );
+
+        let id = router.trash(&inside).unwrap().unwrap();
+        assert!(
+            project
+                .join(PROJECT_TRASH_DIR)
+                .join("files")
+                .join(&id)
+                .exists()
+        );
+        assert_eq!(router.trash(&temp).unwrap(), None);
+        assert!(!temp.exists());
+        router.trash(&other).unwrap();
+        assert_eq!(router.list_restorable(None, None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_restores_from_the_trash_that_holds_the_entry() {
+        let project = TempDir::new().unwrap();
+        fs::create_dir(project.path().join(PROJECT_TRASH_DIR)).unwrap();
+        let global = TempDir::new().unwrap();
+        let mut router = router(global.path(), "");
+        router.stores.push(Store::Managed(ManagedTrash::at(
+            project.path().join(PROJECT_TRASH_DIR),
+            "project",
+        )));
+
+        let inside = project.path().join("build.log");
+        fs::write(&inside, "log").unwrap();
+        let id = router.trash(&inside).unwrap().unwrap();
+        assert_eq!(router.list_restorable(None, None).unwrap().len(), 1);
+
+        router.restore_to(&id, &inside).unwrap();
+        assert_eq!(fs::read_to_string(&inside).unwrap(), "log");
+    }
+
+    #[test]
+    fn test_cleanup_leaves_other_stores_alone() {
+        let global = TempDir::new().unwrap();
+        let secondary = TempDir::new().unwrap();
+        let mut router = router(global.path(), "");
+        router.stores.push(Store::Managed(ManagedTrash::at(
+            secondary.path().to_path_buf(),
+            "project",
+        )));
+
+        let source = TempDir::new().unwrap();
+        let kept = source.path().join("kept.txt");
+        let gone = source.path().join("gone.txt");
+        fs::write(&kept, "kept").unwrap();
+        fs::write(&gone, "gone").unwrap();
+        let Store::Managed(project) = &router.stores[1] else {
+            unreachable!()
+        };
+        let kept_id = project.trash(&kept).unwrap().unwrap();
+        router.trash(&gone).unwrap();
+
+        router
+            .cleanup(&crate::prompt::AutoConfirmPrompter, false)
+            .unwrap();
+        let items = router.list_restorable(None, None).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].id, kept_id);
+    }
+
+    #[test]
+    fn test_target_is_routed_once() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().canonicalize().unwrap();
+        let global = TempDir::new().unwrap();
+        let router = router(global.path(), "");
+        let file = project.join("a.txt");
+        fs::write(&file, "a").unwrap();
+
+        assert_eq!(router.route(&file).backend, "managed");
+        // A project trash appearing after the prompt doesn't change the route
+        fs::create_dir(project.join(PROJECT_TRASH_DIR)).unwrap();
+        assert_eq!(router.route(&file).backend, "managed");
+        let id = router.trash(&file).unwrap().unwrap();
+        assert!(global.path().join("files").join(&id).exists());
+
+        fs::write(&file, "a").unwrap();
+        assert_eq!(router.route(&file).backend, "project");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_existing_volume_trash_must_be_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join(".saferm-trash-1000");
+        create_volume_trash(&dir).unwrap();
+        assert_eq!(
+            fs::metadata(&dir).unwrap().permissions().mode() & 0o777,
+            0o700
+        );
+        create_volume_trash(&dir).unwrap();
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(create_volume_trash(&dir).is_err());
+
+        let link = tmp.path().join("link");
+        let target = tmp.path().join("target");
+        fs::create_dir(&target).unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o700)).unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        assert!(create_volume_trash(&link).is_err());
+    }
+}
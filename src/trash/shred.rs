@@ -0,0 +1,333 @@
+use anyhow::{Context, Result};
+use rand::RngCore;
+use rust_i18n::t;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Removes files for good by overwriting their contents before unlinking them.
+///
+/// Only allocated data regions are overwritten, so sparse files don't grow.
+/// Files with other hard links are unlinked without overwriting, as that
+/// would destroy the data behind the remaining links.
+pub struct Shredder {
+    passes: u32,
+    verbose: bool,
+    /// Filesystems already warned about
+    warned: RefCell<HashSet<u64>>,
+}
+
+impl Shredder {
+    pub fn new(passes: u32, verbose: bool) -> Self {
+        Self {
+            passes: passes.max(1),
+            verbose,
+            warned: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// Shred a file, or every file below a directory, and remove it.
+    pub fn remove(&self, path: &Path) -> Result<()> {
+        let meta = fs::symlink_metadata(path)
+            .with_context(|| format!("failed to read metadata: {:?}", path))?;
+        self.warn_if_ineffective(path, &meta);
+
+        if meta.is_dir() {
+            for entry in
+                fs::read_dir(path).with_context(|| format!("failed to read dir: {:?}", path))?
+            {
+                self.remove(&entry?.path())?;
+            }
+            fs::remove_dir(path).with_context(|| format!("failed to remove: {:?}", path))?;
+        } else {
+            if meta.is_file() {
+                if nlink(&meta) > 1 {
+                    eprintln!(
+                        "saferm: warning: '{}' has other hard links; removing without overwriting",
+                        path.display()
+                    );
+                } else {
+                    self.overwrite(path, meta.len())?;
+                }
+            }
+            let path = obscure_name(path);
+            fs::remove_file(&path).with_context(|| format!("failed to remove: {:?}", path))?;
+        }
+
+        if self.verbose {
+            println!("{}", t!("shred_removed", path = path.display().to_string()));
+        }
+        Ok(())
+    }
+
+    fn overwrite(&self, path: &Path, len: u64) -> Result<()> {
+        let mut file = open_for_overwrite(path)?;
+        let extents = data_extents(&file, len);
+        let mut rng = rand::rng();
+        let mut buf = vec![0u8; 64 * 1024];
+
+        for pass in 1..=self.passes {
+            if self.verbose {
+                println!(
+                    "{}",
+                    t!(
+                        "shred_pass",
+                        path = path.display().to_string(),
+                        pass = pass,
+                        passes = self.passes
+                    )
+                );
+            }
+            for &(start, end) in &extents {
+                file.seek(SeekFrom::Start(start))?;
+                let mut remaining = end - start;
+                while remaining > 0 {
+                    let n = remaining.min(buf.len() as u64) as usize;
+                    rng.fill_bytes(&mut buf[..n]);
+                    file.write_all(&buf[..n])
+                        .with_context(|| format!("failed to overwrite: {:?}", path))?;
+                    remaining -= n as u64;
+                }
+            }
+            // Each pass must reach the disk, or the next one just replaces it in the cache
+            file.sync_data()
+                .with_context(|| format!("failed to sync: {:?}", path))?;
+        }
+
+        file.set_len(0)?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Warn once per filesystem where overwriting in place doesn't destroy the old blocks.
+    fn warn_if_ineffective(&self, path: &Path, meta: &fs::Metadata) {
+        if !self.warned.borrow_mut().insert(device(meta)) {
+            return;
+        }
+        if let Some(kind) = overwrite_caveat(path) {
+            eprintln!(
+                "saferm: warning: '{}' is on a {} filesystem; overwriting may leave copies of the data on disk",
+                path.display(),
+                kind
+            );
+        }
+    }
+}
+
+/// Open a file for writing, granting the owner write permission if needed.
+fn open_for_overwrite(path: &Path) -> Result<File> {
+    let open = || OpenOptions::new().write(true).open(path);
+    match open() {
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            make_owner_writable(path)?;
+            open().with_context(|| format!("failed to open for overwriting: {:?}", path))
+        }
+        result => result.with_context(|| format!("failed to open for overwriting: {:?}", path)),
+    }
+}
+
+#[cfg(unix)]
+fn make_owner_writable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = fs::metadata(path)?.permissions().mode();
+    fs::set_permissions(path, fs::Permissions::from_mode(mode | 0o200))
+}
+
+#[cfg(not(unix))]
+fn make_owner_writable(path: &Path) -> std::io::Result<()> {
+    let mut perms = fs::metadata(path)?.permissions();
+    #[allow(clippy::permissions_set_readonly_false)]
+    perms.set_readonly(false);
+    fs::set_permissions(path, perms)
+}
+
+/// Rename a file to zeros of the same length before unlinking, so its name
+/// doesn't linger in the directory entry. Returns the path to unlink.
+fn obscure_name(path: &Path) -> PathBuf {
+    let len = path.file_name().map_or(1, |n| n.len());
+    let hidden = path.with_file_name("0".repeat(len));
+    if hidden.symlink_metadata().is_err() && fs::rename(path, &hidden).is_ok() {
+        hidden
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Byte ranges of `file` that hold data; holes of sparse files are skipped.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn data_extents(file: &File, len: u64) -> Vec<(u64, u64)> {
+    use std::os::fd::AsRawFd;
+    let fd = file.as_raw_fd();
+    let seek = |offset: u64, whence| {
+        // SAFETY: lseek only repositions the offset of a descriptor we own.
+        let pos = unsafe { libc::lseek(fd, offset as libc::off_t, whence) };
+        u64::try_from(pos).ok()
+    };
+
+    let mut extents = vec![];
+    let mut offset = 0;
+    while offset < len {
+        // No more data (ENXIO) or no SEEK_DATA support: stop or fall back
+        let Some(start) = seek(offset, libc::SEEK_DATA) else {
+            if extents.is_empty()
+                && offset == 0
+                && std::io::Error::last_os_error().raw_os_error() != Some(libc::ENXIO)
+            {
+                return vec![(0, len)];
+            }
+            break;
+        };
+        let end = seek(start, libc::SEEK_HOLE).unwrap_or(len).min(len);
+        if end <= start {
+            break;
+        }
+        extents.push((start, end));
+        offset = end;
+    }
+    extents
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn data_extents(_file: &File, len: u64) -> Vec<(u64, u64)> {
+    vec![(0, len)]
+}
+
+#[cfg(unix)]
+fn nlink(meta: &fs::Metadata) -> u64 {
+    std::os::unix::fs::MetadataExt::nlink(meta)
+}
+
+#[cfg(not(unix))]
+fn nlink(_meta: &fs::Metadata) -> u64 {
+    1
+}
+
+#[cfg(unix)]
+fn device(meta: &fs::Metadata) -> u64 {
+    std::os::unix::fs::MetadataExt::dev(meta)
+}
+
+#[cfg(not(unix))]
+fn device(_meta: &fs::Metadata) -> u64 {
+    0
+}
+
+/// Describe the filesystem of `path` if overwriting in place isn't effective there.
+#[cfg(target_os = "linux")]
+fn overwrite_caveat(path: &Path) -> Option<String> {
+    use std::os::unix::ffi::OsStrExt;
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statfs writes into the zeroed struct we pass and reads a valid C string.
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    let kind = match stat.f_type as u32 {
+        0x9123_683E => "btrfs (copy-on-write)",
+        0x2FC1_2FC1 => "zfs (copy-on-write)",
+        0xCA45_1A4E => "bcachefs (copy-on-write)",
+        0xF2F5_2010 => "f2fs (log-structured)",
+        0x3434 => "nilfs2 (log-structured)",
+        0xEF53 if mounted_with_data_journal(path) => "ext3/ext4 data=journal (journaling)",
+        _ => return None,
+    };
+    Some(kind.to_string())
+}
+
+/// True if the mount holding `path` journals file data, not just metadata.
+#[cfg(target_os = "linux")]
+fn mounted_with_data_journal(path: &Path) -> bool {
+    let Ok(mounts) = fs::read_to_string("/proc/self/mounts") else {
+        return false;
+    };
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            Some((*fields.get(1)?, *fields.get(3)?))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.len())
+        .is_some_and(|(_, options)| options.split(',').any(|o| o == "data=journal"))
+}
+
+#[cfg(target_os = "macos")]
+fn overwrite_caveat(path: &Path) -> Option<String> {
+    use std::os::unix::ffi::OsStrExt;
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statfs writes into the zeroed struct we pass and reads a valid C string.
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    // SAFETY: f_fstypename is a NUL-terminated string filled in by statfs.
+    let name = unsafe { std::ffi::CStr::from_ptr(stat.f_fstypename.as_ptr()) };
+    (name.to_bytes() == b"apfs").then(|| "apfs (copy-on-write)".to_string())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn overwrite_caveat(_path: &Path) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_shred_tree() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("creds");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("id_rsa"), "PRIVATE KEY").unwrap();
+        fs::write(dir.join("sub/token"), "secret").unwrap();
+
+        Shredder::new(2, false).remove(&dir).unwrap();
+        assert!(!dir.exists());
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hard_linked_file_is_not_overwritten() {
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("a");
+        let link = tmp.path().join("b");
+        fs::write(&file, "shared").unwrap();
+        fs::hard_link(&file, &link).unwrap();
+
+        Shredder::new(1, false).remove(&file).unwrap();
+        assert!(!file.exists());
+        assert_eq!(fs::read_to_string(&link).unwrap(), "shared");
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn test_data_extents_cover_written_data() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("sparse");
+        let mut file = File::create(&path).unwrap();
+        file.write_all(b"head").unwrap();
+        file.set_len(4 << 20).unwrap();
+        file.sync_all().unwrap();
+
+        let file = File::open(&path).unwrap();
+        let extents = data_extents(&file, 4 << 20);
+        // The written head is always covered; filesystems without hole
+        // reporting simply return the whole file.
+        assert!(
+            extents
+                .first()
+                .is_some_and(|&(start, end)| start == 0 && end >= 4)
+        );
+        assert!(
+            extents
+                .iter()
+                .all(|&(start, end)| start < end && end <= 4 << 20)
+        );
+    }
+}
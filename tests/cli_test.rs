@@ -1,3 +1,6 @@
+// Runs the saferm binary, which needs the `cli` feature
+#![cfg(feature = "cli")]
+
 use assert_cmd::cargo::cargo_bin_cmd;
 use predicates::prelude::*;
 use std::fs;
@@ -259,6 +262,79 @@ fn test_partial_failure_exits_nonzero() {
         .failure();
 }
 
+#[test]
+fn test_parallel_errors_in_argument_order() {
+    let tmp = TempDir::new().unwrap();
+    let mut args = vec!["-f".to_string()];
+    let mut dirs = Vec::new();
+    for i in 0..30 {
+        let path = tmp.path().join(format!("target{:02}", i));
+        // Every third target is a directory, which fails without -r
+        if i % 3 == 0 {
+            fs::create_dir(&path).unwrap();
+            dirs.push(path.display().to_string());
+        } else {
+            fs::write(&path, "data").unwrap();
+        }
+        args.push(path.display().to_string());
+    }
+
+    let (mut cmd, _trash) = saferm_isolated();
+    let output = cmd
+        .env("SAFERM_JOBS", "4")
+        .env("LANG", "en_US.UTF-8")
+        .args(&args)
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let reported: Vec<String> = stderr
+        .lines()
+        .filter_map(|line| dirs.iter().find(|dir| line.contains(dir.as_str())).cloned())
+        .collect();
+    assert_eq!(reported, dirs);
+    for i in (0..30).filter(|i| i % 3 != 0) {
+        assert!(!tmp.path().join(format!("target{:02}", i)).exists());
+    }
+}
+
+#[test]
+fn test_parallel_nested_targets_match_sequential_run() {
+    for _ in 0..10 {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("d");
+        fs::create_dir(&dir).unwrap();
+        let mut args = vec!["-rf".to_string(), dir.display().to_string()];
+        for i in 0..50 {
+            let file = dir.join(format!("f{}", i));
+            fs::write(&file, "data").unwrap();
+            args.push(file.display().to_string());
+        }
+        // The directory again, after its children
+        args.push(dir.display().to_string());
+
+        let (mut cmd, _trash) = saferm_isolated();
+        cmd.env("SAFERM_JOBS", "8").args(&args).assert().success();
+        assert!(!dir.exists());
+    }
+}
+
+#[test]
+fn test_invalid_jobs_warns() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("file.txt");
+    fs::write(&file, "data").unwrap();
+
+    let (mut cmd, _trash) = saferm_isolated();
+    cmd.env("SAFERM_JOBS", "many")
+        .args(["-f", file.to_str().unwrap()])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("invalid SAFERM_JOBS 'many'"));
+    assert!(!file.exists());
+}
+
 #[test]
 fn test_symlink_to_dir_without_recursive() {
     let tmp = TempDir::new().unwrap();
@@ -454,3 +530,873 @@ fn test_restore_directory() {
     assert!(dir.exists(), "Directory should have been restored");
     assert_eq!(fs::read_to_string(dir.join("inner.txt")).unwrap(), "inside");
 }
+
+// ===== Audit log tests =====
+
+#[test]
+fn test_verify_log_detects_tampering() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("audited.txt");
+    fs::write(&file, "data").unwrap();
+    let log = tmp.path().join("audit.log");
+    let key = tmp.path().join("audit.key");
+    fs::write(&key, "seed\n").unwrap();
+    let (mut trash_cmd, trash_dir) = saferm_isolated();
+
+    trash_cmd
+        .env("SAFERM_AUDIT_LOG", &log)
+        .env("SAFERM_AUDIT_KEY_FILE", &key)
+        .args(["-f", file.to_str().unwrap()])
+        .assert()
+        .success();
+
+    saferm_with_trash(&trash_dir)
+        .env("SAFERM_AUDIT_LOG", &log)
+        .env("SAFERM_AUDIT_KEY_FILE", &key)
+        .arg("--verify-log")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1"));
+
+    let content = fs::read_to_string(&log).unwrap();
+    assert!(content.contains("op=trash"));
+    fs::write(&log, content.replace("audited.txt", "innocent.txt")).unwrap();
+
+    saferm_with_trash(&trash_dir)
+        .env("SAFERM_AUDIT_LOG", &log)
+        .env("SAFERM_AUDIT_KEY_FILE", &key)
+        .arg("--verify-log")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("tampered").or(predicate::str::contains("改ざん")));
+}
+
+// ===== Integrity tests =====
+
+#[test]
+fn test_verify_reports_corrupted_item() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("checked.txt");
+    fs::write(&file, "original").unwrap();
+    let (mut trash_cmd, trash_dir) = saferm_isolated();
+
+    trash_cmd
+        .args(["-f", file.to_str().unwrap()])
+        .assert()
+        .success();
+
+    saferm_with_trash(&trash_dir)
+        .arg("--verify")
+        .assert()
+        .success();
+
+    // The single entry in files/ is stored under its id
+    let entry = fs::read_dir(trash_dir.path().join("files"))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    fs::write(entry.path(), "edited").unwrap();
+
+    saferm_with_trash(&trash_dir)
+        .arg("--verify")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("checked.txt"));
+
+    // Restore refuses the corrupted item and leaves it in the trash
+    saferm_with_trash(&trash_dir)
+        .args(["--restore", "-f", "checked"])
+        .assert()
+        .failure();
+    assert!(!file.exists());
+}
+
+// ===== Fsck tests =====
+
+#[test]
+fn test_fsck_reports_and_repairs() {
+    let (mut cmd, trash_dir) = saferm_isolated();
+    fs::create_dir_all(trash_dir.path().join("files")).unwrap();
+    fs::create_dir_all(trash_dir.path().join("info")).unwrap();
+    fs::write(
+        trash_dir.path().join("info").join("ghost.txt.trashinfo"),
+        "[Trash Info]\nPath=/tmp/ghost.txt\n",
+    )
+    .unwrap();
+
+    // Without -f in non-TTY: report only, exit non-zero
+    cmd.arg("--fsck")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("ghost.txt"));
+    assert!(trash_dir.path().join("info/ghost.txt.trashinfo").exists());
+
+    saferm_with_trash(&trash_dir)
+        .args(["--fsck", "-f"])
+        .assert()
+        .success();
+    assert!(!trash_dir.path().join("info/ghost.txt.trashinfo").exists());
+
+    saferm_with_trash(&trash_dir)
+        .arg("--fsck")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("consistent").or(predicate::str::contains("不整合")));
+}
+
+// ===== Version history tests =====
+
+#[test]
+fn test_versions_lists_and_restores_latest() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("cfg.toml");
+    let (mut trash_cmd, trash_dir) = saferm_isolated();
+
+    fs::write(&file, "v1").unwrap();
+    trash_cmd
+        .args(["-f", file.to_str().unwrap()])
+        .assert()
+        .success();
+    // Deletion dates have one-second resolution
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    fs::write(&file, "v2").unwrap();
+    saferm_with_trash(&trash_dir)
+        .args(["-f", file.to_str().unwrap()])
+        .assert()
+        .success();
+
+    // Non-TTY without -f only lists
+    saferm_with_trash(&trash_dir)
+        .args(["--versions", file.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("v1").and(predicate::str::contains("v2")));
+    assert!(!file.exists());
+
+    saferm_with_trash(&trash_dir)
+        .args(["--versions", "-f", file.to_str().unwrap()])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "v2");
+}
+
+#[test]
+fn test_restore_by_id() {
+    let tmp = TempDir::new().unwrap();
+    let file_a = tmp.path().join("same.txt");
+    let (mut trash_cmd, trash_dir) = saferm_isolated();
+
+    fs::write(&file_a, "first").unwrap();
+    let output = trash_cmd
+        .args(["-fv", file_a.to_str().unwrap()])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    fs::write(&file_a, "second").unwrap();
+    saferm_with_trash(&trash_dir)
+        .args(["-f", file_a.to_str().unwrap()])
+        .assert()
+        .success();
+
+    // Verbose output reports the id of the first entry: "... (managed, id XXXX)"
+    let id = stdout
+        .trim_end()
+        .trim_end_matches(')')
+        .rsplit(' ')
+        .next()
+        .unwrap()
+        .to_string();
+    assert_eq!(id.len(), 26, "unexpected verbose output: {}", stdout);
+
+    saferm_with_trash(&trash_dir)
+        .args(["--restore", "-f", "--id", &id])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&file_a).unwrap(), "first");
+
+    saferm_with_trash(&trash_dir)
+        .args(["--restore", "-f", "--id", &id])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(id.as_str()));
+}
+
+#[test]
+fn test_list_sorts_and_paginates() {
+    let tmp = TempDir::new().unwrap();
+    let (_, trash_dir) = saferm_isolated();
+    for (name, content) in [("small.txt", "x"), ("large.txt", "xxxxxxxxxx")] {
+        let file = tmp.path().join(name);
+        fs::write(&file, content).unwrap();
+        saferm_with_trash(&trash_dir)
+            .args(["-f", file.to_str().unwrap()])
+            .assert()
+            .success();
+    }
+
+    let output = saferm_with_trash(&trash_dir)
+        .args(["--list", "--sort", "size", "--limit", "1"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2, "unexpected output: {}", stdout);
+    assert!(lines[0].ends_with("large.txt"));
+    assert!(lines[1].contains("1 of 2"));
+
+    saferm_with_trash(&trash_dir)
+        .args(["--list", "small"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("small.txt"))
+        .stdout(predicate::str::contains("large.txt").not());
+}
+
+#[test]
+fn test_encrypted_trash_requires_key_to_restore() {
+    let tmp = TempDir::new().unwrap();
+    let key = tmp.path().join("key.txt");
+    let identity = age::x25519::Identity::generate();
+    fs::write(
+        &key,
+        age::secrecy::ExposeSecret::expose_secret(&identity.to_string()),
+    )
+    .unwrap();
+    let secret = tmp.path().join("dump.sql");
+    fs::write(&secret, "INSERT INTO users VALUES ('alice', 'pa55word');").unwrap();
+
+    let (mut cmd, trash_dir) = saferm_isolated();
+    cmd.env("SAFERM_ENCRYPT_KEY_FILE", &key)
+        .args(["-f", secret.to_str().unwrap()])
+        .assert()
+        .success();
+    let stored = fs::read_dir(trash_dir.path().join("files"))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    assert!(
+        !fs::read(stored)
+            .unwrap()
+            .windows(8)
+            .any(|w| w == b"pa55word")
+    );
+
+    saferm_with_trash(&trash_dir)
+        .args(["--list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("dump.sql"));
+
+    saferm_with_trash(&trash_dir)
+        .args(["--restore", "-f", "dump.sql"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("SAFERM_ENCRYPT_KEY_FILE"));
+    assert!(!secret.exists());
+
+    saferm_with_trash(&trash_dir)
+        .env("SAFERM_ENCRYPT_KEY_FILE", &key)
+        .args(["--restore", "-f", "dump.sql"])
+        .assert()
+        .success();
+    assert!(fs::read_to_string(&secret).unwrap().contains("pa55word"));
+}
+
+#[test]
+fn test_dedup_store_rebuilds_and_collects_blobs() {
+    let tmp = TempDir::new().unwrap();
+    let (_, trash_dir) = saferm_isolated();
+    let target = tmp.path().join("target");
+    for _ in 0..2 {
+        fs::create_dir_all(target.join("debug")).unwrap();
+        fs::write(target.join("debug/app"), "binary").unwrap();
+        saferm_with_trash(&trash_dir)
+            .env("SAFERM_DEDUP", "1")
+            .args(["-rf", target.to_str().unwrap()])
+            .assert()
+            .success();
+    }
+    let objects = trash_dir.path().join("objects");
+    assert_eq!(fs::read_dir(&objects).unwrap().count(), 1);
+
+    // Restoring works without the setting; it only affects new entries
+    saferm_with_trash(&trash_dir)
+        .args(["--restore", "-f", "target"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(target.join("debug/app")).unwrap(),
+        "binary"
+    );
+
+    // Restoring the last entry referencing the blobs collects them
+    let copy = tmp.path().join("copy");
+    let output = saferm_with_trash(&trash_dir)
+        .args(["--list"])
+        .output()
+        .unwrap();
+    let id = String::from_utf8(output.stdout).unwrap()[..26].to_string();
+    fs::rename(&target, &copy).unwrap();
+    saferm_with_trash(&trash_dir)
+        .args(["--restore", "-f", "--id", &id])
+        .assert()
+        .success();
+    assert!(target.join("debug/app").exists());
+    assert_eq!(fs::read_dir(&objects).unwrap().count(), 0);
+}
+
+#[test]
+fn test_shred_requires_cleanup_and_passes() {
+    saferm()
+        .args(["--shred", "file.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--cleanup"));
+
+    saferm()
+        .args(["--cleanup", "--shred", "--shred-passes", "0"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_purge_by_id() {
+    let tmp = TempDir::new().unwrap();
+    let keep = tmp.path().join("keep.txt");
+    let secret = tmp.path().join("secret.txt");
+    fs::write(&keep, "keep").unwrap();
+    fs::write(&secret, "API_TOKEN=secret").unwrap();
+    let (mut trash_cmd, trash_dir) = saferm_isolated();
+    trash_cmd
+        .args(["-f", keep.to_str().unwrap(), secret.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let output = saferm_with_trash(&trash_dir)
+        .args(["--list", "secret"])
+        .output()
+        .unwrap();
+    let id = String::from_utf8(output.stdout).unwrap()[..26].to_string();
+
+    // Outside a TTY: filters are refused and ids need -f
+    saferm_with_trash(&trash_dir)
+        .args(["--purge", "secret"])
+        .assert()
+        .failure();
+    saferm_with_trash(&trash_dir)
+        .args(["--purge", "--id", &id])
+        .assert()
+        .failure();
+
+    saferm_with_trash(&trash_dir)
+        .args(["--purge", "--shred", "-fv", "--id", &id])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("permanently deleted"));
+
+    let output = saferm_with_trash(&trash_dir)
+        .args(["--list"])
+        .output()
+        .unwrap();
+    let listing = String::from_utf8(output.stdout).unwrap();
+    assert!(listing.contains("keep.txt"));
+    assert!(!listing.contains("secret.txt"));
+
+    // Unknown ids fail
+    saferm_with_trash(&trash_dir)
+        .args(["--purge", "-f", "--id", &id])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(id.as_str()));
+}
+
+#[test]
+fn test_purge_conflicts_with_cleanup_and_restore() {
+    saferm().args(["--purge", "--cleanup"]).assert().failure();
+    saferm().args(["--purge", "--restore"]).assert().failure();
+    saferm().args(["--id", "X"]).assert().failure();
+}
+
+#[test]
+fn test_pin_and_unpin_by_id() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("keep.txt");
+    fs::write(&file, "keep").unwrap();
+    let (mut trash_cmd, trash_dir) = saferm_isolated();
+    trash_cmd
+        .args(["-f", file.to_str().unwrap()])
+        .assert()
+        .success();
+    let output = saferm_with_trash(&trash_dir)
+        .args(["--list"])
+        .output()
+        .unwrap();
+    let id = String::from_utf8(output.stdout).unwrap()[..26].to_string();
+
+    saferm_with_trash(&trash_dir)
+        .args(["--pin", "-v", "--id", &id])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("pinned"));
+    saferm_with_trash(&trash_dir)
+        .args(["--list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("keep.txt [pinned]"));
+
+    // Filters need a TTY or -f; already unpinned items are not offered
+    saferm_with_trash(&trash_dir)
+        .args(["--unpin", "keep"])
+        .assert()
+        .failure();
+    saferm_with_trash(&trash_dir)
+        .args(["--unpin", "-f", "keep"])
+        .assert()
+        .success();
+    saferm_with_trash(&trash_dir)
+        .args(["--list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[pinned]").not());
+
+    saferm()
+        .args(["--pin", "--restore", "x"])
+        .assert()
+        .failure();
+    saferm().args(["--include-pinned"]).assert().failure();
+}
+
+#[test]
+fn test_reason_and_tags() {
+    let tmp = TempDir::new().unwrap();
+    let old = tmp.path().join("old_schema");
+    fs::create_dir(&old).unwrap();
+    fs::write(old.join("v1.sql"), "create table t;").unwrap();
+    let other = tmp.path().join("other.txt");
+    fs::write(&other, "x").unwrap();
+
+    let (mut trash_cmd, trash_dir) = saferm_isolated();
+    trash_cmd
+        .args([
+            "-rf",
+            "--reason",
+            "replaced by v2 schema",
+            "--tag",
+            "migration",
+            old.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+    saferm_with_trash(&trash_dir)
+        .args(["-f", other.to_str().unwrap()])
+        .assert()
+        .success();
+
+    saferm_with_trash(&trash_dir)
+        .args(["--list", "--tag", "migration"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "old_schema #migration \"replaced by v2 schema\"",
+        ))
+        .stdout(predicate::str::contains("other.txt").not());
+
+    saferm_with_trash(&trash_dir)
+        .args(["--restore", "-f", "--tag", "migration"])
+        .assert()
+        .success();
+    assert!(old.join("v1.sql").exists());
+    assert!(!other.exists());
+
+    saferm()
+        .args(["--tag", "a,b", "file.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("a,b"));
+    saferm()
+        .args(["--restore", "--reason", "x"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_named_bins_are_separate_with_own_quota() {
+    let tmp = TempDir::new().unwrap();
+    let (_, trash_dir) = saferm_isolated();
+    let trash = |args: &[&str]| {
+        saferm_with_trash(&trash_dir)
+            .env("SAFERM_BIN_SCRATCH_QUOTA", "10")
+            .args(args)
+            .assert()
+            .success()
+    };
+
+    for name in ["a.o", "b.o"] {
+        let file = tmp.path().join(name);
+        fs::write(&file, "12345678").unwrap();
+        trash(&["-fv", "--bin", "scratch", file.to_str().unwrap()]);
+    }
+    let keep = tmp.path().join("keep.txt");
+    fs::write(&keep, "precious content").unwrap();
+    trash(&["-f", keep.to_str().unwrap()]);
+
+    // The quota only applies to the scratch bin; a.o was evicted for b.o
+    assert!(trash_dir.path().join("bins/scratch/files").exists());
+    trash(&["--list", "--bin", "scratch"])
+        .stdout(predicate::str::contains("b.o"))
+        .stdout(predicate::str::contains("a.o").not())
+        .stdout(predicate::str::contains("keep.txt").not());
+    trash(&["--list"])
+        .stdout(predicate::str::contains("keep.txt"))
+        .stdout(predicate::str::contains("b.o").not());
+
+    trash(&["--restore", "-f", "--bin", "scratch", "b.o"]);
+    assert!(tmp.path().join("b.o").exists());
+
+    saferm()
+        .args(["--bin", "../escape", "file.txt"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_project_trash_inside_work_tree() {
+    let project = TempDir::new().unwrap();
+    fs::create_dir(project.path().join(".git")).unwrap();
+    fs::create_dir(project.path().join(".saferm-trash")).unwrap();
+    fs::create_dir(project.path().join("src")).unwrap();
+    let file = project.path().join("src/generated.rs");
+    fs::write(&file, "// generated").unwrap();
+    let (_, trash_dir) = saferm_isolated();
+
+    saferm_with_trash(&trash_dir)
+        .current_dir(project.path())
+        .args(["-fv", "src/generated.rs"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("project"));
+    assert!(!file.exists());
+    let files = fs::read_dir(project.path().join(".saferm-trash/files")).unwrap();
+    assert_eq!(files.count(), 1);
+    assert!(!trash_dir.path().join("files").exists());
+
+    // Only listed from inside the project
+    saferm_with_trash(&trash_dir)
+        .current_dir(project.path().join("src"))
+        .arg("--list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("generated.rs"));
+    saferm_with_trash(&trash_dir)
+        .current_dir(trash_dir.path())
+        .arg("--list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("generated.rs").not());
+
+    saferm_with_trash(&trash_dir)
+        .current_dir(project.path())
+        .args(["--restore", "-f", "generated.rs"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "// generated");
+}
+
+#[test]
+fn test_routes_send_targets_to_their_backend() {
+    let tmp = TempDir::new().unwrap();
+    let scratch = tmp.path().join("scratch");
+    fs::create_dir(&scratch).unwrap();
+    let temp = scratch.join("cache.bin");
+    fs::write(&temp, "cache").unwrap();
+    let keep = tmp.path().join("keep.txt");
+    fs::write(&keep, "keep").unwrap();
+    let (_, trash_dir) = saferm_isolated();
+    let routes = format!("{}=direct", scratch.display());
+
+    saferm_with_trash(&trash_dir)
+        .env("SAFERM_ROUTES", &routes)
+        .args(["-fv", temp.to_str().unwrap(), keep.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("goes to direct"))
+        .stdout(predicate::str::contains("permanently deleted"))
+        .stdout(predicate::str::contains("(managed, id"));
+    assert!(!temp.exists());
+    assert!(!keep.exists());
+
+    saferm_with_trash(&trash_dir)
+        .arg("--list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("keep.txt"))
+        .stdout(predicate::str::contains("cache.bin").not());
+
+    // Unknown rules are reported and skipped
+    saferm_with_trash(&trash_dir)
+        .env("SAFERM_ROUTES", "tmpfs=direct")
+        .arg("--list")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("invalid SAFERM_ROUTES rule"));
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+#[test]
+fn test_list_and_restore_across_os_and_managed_trash() {
+    let data_home = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let (_, trash_dir) = saferm_isolated();
+    let detected = || {
+        let mut cmd = cargo_bin_cmd!("saferm");
+        cmd.env_remove("SAFERM_TRASH_BACKEND")
+            .env_remove("XDG_CURRENT_DESKTOP")
+            .env_remove("DESKTOP_SESSION")
+            .env("XDG_DATA_HOME", data_home.path())
+            .env("SAFERM_MANAGED_TRASH_DIR", trash_dir.path());
+        cmd
+    };
+
+    // An item trashed from a desktop session, in the FreeDesktop layout
+    let os_trash = data_home.path().join("Trash");
+    fs::create_dir_all(os_trash.join("files")).unwrap();
+    fs::create_dir_all(os_trash.join("info")).unwrap();
+    let desktop_file = work.path().join("from-desktop.txt");
+    fs::write(os_trash.join("files/from-desktop.txt"), "desktop").unwrap();
+    fs::write(
+        os_trash.join("info/from-desktop.txt.trashinfo"),
+        format!(
+            "[Trash Info]\nPath={}\nDeletionDate=2024-01-01T00:00:00\n",
+            desktop_file.display()
+        ),
+    )
+    .unwrap();
+
+    // And one trashed over SSH, which goes to the managed trash
+    let ssh_file = work.path().join("from-ssh.txt");
+    fs::write(&ssh_file, "ssh").unwrap();
+    detected()
+        .args(["-f", ssh_file.to_str().unwrap()])
+        .assert()
+        .success();
+
+    detected()
+        .arg("--list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[os] "))
+        .stdout(predicate::str::contains("[managed] "))
+        .stdout(predicate::str::contains("from-desktop.txt"))
+        .stdout(predicate::str::contains("from-ssh.txt"));
+
+    detected()
+        .args(["--restore", "-f", "from-"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&desktop_file).unwrap(), "desktop");
+    assert_eq!(fs::read_to_string(&ssh_file).unwrap(), "ssh");
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+#[test]
+fn test_migrate_between_managed_and_os_trash() {
+    let data_home = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let (_, trash_dir) = saferm_isolated();
+    let saferm = || {
+        let mut cmd = saferm_with_trash(&trash_dir);
+        cmd.env("XDG_DATA_HOME", data_home.path());
+        cmd
+    };
+
+    let file = work.path().join("notes.txt");
+    fs::write(&file, "notes").unwrap();
+    saferm()
+        .args(["-f", file.to_str().unwrap()])
+        .assert()
+        .success();
+    let id = fs::read_dir(trash_dir.path().join("files"))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .file_name();
+
+    // A dry run only reports
+    saferm()
+        .args(["--migrate", "--from", "managed", "--to", "os", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(file.to_str().unwrap()));
+    assert!(!data_home.path().join("Trash").exists());
+
+    saferm()
+        .args(["--migrate", "--from", "managed", "--to", "os"])
+        .assert()
+        .failure();
+    saferm()
+        .args(["--migrate", "--from", "managed", "--to", "os", "-f"])
+        .assert()
+        .success();
+    let os_trash = data_home.path().join("Trash");
+    assert_eq!(
+        fs::read_to_string(os_trash.join("files").join(&id)).unwrap(),
+        "notes"
+    );
+    let mut info_name = id.clone();
+    info_name.push(".trashinfo");
+    let info = fs::read_to_string(os_trash.join("info").join(info_name)).unwrap();
+    assert!(info.contains(&format!("Path={}\n", file.display())));
+    assert!(info.contains("DeletionDate="));
+    assert!(!trash_dir.path().join("files").join(&id).exists());
+
+    saferm()
+        .args(["--migrate", "--from", "os", "--to", "managed", "-f"])
+        .assert()
+        .success();
+    assert!(
+        fs::read_dir(os_trash.join("info"))
+            .unwrap()
+            .next()
+            .is_none()
+    );
+    saferm()
+        .args(["--restore", "-f", "notes"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "notes");
+}
+
+#[test]
+fn test_managed_trash_reads_xdg_home_trash() {
+    let data_home = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let (_, trash_dir) = saferm_isolated();
+    let saferm = || {
+        let mut cmd = saferm_with_trash(&trash_dir);
+        cmd.env("XDG_DATA_HOME", data_home.path())
+            .env("SAFERM_XDG_TRASH", "1");
+        cmd
+    };
+
+    // Items left by trash-cli, one with a percent-encoded path
+    let home_trash = data_home.path().join("Trash");
+    fs::create_dir_all(home_trash.join("files/old logs")).unwrap();
+    fs::create_dir_all(home_trash.join("info")).unwrap();
+    fs::write(home_trash.join("files/old logs/app.log"), "log").unwrap();
+    fs::write(home_trash.join("files/report 2019.txt"), "report").unwrap();
+    let report = work.path().join("report 2019.txt");
+    fs::write(
+        home_trash.join("info/report 2019.txt.trashinfo"),
+        format!(
+            "[Trash Info]\nPath={}\nDeletionDate=2019-06-01T12:00:00\n",
+            report.display().to_string().replace(' ', "%20")
+        ),
+    )
+    .unwrap();
+    let logs_info = home_trash.join("info/old logs.trashinfo");
+    fs::write(
+        &logs_info,
+        "[Trash Info]\nPath=/var/tmp/old%20logs\nDeletionDate=2019-06-01T12:00:00\n",
+    )
+    .unwrap();
+
+    saferm()
+        .arg("--list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("report 2019.txt"))
+        .stdout(predicate::str::contains("/var/tmp/old logs"));
+
+    saferm()
+        .args(["--restore", "-f", "report"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&report).unwrap(), "report");
+
+    saferm()
+        .args(["--purge", "-f", "--id", logs_info.to_str().unwrap()])
+        .assert()
+        .success();
+    assert!(!home_trash.join("files/old logs").exists());
+    assert!(!logs_info.exists());
+
+    // Without the compatibility mode the home trash is not read
+    fs::write(home_trash.join("files/report 2019.txt"), "again").unwrap();
+    fs::write(
+        home_trash.join("info/report 2019.txt.trashinfo"),
+        "[Trash Info]\nPath=/tmp/report%202019.txt\n",
+    )
+    .unwrap();
+    saferm_with_trash(&trash_dir)
+        .env("XDG_DATA_HOME", data_home.path())
+        .arg("--list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("report 2019.txt").not());
+}
+
+/// The sample helper in `tests/helpers/exec_helper.rs`, built by `cargo test`.
+fn exec_helper() -> std::path::PathBuf {
+    let deps = std::env::current_exe().unwrap();
+    let path = deps
+        .parent()
+        .and_then(|dir| dir.parent())
+        .unwrap()
+        .join("examples")
+        .join(format!("exec-helper{}", std::env::consts::EXE_SUFFIX));
+    assert!(
+        path.exists(),
+        "sample helper not built; run `cargo build --examples`"
+    );
+    path
+}
+
+#[test]
+fn test_exec_backend_helper() {
+    let store = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let backend = format!("exec:{}", exec_helper().display());
+    let saferm = || {
+        let mut cmd = cargo_bin_cmd!("saferm");
+        cmd.env("SAFERM_TRASH_BACKEND", &backend)
+            .env("EXEC_HELPER_STORE", store.path());
+        cmd
+    };
+
+    let file = work.path().join("draft.txt");
+    fs::write(&file, "draft").unwrap();
+    saferm()
+        .args(["-fv", "--tag", "docs", file.to_str().unwrap()])
+        .assert()
+        .success();
+    assert!(!file.exists());
+
+    saferm()
+        .args(["--list", "--tag", "docs"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(file.to_str().unwrap()))
+        .stdout(predicate::str::contains("#docs"));
+
+    saferm()
+        .args(["--restore", "-f", "draft"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "draft");
+
+    // A slow helper is cut off with the localized timeout message
+    saferm()
+        .env("EXEC_HELPER_DELAY", "5")
+        .env("SAFERM_EXEC_TIMEOUT", "1")
+        .env("LANG", "en_US.UTF-8")
+        .args(["-f", file.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("did not answer within 1s"));
+    assert!(file.exists());
+}
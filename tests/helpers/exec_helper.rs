@@ -0,0 +1,100 @@
+//! A sample helper for `SAFERM_TRASH_BACKEND=exec:/path/to/helper`.
+//!
+//! It keeps trashed items in the directory named by `EXEC_HELPER_STORE`:
+//! content under `files/<id>` and metadata under `info/<id>.json`. It reads
+//! one JSON request from stdin and answers with one JSON object on stdout.
+//! `EXEC_HELPER_DELAY` (seconds) makes it sleep first, to exercise timeouts.
+
+use serde_json::{Value, json};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    if let Some(delay) = std::env::var("EXEC_HELPER_DELAY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        std::thread::sleep(std::time::Duration::from_secs(delay));
+    }
+
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input).unwrap();
+    let response = match serde_json::from_str::<Value>(&input) {
+        Ok(request) => handle(&request).unwrap_or_else(|e| json!({"ok": false, "error": e})),
+        Err(e) => json!({"ok": false, "error": e.to_string()}),
+    };
+    println!("{}", response);
+}
+
+fn handle(request: &Value) -> Result<Value, String> {
+    if request["protocol"] != 1 {
+        return Err(format!("unsupported protocol {}", request["protocol"]));
+    }
+    let store = PathBuf::from(std::env::var("EXEC_HELPER_STORE").map_err(|e| e.to_string())?);
+    let files = store.join("files");
+    let info = store.join("info");
+    for dir in [&files, &info] {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let text = |key: &str| request[key].as_str().map(str::to_string);
+
+    match request["op"].as_str().unwrap_or_default() {
+        "backend_name" => Ok(json!({"ok": true, "name": "sample"})),
+        "trash" => {
+            let path = PathBuf::from(text("path").ok_or("missing path")?);
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            let id = format!("{}-{}", now.as_nanos(), std::process::id());
+            fs::rename(&path, files.join(&id)).map_err(|e| e.to_string())?;
+            let meta = json!({
+                "id": id,
+                "original_path": path,
+                "deleted_at": now.as_secs(),
+                "reason": request["reason"],
+                "tags": request["tags"],
+            });
+            fs::write(info.join(format!("{}.json", id)), meta.to_string())
+                .map_err(|e| e.to_string())?;
+            Ok(json!({"ok": true, "id": id}))
+        }
+        "list_restorable" => {
+            let filter = text("filter");
+            let tag = request["tag"].clone();
+            let mut items = vec![];
+            for entry in fs::read_dir(&info).map_err(|e| e.to_string())? {
+                let content = fs::read_to_string(entry.map_err(|e| e.to_string())?.path())
+                    .map_err(|e| e.to_string())?;
+                let item: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+                let path = item["original_path"].as_str().unwrap_or_default();
+                let matches_filter = filter.as_deref().is_none_or(|pat| path.contains(pat));
+                let matches_tag = tag.is_null()
+                    || item["tags"]
+                        .as_array()
+                        .is_some_and(|tags| tags.contains(&tag));
+                if matches_filter && matches_tag {
+                    items.push(item);
+                }
+            }
+            Ok(json!({"ok": true, "items": items}))
+        }
+        "restore_to" => {
+            let id = text("id").ok_or("missing id")?;
+            let destination = text("destination").ok_or("missing destination")?;
+            let content = files.join(&id);
+            if !content.exists() || id.contains('/') {
+                return Err(format!("no such item: {}", id));
+            }
+            fs::rename(&content, Path::new(&destination)).map_err(|e| e.to_string())?;
+            fs::remove_file(info.join(format!("{}.json", id))).map_err(|e| e.to_string())?;
+            Ok(json!({"ok": true}))
+        }
+        "cleanup" => {
+            for dir in [&files, &info] {
+                fs::remove_dir_all(dir).map_err(|e| e.to_string())?;
+            }
+            Ok(json!({"ok": true}))
+        }
+        other => Err(format!("unknown op {:?}", other)),
+    }
+}